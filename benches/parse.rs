@@ -0,0 +1,107 @@
+//! Parsing throughput on minimap2-like output with long `cg` and `cs` tags.
+//!
+//! Run with `cargo bench --bench parse`. Every benchmark builds owned
+//! records from the same input, so the numbers are comparable.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use nom::error::ErrorKind;
+
+use paf::bench::sample_input;
+use paf::complete::{paf_str, paf_u8, strict_paf_str};
+use paf::{PafReader, Strand};
+
+
+/// The mandatory columns of a record, as a hand-rolled parser would keep them.
+#[allow(dead_code)]
+struct Split {
+    query: String,
+    query_length: u64,
+    query_start: u64,
+    query_end: u64,
+    strand: Strand,
+    target: String,
+    target_length: u64,
+    target_start: u64,
+    target_end: u64,
+    nmatch: u64,
+    alnlen: u64,
+    mapq: u8,
+    fields: Vec<String>,
+}
+
+
+/// The usual hand-rolled parser: split on tabs and convert each column.
+fn split_line(line: &str) -> Option<Split> {
+    let mut columns = line.split('\t');
+    let mut next = || columns.next();
+    let number = |c: Option<&str>| c?.parse::<u64>().ok();
+
+    Some(Split {
+        query: next()?.to_string(),
+        query_length: number(next())?,
+        query_start: number(next())?,
+        query_end: number(next())?,
+        strand: match next()? {
+            "+" => Strand::Plus,
+            "-" => Strand::Minus,
+            _ => Strand::Unknown,
+        },
+        target: next()?.to_string(),
+        target_length: number(next())?,
+        target_start: number(next())?,
+        target_end: number(next())?,
+        nmatch: number(next())?,
+        alnlen: number(next())?,
+        mapq: next()?.parse().ok()?,
+        fields: columns.map(String::from).collect(),
+    })
+}
+
+
+fn parse(c: &mut Criterion) {
+    let input = sample_input(1_000, 42);
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+
+    group.bench_function("split", |b| {
+        b.iter(|| input.lines().filter_map(split_line).count())
+    });
+
+    group.bench_function("paf_str", |b| {
+        b.iter(|| {
+            input.lines()
+                .filter_map(|l| paf_str::<(&str, ErrorKind)>(l).ok())
+                .count()
+        })
+    });
+
+    group.bench_function("strict_paf_str", |b| {
+        b.iter(|| {
+            input.lines()
+                .filter_map(|l| strict_paf_str::<(&str, ErrorKind)>(l).ok())
+                .count()
+        })
+    });
+
+    group.bench_function("paf_u8", |b| {
+        b.iter(|| {
+            let mut rest = input.as_bytes();
+            let mut n = 0;
+            while let Ok((r, _)) = paf_u8::<(&[u8], ErrorKind)>(rest) {
+                rest = r;
+                n += 1;
+            }
+            n
+        })
+    });
+
+    group.bench_function("reader", |b| {
+        b.iter(|| PafReader::new(input.as_bytes()).filter_map(Result::ok).count())
+    });
+
+    group.finish();
+}
+
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+
+
+/// A BED interval with any trailing columns kept verbatim.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct BedRecord {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+    pub name: Option<String>,
+    pub rest: Vec<String>,
+}
+
+
+impl BedRecord {
+    /// Construct a new three column `BedRecord`.
+    pub fn new(chrom: String, start: u64, end: u64) -> Self {
+        BedRecord { chrom, start, end, name: None, rest: Vec::new() }
+    }
+
+    /// The strand column, if present.
+    pub fn strand(&self) -> Option<&str> {
+        self.rest.get(1).map(|s| s.as_str())
+    }
+}
+
+
+impl fmt::Display for BedRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", self.chrom, self.start, self.end)?;
+        if let Some(name) = &self.name {
+            write!(f, "\t{}", name)?;
+        }
+        for column in &self.rest {
+            write!(f, "\t{}", column)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Parse a single BED line, attributing any error to `line_num`.
+fn parse_bed_line(line: &str, line_num: usize) -> Result<BedRecord, Error> {
+    let mut columns = line.split('\t');
+    let err = |message: &str| Error::ParseBed { line_num, message: message.to_string() };
+
+    let chrom = columns.next().filter(|c| !c.is_empty()).ok_or_else(|| err("missing chrom"))?;
+    let start = columns
+        .next()
+        .ok_or_else(|| err("missing start"))?
+        .parse()
+        .map_err(|_| err("expected an unsigned integer in column: start"))?;
+    let end = columns
+        .next()
+        .ok_or_else(|| err("missing end"))?
+        .parse()
+        .map_err(|_| err("expected an unsigned integer in column: end"))?;
+
+    if start > end {
+        return Err(err("start is greater than end"));
+    }
+
+    Ok(BedRecord {
+        chrom: chrom.to_string(),
+        start,
+        end,
+        name: columns.next().map(String::from),
+        rest: columns.map(String::from).collect(),
+    })
+}
+
+
+/// Read all intervals from a BED source.
+///
+/// Blank lines and `#`, `track` and `browser` header lines are skipped.
+pub fn read_bed<R: BufRead>(reader: R) -> Result<Vec<BedRecord>, Error> {
+    let mut output = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.context(Io)?;
+        let line = line.trim_end_matches('\r');
+
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+
+        output.push(parse_bed_line(line, i + 1)?);
+    }
+    Ok(output)
+}
+
+
+
+/// Write intervals as BED lines.
+pub fn write_bed<W: Write>(records: &[BedRecord], mut writer: W) -> Result<(), Error> {
+    for record in records {
+        writeln!(writer, "{}", record).context(Io)?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_bed() {
+        let input = "# header\nchr1\t10\t20\nchr2\t0\t5\tgene1\t0\t-\n";
+        let records = read_bed(input.as_bytes()).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], BedRecord::new("chr1".to_string(), 10, 20));
+        assert_eq!(records[1].name.as_deref(), Some("gene1"));
+        assert_eq!(records[1].strand(), Some("-"));
+        assert_eq!(records[1].to_string(), "chr2\t0\t5\tgene1\t0\t-");
+
+        match read_bed("chr1\t20\tten\n".as_bytes()) {
+            Err(Error::ParseBed { line_num, .. }) => assert_eq!(line_num, 1),
+            e => panic!("Expected a BED parse error, got {:?}", e),
+        }
+    }
+}
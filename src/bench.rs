@@ -0,0 +1,203 @@
+//! Measuring parsing speed on realistic input.
+//!
+//! [`sample_input`] builds minimap2-like output with long `cg` and `cs`
+//! tags, and [`parse_throughput`] times a [`PafReader`] over any input.
+//! The criterion suite in `benches/parse.rs` compares the `&str`, `&[u8]`
+//! and reader parsers against hand-rolled splitting on the same input, see
+//! the README for how to run it.
+
+use std::fmt::Write as _;
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+use crate::errors::Error;
+use crate::reader::PafReader;
+
+
+const BASES: [char; 4] = ['a', 'c', 'g', 't'];
+
+
+/// A small deterministic generator, so samples are the same on every run.
+struct SplitMix(u64);
+
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// An integer in `low..high`.
+    fn range(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next() % (high - low)
+    }
+
+    fn base(&mut self) -> char {
+        BASES[self.range(0, 4) as usize]
+    }
+
+    /// A target and query base that differ, for a substitution.
+    fn mismatch(&mut self) -> (char, char) {
+        let target = self.range(0, 4);
+        let query = (target + self.range(1, 4)) % 4;
+        (BASES[target as usize], BASES[query as usize])
+    }
+}
+
+
+/// Append one minimap2-like line, with consistent `cg` and `cs` tags.
+fn sample_line(rng: &mut SplitMix, i: usize, output: &mut String) {
+    let (mut cg, mut cs) = (String::new(), String::new());
+    let (mut qspan, mut tspan, mut nmatch, mut alnlen, mut edits) = (0, 0, 0, 0, 0);
+    let (mut run, mut exact) = (0, 0);
+
+    for _ in 0..rng.range(20, 120) {
+        let matches = rng.range(20, 400);
+        run += matches;
+        exact += matches;
+        nmatch += matches;
+
+        match rng.range(0, 4) {
+            0 | 1 => {
+                let (target, query) = rng.mismatch();
+                let _ = write!(cs, ":{}*{}{}", exact, target, query);
+                run += 1;
+                exact = 0;
+                edits += 1;
+            },
+            kind => {
+                let len = rng.range(1, 12);
+                let (op, sign) = if kind == 2 { ('I', '+') } else { ('D', '-') };
+                let _ = write!(cg, "{}M{}{}", run, len, op);
+                let _ = write!(cs, ":{}{}", exact, sign);
+                (0..len).for_each(|_| cs.push(rng.base()));
+                qspan += run + if op == 'I' { len } else { 0 };
+                tspan += run + if op == 'D' { len } else { 0 };
+                alnlen += run + len;
+                edits += len;
+                run = 0;
+                exact = 0;
+            },
+        }
+    }
+
+    let matches = rng.range(20, 400);
+    run += matches;
+    exact += matches;
+    nmatch += matches;
+    let _ = write!(cg, "{}M", run);
+    let _ = write!(cs, ":{}", exact);
+    qspan += run;
+    tspan += run;
+    alnlen += run;
+
+    let qlen = qspan + rng.range(0, 2_000);
+    let qstart = rng.range(0, qlen - qspan + 1);
+    let tlen = 10_000_000 + rng.range(0, 90_000_000);
+    let tstart = rng.range(0, tlen - tspan + 1);
+    let strand = if rng.range(0, 2) == 0 { '+' } else { '-' };
+
+    let _ = writeln!(
+        output,
+        "read_{}\t{}\t{}\t{}\t{}\tchr{}\t{}\t{}\t{}\t{}\t{}\t60\tNM:i:{}\tms:i:{}\tAS:i:{}\tnn:i:0\t\
+         tp:A:P\tcm:i:{}\ts1:i:{}\ts2:i:0\tde:f:{:.4}\trl:i:0\tcg:Z:{}\tcs:Z:{}",
+        i, qlen, qstart, qstart + qspan, strand,
+        1 + i % 22, tlen, tstart, tstart + tspan,
+        nmatch, alnlen, edits, nmatch * 2, nmatch as i64 * 2 - edits as i64 * 4,
+        nmatch / 20, nmatch / 10, edits as f64 / alnlen as f64, cg, cs
+    );
+}
+
+
+/// Generate `n` lines resembling minimap2 `-c --cs` output of long reads.
+///
+/// Every line has the usual minimap2 tags and a `cg` and `cs` tag of a
+/// few hundred to a few thousand bytes, consistent with its spans. The
+/// same `seed` always gives the same lines.
+pub fn sample_input(n: usize, seed: u64) -> String {
+    let mut rng = SplitMix(seed);
+    let mut output = String::new();
+    for i in 0..n {
+        sample_line(&mut rng, i, &mut output);
+    }
+    output
+}
+
+
+/// The result of a [`parse_throughput`] run.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct Throughput {
+    /// The number of records parsed.
+    pub records: u64,
+    /// The number of bytes read.
+    pub bytes: u64,
+    /// The time taken.
+    pub elapsed: Duration,
+}
+
+
+impl Throughput {
+    /// Records parsed per second.
+    pub fn records_per_second(&self) -> f64 {
+        self.records as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Megabytes (10^6 bytes) read per second.
+    pub fn megabytes_per_second(&self) -> f64 {
+        self.bytes as f64 / 1e6 / self.elapsed.as_secs_f64()
+    }
+}
+
+
+/// Time parsing every record from `reader` with a default [`PafReader`].
+///
+/// This measures the whole path from bytes to owned records, including
+/// tag validation, so it is a fair comparison with a hand-rolled parser
+/// that also builds records. Pass input held in memory, e.g. from
+/// [`sample_input`], to leave IO out of the measurement.
+pub fn parse_throughput<R: BufRead>(reader: R) -> Result<Throughput, Error> {
+    let mut reader = PafReader::new(reader);
+    let start = Instant::now();
+    while reader.read_record()?.is_some() {}
+    let elapsed = start.elapsed();
+
+    let progress = reader.progress();
+    Ok(Throughput { records: progress.records, bytes: progress.bytes, elapsed })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cs::CsOp;
+    use crate::paf::PAF;
+
+    #[test]
+    fn test_sample_input() {
+        let input = sample_input(20, 1);
+        assert_eq!(input, sample_input(20, 1));
+
+        for line in input.lines() {
+            let record: PAF = line.parse().unwrap();
+            let cigar = record.cigar().unwrap();
+            let (qlen, tlen) = (cigar.query_len().unwrap(), cigar.target_len().unwrap());
+            assert_eq!(qlen, record.query().end() - record.query().start());
+            assert_eq!(tlen, record.target().end() - record.target().start());
+            let cs = record.cs().unwrap();
+            assert_eq!((cs.query_len(), cs.target_len()), (qlen, tlen));
+
+            for op in cs.ops() {
+                if let CsOp::Substitution { target, query } = op {
+                    assert_ne!(target, query);
+                }
+            }
+        }
+
+        let throughput = parse_throughput(input.as_bytes()).unwrap();
+        assert_eq!(throughput.records, 20);
+        assert_eq!(throughput.bytes, input.len() as u64);
+    }
+}
@@ -0,0 +1,397 @@
+//! Writing of blocked gzip (BGZF) files, as produced by `bgzip`.
+//!
+//! BGZF files are a series of independently compressed gzip members of at
+//! most 64 KiB each, so a position in the uncompressed stream can be found
+//! from a "virtual offset": the compressed offset of its block shifted left
+//! 16 bits, plus the offset within the uncompressed block.
+//!
+//! [`BgzfReader`] can seek directly to such an offset, which is what allows
+//! region queries against an index.
+
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+
+
+/// The largest uncompressed payload put in a single block.
+pub(crate) const MAX_BLOCK_DATA: usize = 0xff00;
+
+/// The largest uncompressed size of a block allowed by the format.
+const MAX_BLOCK_SIZE: usize = 0x10000;
+
+/// The empty block marking the end of a BGZF file.
+pub(crate) const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43,
+    0x02, 0x00, 0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+
+/// Compress a stream into BGZF blocks.
+///
+/// The end-of-file marker is written by [`BgzfWriter::finish`], or on drop
+/// if `finish` was not called, in which case errors are ignored.
+pub struct BgzfWriter<W: Write> {
+    inner: Option<W>,
+    buffer: Vec<u8>,
+    compressed_offset: u64,
+    level: Compression,
+}
+
+
+impl<W: Write> BgzfWriter<W> {
+    /// Construct a new writer with the default compression level.
+    pub fn new(inner: W) -> Self {
+        BgzfWriter {
+            inner: Some(inner),
+            buffer: Vec::with_capacity(MAX_BLOCK_DATA),
+            compressed_offset: 0,
+            level: Compression::default(),
+        }
+    }
+
+    /// The virtual offset of the next byte to be written.
+    pub fn virtual_offset(&self) -> u64 {
+        (self.compressed_offset << 16) | self.buffer.len() as u64
+    }
+
+    /// Compress any buffered data into a block, even if the block is small.
+    ///
+    /// This is useful to start a new block at a record boundary.
+    pub fn flush_block(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let block = compress_block(&self.buffer, self.level)?;
+        self.inner_mut().write_all(&block)?;
+        self.compressed_offset += block.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Write the remaining data and the end-of-file marker, returning the
+    /// underlying sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_eof()?;
+        Ok(self.inner.take().expect("inner is only taken by finish"))
+    }
+
+    fn write_eof(&mut self) -> io::Result<()> {
+        self.flush_block()?;
+        self.inner_mut().write_all(&EOF_BLOCK)?;
+        self.inner_mut().flush()
+    }
+
+    fn inner_mut(&mut self) -> &mut W {
+        self.inner.as_mut().expect("inner is only taken by finish")
+    }
+}
+
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(MAX_BLOCK_DATA - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+
+        if self.buffer.len() == MAX_BLOCK_DATA {
+            self.flush_block()?;
+        }
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_block()?;
+        self.inner_mut().flush()
+    }
+}
+
+
+impl<W: Write> Drop for BgzfWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_eof();
+        }
+    }
+}
+
+
+/// Decompress a BGZF stream block by block, tracking virtual offsets.
+pub struct BgzfReader<R> {
+    inner: R,
+    block: Vec<u8>,
+    pos: usize,
+    block_offset: u64,
+    next_block_offset: u64,
+    eof_marker: bool,
+}
+
+
+impl<R: Read> BgzfReader<R> {
+    /// Construct a new reader positioned at the start of the stream.
+    pub fn new(inner: R) -> Self {
+        BgzfReader {
+            inner,
+            block: Vec::new(),
+            pos: 0,
+            block_offset: 0,
+            next_block_offset: 0,
+            eof_marker: false,
+        }
+    }
+
+    /// The virtual offset of the next byte to be read.
+    pub fn virtual_offset(&self) -> u64 {
+        (self.block_offset << 16) | self.pos as u64
+    }
+
+    /// Whether the last block read was the empty end-of-file marker.
+    ///
+    /// `bgzip` always ends a file with this marker, so if it is missing once
+    /// the stream is exhausted the file has probably been truncated at a
+    /// block boundary.
+    pub fn has_eof_marker(&self) -> bool {
+        self.eof_marker
+    }
+
+    /// Read and decompress the next block, returning false at the end.
+    ///
+    /// The end is only where no more bytes follow a whole block. A block cut
+    /// short anywhere, including in its header, is an `UnexpectedEof` error.
+    fn read_block(&mut self) -> io::Result<bool> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut header = [0u8; 12];
+        let mut filled = 0;
+        while filled < header.len() {
+            match self.inner.read(&mut header[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "BGZF block header is truncated",
+                    ));
+                },
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+
+        if header[..4] != [0x1f, 0x8b, 0x08, 0x04] {
+            return Err(invalid("not a BGZF block"));
+        }
+
+        let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+        let mut extra = vec![0u8; xlen];
+        self.inner.read_exact(&mut extra)?;
+
+        let mut bsize = None;
+        let mut i = 0;
+        while i + 4 <= extra.len() {
+            let slen = u16::from_le_bytes([extra[i + 2], extra[i + 3]]) as usize;
+            if extra[i] == b'B' && extra[i + 1] == b'C' && slen == 2 && i + 6 <= extra.len() {
+                bsize = Some(u16::from_le_bytes([extra[i + 4], extra[i + 5]]) as usize);
+            }
+            i += 4 + slen;
+        }
+
+        let bsize = bsize.ok_or_else(|| invalid("BGZF block is missing its size field"))?;
+        let remaining = (bsize + 1)
+            .checked_sub(12 + xlen)
+            .filter(|r| *r >= 8)
+            .ok_or_else(|| invalid("BGZF block size is too small"))?;
+
+        let mut rest = vec![0u8; remaining];
+        self.inner.read_exact(&mut rest)?;
+
+        let (cdata, footer) = rest.split_at(remaining - 8);
+        let crc32 = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]);
+        let isize = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]) as usize;
+
+        if isize > MAX_BLOCK_SIZE {
+            return Err(invalid("BGZF block is too large"));
+        }
+
+        self.block.clear();
+        self.block.reserve(isize);
+        DeflateDecoder::new(cdata)
+            .take(isize as u64 + 1)
+            .read_to_end(&mut self.block)?;
+
+        if self.block.len() != isize {
+            return Err(invalid("BGZF block has the wrong uncompressed size"));
+        }
+
+        let mut crc = Crc::new();
+        crc.update(&self.block);
+        if crc.sum() != crc32 {
+            return Err(invalid("BGZF block failed its CRC32 check"));
+        }
+
+        self.eof_marker = isize == 0;
+
+        self.block_offset = self.next_block_offset;
+        self.next_block_offset += (bsize + 1) as u64;
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+
+impl<R: Read + Seek> BgzfReader<R> {
+    /// Seek to a virtual offset.
+    pub fn seek_virtual(&mut self, offset: u64) -> io::Result<()> {
+        let coffset = offset >> 16;
+        let uoffset = (offset & 0xffff) as usize;
+
+        self.inner.seek(SeekFrom::Start(coffset))?;
+        self.next_block_offset = coffset;
+        self.block.clear();
+        self.pos = 0;
+        self.eof_marker = false;
+
+        if self.read_block()? && uoffset <= self.block.len() {
+            self.pos = uoffset;
+            Ok(())
+        } else if uoffset == 0 {
+            // Seeking to the end of the stream.
+            self.block_offset = coffset;
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "virtual offset is out of range"))
+        }
+    }
+}
+
+
+impl<R: Read> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+
+impl<R: Read> BufRead for BgzfReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // Loop as empty blocks, like the EOF marker, may occur anywhere.
+        while self.pos >= self.block.len() {
+            if !self.read_block()? {
+                break;
+            }
+        }
+        Ok(&self.block[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.block.len());
+    }
+}
+
+
+/// Compress `data` as a single BGZF block.
+fn compress_block(data: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(data.len()), level);
+    encoder.write_all(data)?;
+    let cdata = encoder.finish()?;
+
+    let mut crc = Crc::new();
+    crc.update(data);
+
+    // The 18 byte header, compressed data, then an 8 byte footer.
+    let bsize = cdata.len() + 25;
+    assert!(bsize < 0x10000, "compressed BGZF block is too large");
+
+    let mut block = Vec::with_capacity(bsize + 1);
+    block.extend_from_slice(&[0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0]);
+    block.extend_from_slice(&(bsize as u16).to_le_bytes());
+    block.extend_from_slice(&cdata);
+    block.extend_from_slice(&crc.sum().to_le_bytes());
+    block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    Ok(block)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_bgzf_round_trip() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut writer = BgzfWriter::new(Vec::new());
+        writer.write_all(&data[..10]).unwrap();
+        assert_eq!(writer.virtual_offset(), 10);
+        writer.flush_block().unwrap();
+        let offset = writer.virtual_offset();
+        assert_eq!(offset & 0xffff, 0);
+        assert!(offset >> 16 > 0);
+
+        writer.write_all(&data[10..]).unwrap();
+        let compressed = writer.finish().unwrap();
+        assert!(compressed.ends_with(&EOF_BLOCK));
+
+        let mut decoded = Vec::new();
+        flate2::read::MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+
+        let mut reader = BgzfReader::new(std::io::Cursor::new(&compressed));
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, data);
+
+        reader.seek_virtual(offset).unwrap();
+        let mut buf = [0u8; 5];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &data[10..15]);
+    }
+
+    #[test]
+    fn test_bgzf_corrupt() {
+        let mut writer = BgzfWriter::new(Vec::new());
+        writer.write_all(b"first block\n").unwrap();
+        writer.flush_block().unwrap();
+        let second = (writer.virtual_offset() >> 16) as usize;
+        writer.write_all(b"second block\n").unwrap();
+        let compressed = writer.finish().unwrap();
+
+        let read = |data: &[u8]| -> (io::Result<Vec<u8>>, bool) {
+            let mut reader = BgzfReader::new(data);
+            let mut decoded = Vec::new();
+            let result = reader.read_to_end(&mut decoded).map(|_| decoded);
+            (result, reader.has_eof_marker())
+        };
+
+        let (result, marker) = read(&compressed);
+        assert_eq!(result.unwrap(), b"first block\nsecond block\n");
+        assert!(marker);
+
+        // Cut at a block boundary, the data is fine but the marker is missing.
+        let (result, marker) = read(&compressed[..second]);
+        assert_eq!(result.unwrap(), b"first block\n");
+        assert!(!marker);
+
+        // Cut part way through a block header.
+        let (result, _) = read(&compressed[..second + 5]);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // A wrong checksum, then an uncompressed size over the limit.
+        let mut bad = compressed.clone();
+        bad[second - 8] ^= 0xff;
+        assert!(read(&bad).0.is_err());
+
+        let mut bad = compressed.clone();
+        bad[second - 2] = 0x10;
+        assert_eq!(read(&bad).0.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
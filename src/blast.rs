@@ -0,0 +1,89 @@
+//! Conversion to BLAST tabular output (`-outfmt 6`).
+
+use crate::cigar::CigarKind;
+use crate::paf::{Strand, PAF};
+
+
+impl PAF {
+    /// Format the record as a 12-column BLAST tabular line.
+    ///
+    /// The columns are query, subject, percent identity, alignment length,
+    /// mismatches, gap opens, 1-based query start and end, 1-based subject
+    /// start and end, e-value and bit score. Minus strand hits have the
+    /// subject start after the end, as BLAST writes them.
+    ///
+    /// PAF has no e-value, so it is written as 0, and the bit score column
+    /// is [`PAF::score`]. Mismatches and gap opens are counted from the `cg`
+    /// tag if present, where `M` operations count as mismatches only for
+    /// bases not covered by the number of matches column. Without a CIGAR,
+    /// every non-matching column counts as a mismatch and gap opens are 0.
+    pub fn to_blast_tab(&self) -> String {
+        let (mismatches, gap_opens) = match self.cigar() {
+            Some(cigar) => {
+                let mut aligned = 0;
+                let mut diffs = 0;
+                let mut has_m = false;
+                let mut gap_opens = 0;
+                for op in cigar.ops() {
+                    match op.kind() {
+                        CigarKind::Match => {
+                            has_m = true;
+                            aligned += op.len();
+                        },
+                        CigarKind::Equal => aligned += op.len(),
+                        CigarKind::Diff => {
+                            aligned += op.len();
+                            diffs += op.len();
+                        },
+                        CigarKind::Insertion | CigarKind::Deletion => gap_opens += 1,
+                        _ => {},
+                    }
+                }
+
+                let mismatches = if has_m { aligned.saturating_sub(self.nmatch()) } else { diffs };
+                (mismatches, gap_opens)
+            },
+            None => (self.alnlen().saturating_sub(self.nmatch()), 0),
+        };
+
+        let target = self.target();
+        let (sstart, send) = match self.strand() {
+            Strand::Minus => (target.end(), target.start() + 1),
+            _ => (target.start() + 1, target.end()),
+        };
+
+        format!(
+            "{}\t{}\t{:.3}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t0\t{}",
+            self.query().name(),
+            target.name(),
+            self.identity() * 100.0,
+            self.alnlen(),
+            mismatches,
+            gap_opens,
+            self.query().start() + 1,
+            self.query().end(),
+            sstart,
+            send,
+            self.score(),
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_to_blast_tab() {
+        let record = paf("q1\t100\t10\t30\t+\tt1\t1000\t100\t121\t17\t23\t60\tcg:Z:5=1X4=2I5=3D3=\tAS:i:30");
+        assert_eq!(record.to_blast_tab(), "q1\tt1\t73.913\t23\t1\t2\t11\t30\t101\t121\t0\t30");
+
+        let record = paf("q1\t100\t0\t10\t-\tt1\t1000\t100\t110\t9\t10\t60");
+        assert_eq!(record.to_blast_tab(), "q1\tt1\t90.000\t10\t1\t0\t1\t10\t110\t101\t0\t9");
+    }
+}
@@ -0,0 +1,508 @@
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use nom::combinator::{cut, all_consuming};
+
+use crate::errors::Error;
+use crate::paf::{Locus, Strand, PAF};
+
+
+/// The kind of a CIGAR operation.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub enum CigarKind {
+    /// Alignment match or mismatch (`M`).
+    Match,
+    /// Insertion to the target, present in the query (`I`).
+    Insertion,
+    /// Deletion from the target, absent in the query (`D`).
+    Deletion,
+    /// Skipped target region, e.g. an intron (`N`).
+    Skip,
+    /// Soft clipped query (`S`).
+    SoftClip,
+    /// Hard clipped query (`H`).
+    HardClip,
+    /// Silent padding (`P`).
+    Padding,
+    /// Sequence match (`=`).
+    Equal,
+    /// Sequence mismatch (`X`).
+    Diff,
+}
+
+
+impl CigarKind {
+    /// Whether the operation consumes query bases.
+    pub fn consumes_query(self) -> bool {
+        matches!(
+            self,
+            CigarKind::Match | CigarKind::Insertion | CigarKind::SoftClip
+                | CigarKind::Equal | CigarKind::Diff
+        )
+    }
+
+    /// Whether the operation consumes target bases.
+    pub fn consumes_target(self) -> bool {
+        matches!(
+            self,
+            CigarKind::Match | CigarKind::Deletion | CigarKind::Skip
+                | CigarKind::Equal | CigarKind::Diff
+        )
+    }
+}
+
+
+impl From<CigarKind> for char {
+    fn from(kind: CigarKind) -> Self {
+        match kind {
+            CigarKind::Match => 'M',
+            CigarKind::Insertion => 'I',
+            CigarKind::Deletion => 'D',
+            CigarKind::Skip => 'N',
+            CigarKind::SoftClip => 'S',
+            CigarKind::HardClip => 'H',
+            CigarKind::Padding => 'P',
+            CigarKind::Equal => '=',
+            CigarKind::Diff => 'X',
+        }
+    }
+}
+
+
+impl TryFrom<char> for CigarKind {
+    type Error = Error;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            'M' => Ok(CigarKind::Match),
+            'I' => Ok(CigarKind::Insertion),
+            'D' => Ok(CigarKind::Deletion),
+            'N' => Ok(CigarKind::Skip),
+            'S' => Ok(CigarKind::SoftClip),
+            'H' => Ok(CigarKind::HardClip),
+            'P' => Ok(CigarKind::Padding),
+            '=' => Ok(CigarKind::Equal),
+            'X' => Ok(CigarKind::Diff),
+            c   => Err(Error::ParseChar { got: c, expected: "MIDNSHP=X".to_string() })
+        }
+    }
+}
+
+
+/// A single CIGAR operation with its length.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub struct CigarOp {
+    kind: CigarKind,
+    len: u64,
+}
+
+
+impl CigarOp {
+    /// Construct a new `CigarOp` object.
+    pub fn new(kind: CigarKind, len: u64) -> Self {
+        CigarOp { kind, len }
+    }
+
+    /// The kind of operation.
+    pub fn kind(&self) -> CigarKind {
+        self.kind
+    }
+
+    /// The number of bases the operation spans.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the operation spans no bases.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+
+impl fmt::Display for CigarOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.len, char::from(self.kind))
+    }
+}
+
+
+/// A CIGAR string, as stored in the `cg` tag.
+#[derive(Debug, Eq, PartialEq, Clone, Hash, Default)]
+pub struct Cigar(Vec<CigarOp>);
+
+
+impl Cigar {
+    /// Construct a new `Cigar` object.
+    pub fn new(ops: Vec<CigarOp>) -> Self {
+        Cigar(ops)
+    }
+
+    /// The operations, in target order.
+    pub fn ops(&self) -> &[CigarOp] {
+        &self.0
+    }
+
+    /// The number of query bases spanned.
+    ///
+    /// Returns `Error::InvalidAlignment` if the total overflows a `u64`.
+    pub fn query_len(&self) -> Result<u64, Error> {
+        self.span(CigarKind::consumes_query, "query")
+    }
+
+    /// The number of target bases spanned.
+    ///
+    /// Returns `Error::InvalidAlignment` if the total overflows a `u64`.
+    pub fn target_len(&self) -> Result<u64, Error> {
+        self.span(CigarKind::consumes_target, "target")
+    }
+
+    /// Sum the lengths of the operations that consume a sequence.
+    fn span(&self, consumes: fn(CigarKind) -> bool, what: &str) -> Result<u64, Error> {
+        self.0
+            .iter()
+            .filter(|o| consumes(o.kind))
+            .try_fold(0u64, |total, o| total.checked_add(o.len))
+            .ok_or_else(|| Error::InvalidAlignment {
+                message: format!("the CIGAR spans more {} bases than fit in a u64", what),
+            })
+    }
+}
+
+
+impl From<Vec<CigarOp>> for Cigar {
+    fn from(ops: Vec<CigarOp>) -> Self {
+        Cigar(ops)
+    }
+}
+
+
+impl fmt::Display for Cigar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for op in &self.0 {
+            write!(f, "{}", op)?;
+        }
+        Ok(())
+    }
+}
+
+
+impl FromStr for Cigar {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, cigar) = all_consuming(cut(crate::complete::cigar_str))(s)
+            .map_err(|e| {
+                match e {
+                    nom::Err::Error(ei) | nom::Err::Failure(ei) => {
+                        crate::errors::convert_error_str(s, ei, None)
+                    },
+                    _ => unreachable!(),
+                }
+            })?;
+
+        Ok(cigar)
+    }
+}
+
+
+/// A gap-free aligned segment, with forward strand coordinates on both the
+/// query and the target.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub struct Block {
+    pub query_start: u64,
+    pub query_end: u64,
+    pub target_start: u64,
+    pub target_end: u64,
+}
+
+
+impl Block {
+    /// The half-open interval of the block on the query.
+    pub fn query(&self) -> Range<u64> {
+        self.query_start..self.query_end
+    }
+
+    /// The half-open interval of the block on the target.
+    pub fn target(&self) -> Range<u64> {
+        self.target_start..self.target_end
+    }
+
+    /// The length of the block.
+    pub fn len(&self) -> u64 {
+        self.target_end - self.target_start
+    }
+
+    /// Whether the block has length 0.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+
+/// Compute the gap-free blocks of an alignment from its CIGAR.
+///
+/// For minus strand alignments the query coordinates of successive blocks
+/// decrease, as the CIGAR runs along the reverse complement of the query.
+/// Returns `Error::InvalidAlignment` if the CIGAR would run past `u64::MAX`
+/// on either sequence.
+pub(crate) fn blocks(record: &PAF, cigar: &Cigar) -> Result<Vec<Block>, Error> {
+    // With the ends in range, no offset below can overflow.
+    let overflow = |what: &str| Error::InvalidAlignment {
+        message: format!("the CIGAR runs past the largest {} coordinate", what),
+    };
+    record.query().start().checked_add(cigar.query_len()?).ok_or_else(|| overflow("query"))?;
+    record.target().start().checked_add(cigar.target_len()?).ok_or_else(|| overflow("target"))?;
+
+    let mut qoff = 0;
+    let mut toff = record.target().start();
+    let mut output = Vec::new();
+
+    for op in cigar.ops() {
+        let kind = op.kind();
+        if kind.consumes_query() && kind.consumes_target() {
+            let (query_start, query_end) = match record.strand() {
+                Strand::Minus => {
+                    let end = record.query().end().saturating_sub(qoff);
+                    (end.saturating_sub(op.len()), end)
+                },
+                _ => {
+                    let start = record.query().start() + qoff;
+                    (start, start + op.len())
+                },
+            };
+
+            output.push(Block {
+                query_start,
+                query_end,
+                target_start: toff,
+                target_end: toff + op.len(),
+            });
+        }
+
+        if kind.consumes_query() {
+            qoff += op.len();
+        }
+        if kind.consumes_target() {
+            toff += op.len();
+        }
+    }
+
+    Ok(output)
+}
+
+
+/// Merge blocks that are contiguous on both sequences, e.g. from `5=1X4=`.
+pub(crate) fn merge_blocks(blocks: Vec<Block>, minus: bool) -> Vec<Block> {
+    let mut merged: Vec<Block> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        match merged.last_mut() {
+            Some(last) if last.target_end == block.target_start
+                && ((!minus && last.query_end == block.query_start)
+                    || (minus && last.query_start == block.query_end)) => {
+                last.target_end = block.target_end;
+                if minus {
+                    last.query_start = block.query_start;
+                } else {
+                    last.query_end = block.query_end;
+                }
+            },
+            _ => merged.push(block),
+        }
+    }
+    merged
+}
+
+
+impl PAF {
+    /// The gap-free segments of the alignment, from the `cg` tag.
+    ///
+    /// Adjacent `=` and `X` operations form a single block. Blocks are in
+    /// target order; for minus strand alignments the query intervals
+    /// decrease. Returns `None` if there is no valid `cg` tag, or it runs
+    /// past the largest coordinate.
+    pub fn blocks(&self) -> Option<Vec<Block>> {
+        let cigar = self.cigar()?;
+        let blocks = blocks(self, &cigar).ok()?;
+        Some(merge_blocks(blocks, self.strand() == Strand::Minus))
+    }
+
+    /// Build a record from an aligner's result.
+    ///
+    /// The ends, number of matches and alignment length are computed from
+    /// `alignment`, which is stored in a `cg` tag. `qstart` and `tstart` are
+    /// where the aligned region starts, in forward strand coordinates for
+    /// both sequences as usual. Clipping and padding operations are left
+    /// out, as they are not part of the aligned region.
+    ///
+    /// For [`Strand::Minus`], `alignment` runs along the forward strand of
+    /// the target, so it aligns the reverse complement of the query, as in
+    /// the `cg` tags written by minimap2.
+    ///
+    /// `M` operations can't tell matches from mismatches, so they count as
+    /// matches. With only `=`, `X`, `I`, `D` and `N` operations the counts
+    /// are exact and an `NM` tag with the edit distance is added too. The
+    /// mapping quality is 255, for missing, until set with
+    /// [`PAF::set_mapq`].
+    ///
+    /// Returns `Error::InvalidInterval` if either end is past the sequence
+    /// length, or would overflow, and `Error::InvalidAlignment` if the CIGAR
+    /// spans more than `u64::MAX` bases.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_alignment(
+        query_name: String,
+        target_name: String,
+        strand: Strand,
+        alignment: &[CigarOp],
+        qlen: u64,
+        tlen: u64,
+        qstart: u64,
+        tstart: u64,
+    ) -> Result<PAF, Error> {
+        let ops: Vec<CigarOp> = alignment
+            .iter()
+            .filter(|o| !matches!(
+                o.kind(),
+                CigarKind::SoftClip | CigarKind::HardClip | CigarKind::Padding
+            ))
+            .cloned()
+            .collect();
+        let cigar = Cigar::new(ops);
+
+        let query = aligned_locus(query_name, qlen, qstart, cigar.query_len()?)?;
+        let target = aligned_locus(target_name, tlen, tstart, cigar.target_len()?)?;
+
+        // Matches fit within the target span, but the alignment length and
+        // edits count both sequences.
+        let (mut nmatch, mut alnlen, mut edits, mut exact) = (0, 0u64, 0u64, true);
+        for op in cigar.ops() {
+            match op.kind() {
+                CigarKind::Match => {
+                    nmatch += op.len();
+                    alnlen = alnlen.saturating_add(op.len());
+                    exact = false;
+                },
+                CigarKind::Equal => {
+                    nmatch += op.len();
+                    alnlen = alnlen.saturating_add(op.len());
+                },
+                CigarKind::Diff | CigarKind::Insertion | CigarKind::Deletion => {
+                    alnlen = alnlen.saturating_add(op.len());
+                    edits = edits.saturating_add(op.len());
+                },
+                _ => {},
+            }
+        }
+
+        let mut fields = Vec::new();
+        if exact {
+            fields.push(format!("NM:i:{}", edits));
+        }
+        fields.push(format!("cg:Z:{}", cigar));
+
+        Ok(PAF::new(query, strand, target, nmatch, alnlen, 255, fields))
+    }
+}
+
+
+/// A locus of `span` bases from `start`, returning `Error::InvalidInterval`
+/// if it doesn't fit in the sequence.
+fn aligned_locus(name: String, length: u64, start: u64, span: u64) -> Result<Locus, Error> {
+    match start.checked_add(span) {
+        Some(end) => Locus::try_new(name, length, start, end),
+        None => Err(Error::InvalidInterval { line_num: None, name, length, start, end: u64::MAX }),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cigar_parse() {
+        let cigar: Cigar = "10M2I5=1X3D".parse().unwrap();
+        assert_eq!(cigar.ops().len(), 5);
+        assert_eq!(cigar.ops()[1], CigarOp::new(CigarKind::Insertion, 2));
+        assert_eq!(cigar.query_len().unwrap(), 18);
+        assert_eq!(cigar.target_len().unwrap(), 19);
+        assert_eq!(cigar.to_string(), "10M2I5=1X3D");
+
+        assert!("10M2Q".parse::<Cigar>().is_err());
+        assert!("M".parse::<Cigar>().is_err());
+
+        let huge: Cigar = format!("{}M1I{}D", u64::MAX, u64::MAX).parse().unwrap();
+        assert!(matches!(huge.query_len(), Err(Error::InvalidAlignment { .. })));
+        assert!(matches!(huge.target_len(), Err(Error::InvalidAlignment { .. })));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let record: PAF = "q\t100\t10\t30\t-\tt\t100\t50\t73\t18\t25\t60\tcg:Z:5M2I3M5D10M"
+            .parse()
+            .unwrap();
+        let cigar = record.cigar().unwrap();
+
+        assert_eq!(
+            blocks(&record, &cigar).unwrap(),
+            vec![
+                Block { query_start: 25, query_end: 30, target_start: 50, target_end: 55 },
+                Block { query_start: 20, query_end: 23, target_start: 55, target_end: 58 },
+                Block { query_start: 10, query_end: 20, target_start: 63, target_end: 73 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paf_blocks() {
+        let record: PAF = "q\t100\t10\t30\t+\tt\t100\t50\t70\t18\t21\t60\tcg:Z:5=1X4=1I9=1D"
+            .parse()
+            .unwrap();
+
+        let blocks: Vec<_> = record.blocks().unwrap().iter().map(|b| (b.query(), b.target())).collect();
+        assert_eq!(blocks, vec![(10..20, 50..60), (21..30, 60..69)]);
+
+        let record: PAF = "q\t100\t10\t30\t+\tt\t100\t50\t70\t20\t20\t60".parse().unwrap();
+        assert!(record.blocks().is_none());
+
+        // A CIGAR running past the largest coordinate is an error, not a panic.
+        let huge: Cigar = format!("{}M", u64::MAX).parse().unwrap();
+        assert!(super::blocks(&record, &huge).is_err());
+    }
+
+    #[test]
+    fn test_from_alignment() {
+        let ops = vec![
+            CigarOp::new(CigarKind::SoftClip, 4),
+            CigarOp::new(CigarKind::Equal, 10),
+            CigarOp::new(CigarKind::Insertion, 2),
+            CigarOp::new(CigarKind::Equal, 5),
+            CigarOp::new(CigarKind::Diff, 1),
+            CigarOp::new(CigarKind::Deletion, 3),
+            CigarOp::new(CigarKind::Equal, 4),
+        ];
+        let record = PAF::from_alignment("q".to_string(), "t".to_string(), Strand::Minus, &ops, 30, 100, 4, 50)
+            .unwrap();
+        assert_eq!(
+            record.to_string(),
+            "q\t30\t4\t26\t-\tt\t100\t50\t73\t19\t25\t255\tNM:i:6\tcg:Z:10=2I5=1X3D4="
+        );
+        assert_eq!(record.cigar().unwrap().query_len().unwrap(), 22);
+
+        let ops = vec![CigarOp::new(CigarKind::Match, 20)];
+        let record = PAF::from_alignment("q".to_string(), "t".to_string(), Strand::Plus, &ops, 30, 100, 0, 0)
+            .unwrap();
+        assert_eq!(record.to_string(), "q\t30\t0\t20\t+\tt\t100\t0\t20\t20\t20\t255\tcg:Z:20M");
+
+        assert!(PAF::from_alignment("q".to_string(), "t".to_string(), Strand::Plus, &ops, 10, 100, 0, 0).is_err());
+
+        let result = PAF::from_alignment("q".to_string(), "t".to_string(), Strand::Plus, &ops, 30, u64::MAX, 0, u64::MAX - 5);
+        assert!(matches!(result, Err(Error::InvalidInterval { .. })));
+    }
+}
@@ -0,0 +1,155 @@
+//! Comparison of two sets of alignments, e.g. from different mappers.
+
+use std::collections::BTreeMap;
+
+use crate::paf::PAF;
+use crate::set::covered_intervals;
+
+
+/// The overlap between the target bases covered by two alignment sets.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct Similarity {
+    /// Target bases covered by the first set.
+    pub a_bases: u64,
+    /// Target bases covered by the second set.
+    pub b_bases: u64,
+    /// Target bases covered by both sets.
+    pub shared_bases: u64,
+}
+
+
+impl Similarity {
+    /// Target bases covered by either set.
+    pub fn union_bases(&self) -> u64 {
+        self.a_bases + self.b_bases - self.shared_bases
+    }
+
+    /// The Jaccard index, shared bases over the union.
+    ///
+    /// Two empty sets are identical, so have a Jaccard index of 1.
+    pub fn jaccard(&self) -> f64 {
+        if self.union_bases() == 0 {
+            1.0
+        } else {
+            ratio(self.shared_bases, self.union_bases())
+        }
+    }
+
+    /// Shared bases over the bases covered by the smaller set.
+    ///
+    /// This is 0 if either set is empty, as nothing is shared.
+    pub fn overlap_fraction(&self) -> f64 {
+        ratio(self.shared_bases, self.a_bases.min(self.b_bases))
+    }
+
+    /// The fraction of the first set's bases also covered by the second.
+    ///
+    /// This is 0 if the first set is empty.
+    pub fn fraction_of_a(&self) -> f64 {
+        ratio(self.shared_bases, self.a_bases)
+    }
+
+    /// The fraction of the second set's bases also covered by the first.
+    ///
+    /// This is 0 if the second set is empty.
+    pub fn fraction_of_b(&self) -> f64 {
+        ratio(self.shared_bases, self.b_bases)
+    }
+}
+
+
+/// The ratio of two base counts, or 0 if there are no bases to divide by.
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+
+/// Compare the target bases covered by two sets of records.
+pub fn similarity(a: &[PAF], b: &[PAF]) -> Similarity {
+    let a = covered_intervals(a);
+    let b = covered_intervals(b);
+
+    let total = |intervals: &BTreeMap<&str, Vec<(u64, u64)>>| -> u64 {
+        intervals.values().flatten().map(|(s, e)| e - s).sum()
+    };
+
+    let shared_bases = a
+        .iter()
+        .filter_map(|(name, spans)| b.get(name).map(|other| intersection(spans, other)))
+        .sum();
+
+    Similarity { a_bases: total(&a), b_bases: total(&b), shared_bases }
+}
+
+
+/// The length of the intersection of two sorted, merged interval lists.
+fn intersection(a: &[(u64, u64)], b: &[(u64, u64)]) -> u64 {
+    let mut shared = 0;
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].0.max(b[j].0);
+        let end = a[i].1.min(b[j].1);
+        if end > start {
+            shared += end - start;
+        }
+
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    shared
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_similarity() {
+        let a = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t100\t100\t60"),
+            paf("q2\t100\t0\t100\t+\tt1\t1000\t50\t150\t100\t100\t60"),
+            paf("q3\t100\t0\t100\t+\tt2\t1000\t0\t100\t100\t100\t60"),
+        ];
+        let b = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t100\t200\t100\t100\t60"),
+            paf("q3\t100\t0\t100\t+\tt3\t1000\t0\t100\t100\t100\t60"),
+        ];
+
+        let sim = similarity(&a, &b);
+        assert_eq!(sim, Similarity { a_bases: 250, b_bases: 200, shared_bases: 50 });
+        assert_eq!(sim.union_bases(), 400);
+        assert!((sim.jaccard() - 0.125).abs() < 1e-9);
+        assert!((sim.overlap_fraction() - 0.25).abs() < 1e-9);
+        assert!((sim.fraction_of_a() - 0.2).abs() < 1e-9);
+
+        assert_eq!(similarity(&[], &[]).jaccard(), 1.0);
+    }
+
+    #[test]
+    fn test_similarity_empty() {
+        let a = vec![paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t100\t100\t60")];
+
+        let sim = similarity(&[], &a);
+        assert_eq!(sim, Similarity { a_bases: 0, b_bases: 100, shared_bases: 0 });
+        assert_eq!(sim.jaccard(), 0.0);
+        assert_eq!(sim.overlap_fraction(), 0.0);
+        assert_eq!(sim.fraction_of_a(), 0.0);
+        assert_eq!(sim.fraction_of_b(), 0.0);
+
+        let none = similarity(&[], &[]);
+        assert_eq!(none.overlap_fraction(), 0.0);
+        assert_eq!(none.fraction_of_a(), 0.0);
+    }
+}
@@ -1,47 +1,57 @@
-use std::convert::TryInto;
+use core::convert::{TryFrom, TryInto};
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 use nom::IResult;
-use nom::Offset;
-use nom::bytes::complete::is_not;
+use nom::bytes::complete::{is_not, take_while, take_while1, take_while_m_n};
 use nom::character::complete::{
+    anychar,
+    char,
     tab,
-    newline,
+    line_ending,
     digit1,
+    none_of,
     one_of
 };
-use nom::sequence::{tuple, terminated, preceded};
-use nom::multi::separated_list;
+use nom::number::complete::recognize_float;
+use nom::sequence::{tuple, terminated, preceded, pair};
+use nom::multi::{fold_many0, many0};
+use nom::branch::alt;
 use nom::combinator::{
+    cut,
     map,
     map_res,
-    cut,
     opt,
-    all_consuming
+    peek,
+    recognize,
 };
 use nom::error::{
     context,
-    ParseError,
-    VerboseError,
     ErrorKind,
-    VerboseErrorKind
+    ParseError,
 };
 
 use crate::paf::{Strand, Locus, PAF};
+use crate::tags::{Tag, TagArray, TagValue};
+use crate::fields::Fields;
+use crate::cigar::{Cigar, CigarOp, CigarKind};
+use crate::cs::{Cs, CsOp};
 
 
-/// Parse a + or - character as a strand object.
+/// Parse a +, -, * or . character as a strand object.
 fn strand<I, E>(i: I) -> IResult<I, Strand, E>
 where
     I: Clone,
     I: nom::InputIter,
-    I: nom::Slice<std::ops::RangeFrom<usize>>,
+    I: nom::Slice<core::ops::RangeFrom<usize>>,
     I: nom::FindToken<<I as nom::InputIter>::Item>,
     <I as nom::InputIter>::Item: nom::AsChar + Copy,
     &'static str: nom::FindToken<<I as nom::InputIter>::Item>,
     E: ParseError<I>,
 {
     map(
-        context("expected either '+' or '-'", one_of("+-")),
+        context("expected one of '+', '-', '*' or '.'", one_of("+-*.")),
         |s| s.try_into().unwrap()
     )(i)
 }
@@ -54,7 +64,7 @@ where
 {
     context(
         "expected an utf-8 string",
-        map(is_not("\t\r\n"), |bs| String::from(bs))
+        map(is_not("\t\r\n"), String::from)
     )(i)
 }
 
@@ -67,7 +77,7 @@ where
     context(
         "expected an utf-8 string",
         map_res(is_not(&b"\t\r\n"[..]), |bs| {
-            std::str::from_utf8(bs).map(|s| s.to_string())
+            core::str::from_utf8(bs).map(|s| s.to_string())
         })
     )(i)
 }
@@ -94,7 +104,7 @@ where
         "expected an unsigned 64-bit integer",
         map_res(digit1, |s| {
             // This should be safe because we check for digits with nom.
-            let string = unsafe { std::str::from_utf8_unchecked(s) };
+            let string = unsafe { core::str::from_utf8_unchecked(s) };
             string.parse()
         }),
     )(i)
@@ -122,7 +132,7 @@ where
         "expected an unsigned 8-bit integer",
         map_res(digit1, |s| {
             // This should be safe because we check for digits with nom.
-            let string = unsafe { std::str::from_utf8_unchecked(s) };
+            let string = unsafe { core::str::from_utf8_unchecked(s) };
             string.parse()
         }),
     )(i)
@@ -130,7 +140,7 @@ where
 
 
 /// Parse a string as a locus object.
-pub(crate) fn locus_str<'a, E>(i: &'a str) -> IResult<&'a str, Locus, E>
+pub fn locus_str<'a, E>(i: &'a str) -> IResult<&'a str, Locus, E>
 where
     E: ParseError<&'a str>
 {
@@ -147,7 +157,7 @@ where
 
 
 /// Parse a string as a locus object.
-pub(crate) fn locus_u8<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], Locus, E>
+pub fn locus_u8<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], Locus, E>
 where
     E: ParseError<&'a [u8]>
 {
@@ -163,21 +173,246 @@ where
 }
 
 
+/// Parse a non-empty optional field.
+fn field_str<'a, E>(i: &'a str) -> IResult<&'a str, &'a str, E>
+where
+    E: ParseError<&'a str>
+{
+    context("expected an utf-8 string", is_not("\t\r\n"))(i)
+}
+
+
+/// Parse a non-empty optional field of utf-8 bytes.
+fn field_u8<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], &'a str, E>
+where
+    E: ParseError<&'a [u8]>
+{
+    context(
+        "expected an utf-8 string",
+        map_res(is_not(&b"\t\r\n"[..]), core::str::from_utf8)
+    )(i)
+}
+
+
 /// Parse the optional sam fields.
-fn sam_fields_str<'a, E>(i: &'a str) -> IResult<&'a str, Vec<String>, E>
+fn sam_fields_str<'a, E>(i: &'a str) -> IResult<&'a str, Fields, E>
 where
     E: ParseError<&'a str>
 {
-    separated_list(tab, string_str)(i)
+    match opt(field_str)(i)? {
+        (i, Some(first)) => {
+            fold_many0(preceded(tab, field_str), push_field(Fields::new(), first), push_field)(i)
+        },
+        (i, None) => Ok((i, Fields::new())),
+    }
 }
 
 
 /// Parse the optional sam fields.
-fn sam_fields_u8<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], Vec<String>, E>
+fn sam_fields_u8<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], Fields, E>
 where
     E: ParseError<&'a [u8]>
 {
-    separated_list(tab, string_u8)(i)
+    match opt(field_u8)(i)? {
+        (i, Some(first)) => {
+            fold_many0(preceded(tab, field_u8), push_field(Fields::new(), first), push_field)(i)
+        },
+        (i, None) => Ok((i, Fields::new())),
+    }
+}
+
+
+/// Parse the value of a tag given its type character.
+fn tag_value_str<'a, E>(kind: char) -> impl Fn(&'a str) -> IResult<&'a str, TagValue, E>
+where
+    E: ParseError<&'a str>
+{
+    move |i: &'a str| match kind {
+        'A' => context(
+            "expected a single printable character",
+            map(anychar, TagValue::Char)
+        )(i),
+        'i' => context(
+            "expected a signed integer",
+            map_res(recognize(pair(opt(one_of("+-")), digit1)), |s: &str| {
+                s.parse::<i64>().map(TagValue::Int)
+            })
+        )(i),
+        'f' => context(
+            "expected a floating point number",
+            map_res(recognize_float, |s: &str| s.parse::<f64>().map(TagValue::Float))
+        )(i),
+        'Z' => map(take_while(|c| c != '\t' && c != '\r' && c != '\n'), |s: &str| {
+            TagValue::String(s.to_string())
+        })(i),
+        'H' => context(
+            "expected an even number of hexadecimal digits",
+            map(map_res(take_while(|c: char| c.is_ascii_hexdigit()), hex_bytes), TagValue::Hex)
+        )(i),
+        _ => map(tag_array_str, TagValue::Array)(i),
+    }
+}
+
+
+/// Decode pairs of hexadecimal digits into bytes.
+fn hex_bytes(s: &str) -> Result<Vec<u8>, ()> {
+    if s.len() % 2 == 1 {
+        return Err(());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|j| u8::from_str_radix(&s[j..j + 2], 16).map_err(|_| ()))
+        .collect()
+}
+
+
+/// Parse comma prefixed array elements, e.g. `,1,-2`, failing on any out of range.
+fn array_values_str<'a, T, E>(
+    element: impl Fn(&'a str) -> IResult<&'a str, &'a str, E>,
+) -> impl Fn(&'a str) -> IResult<&'a str, Vec<T>, E>
+where
+    T: core::str::FromStr,
+    E: ParseError<&'a str>
+{
+    many0(preceded(char(','), map_res(element, |s: &str| s.parse::<T>())))
+}
+
+
+/// Parse the body of a `B` tag, e.g. `c,1,-2`.
+fn tag_array_str<'a, E>(i: &'a str) -> IResult<&'a str, TagArray, E>
+where
+    E: ParseError<&'a str>
+{
+    let int = |i: &'a str| recognize(pair(opt(one_of("+-")), digit1))(i);
+    let (i, subtype) = context(
+        "expected an array type of 'c', 'C', 's', 'S', 'i', 'I' or 'f'",
+        one_of("cCsSiIf")
+    )(i)?;
+    let (i, array) = context("expected a comma separated array of numbers", |i| match subtype {
+        'c' => map(array_values_str(int), TagArray::Int8)(i),
+        'C' => map(array_values_str(int), TagArray::UInt8)(i),
+        's' => map(array_values_str(int), TagArray::Int16)(i),
+        'S' => map(array_values_str(int), TagArray::UInt16)(i),
+        'i' => map(array_values_str(int), TagArray::Int32)(i),
+        'I' => map(array_values_str(int), TagArray::UInt32)(i),
+        _ => map(array_values_str(recognize_float), TagArray::Float)(i),
+    })(i)?;
+    Ok((i, array))
+}
+
+
+/// Parse a string as a SAM-style `NN:T:value` tag.
+pub fn tag_str<'a, E>(i: &'a str) -> IResult<&'a str, Tag, E>
+where
+    E: ParseError<&'a str>
+{
+    let (i, name) = context(
+        "expected a two character tag name",
+        terminated(
+            take_while_m_n(2, 2, |c: char| c.is_ascii_alphanumeric()),
+            char(':')
+        )
+    )(i)?;
+    let (i, kind) = context(
+        "expected a tag type of 'A', 'i', 'f', 'Z', 'H' or 'B'",
+        terminated(one_of("AifZHB"), char(':'))
+    )(i)?;
+    let (i, value) = tag_value_str(kind)(i)?;
+    Ok((i, Tag::new(name.to_string(), value)))
+}
+
+
+/// Recognise a whole optional field as a tag.
+///
+/// Errors are reported at the start of the field rather than where the tag
+/// stopped matching, so that a column that isn't a tag at all is pointed
+/// out as a whole.
+fn tag_field_str<'a, E>(i: &'a str) -> IResult<&'a str, &'a str, E>
+where
+    E: ParseError<&'a str>
+{
+    match recognize(tag_str::<E>)(i) {
+        Err(nom::Err::Error(_)) | Err(nom::Err::Failure(_)) => {
+            let e = E::from_error_kind(i, ErrorKind::Verify);
+            Err(nom::Err::Error(E::add_context(i, "expected a SAM-style tag, e.g. 'tp:A:P'", e)))
+        },
+        result => result,
+    }
+}
+
+
+/// Parse a single CIGAR operation, e.g. `10M`.
+fn cigar_op_str<'a, E>(i: &'a str) -> IResult<&'a str, CigarOp, E>
+where
+    E: ParseError<&'a str>
+{
+    map(
+        tuple((
+            context("expected an operation length", uint64_str),
+            context(
+                "expected a CIGAR operation of 'M', 'I', 'D', 'N', 'S', 'H', 'P', '=' or 'X'",
+                map_res(one_of("MIDNSHP=X"), CigarKind::try_from)
+            ),
+        )),
+        |(len, kind)| CigarOp::new(kind, len)
+    )(i)
+}
+
+
+/// Parse a string as a CIGAR.
+pub fn cigar_str<'a, E>(i: &'a str) -> IResult<&'a str, Cigar, E>
+where
+    E: ParseError<&'a str>
+{
+    map(many0(cigar_op_str), Cigar::new)(i)
+}
+
+
+/// Parse a single `cs` operation, e.g. `:10` or `*ag`.
+fn cs_op_str<'a, E>(i: &'a str) -> IResult<&'a str, CsOp, E>
+where
+    E: ParseError<&'a str>
+{
+    let bases = |c: char| c.is_ascii_alphabetic();
+    let lower = |c: char| c.is_ascii_lowercase();
+
+    alt((
+        map(preceded(char(':'), uint64_str), CsOp::Match),
+        map(preceded(char('='), take_while1(bases)), |s: &str| CsOp::Identical(s.to_string())),
+        map(
+            preceded(char('*'), pair(anychar, anychar)),
+            |(target, query)| CsOp::Substitution { target, query }
+        ),
+        map(preceded(char('+'), take_while1(bases)), |s: &str| CsOp::Insertion(s.to_string())),
+        map(preceded(char('-'), take_while1(bases)), |s: &str| CsOp::Deletion(s.to_string())),
+        map(
+            preceded(
+                char('~'),
+                tuple((
+                    take_while_m_n(2, 2, lower),
+                    uint64_str,
+                    take_while_m_n(2, 2, lower)
+                ))
+            ),
+            |(donor, len, acceptor): (&str, u64, &str)| CsOp::Intron {
+                donor: donor.to_string(),
+                len,
+                acceptor: acceptor.to_string(),
+            }
+        ),
+    ))(i)
+}
+
+
+/// Parse a string as a `cs` difference string.
+pub fn cs_str<'a, E>(i: &'a str) -> IResult<&'a str, Cs, E>
+where
+    E: ParseError<&'a str>
+{
+    context(
+        "expected a cs operation of ':', '=', '*', '+', '-' or '~'",
+        map(many0(cs_op_str), Cs::new)
+    )(i)
 }
 
 
@@ -201,7 +436,7 @@ where
             context("in column: alignment length", terminated(uint64_str, tab)),
             context("in column: mapping quality", uint8_str),
             context("in column: optional sam fields", preceded(opt(tab), sam_fields_str)),
-            opt(newline)
+            opt(line_ending)
         )),
         |tup| {
             let query = Locus::new(tup.0, tup.1, tup.2, tup.3);
@@ -212,6 +447,131 @@ where
 }
 
 
+/// Parse the query locus, strand and target locus columns of a paf line.
+fn leading_columns_str<'a, E>(line: &'a str) -> IResult<&'a str, (Locus, Strand, Locus), E>
+where
+    E: ParseError<&'a str>
+{
+    map(
+        tuple((
+            context("in column: query seqid", terminated(string_str, tab)),
+            context("in column: query length", terminated(uint64_str, tab)),
+            context("in column: query start", terminated(uint64_str, tab)),
+            context("in column: query end", terminated(uint64_str, tab)),
+            context("in column: strand", terminated(strand, tab)),
+            context("in column: target seqid", terminated(string_str, tab)),
+            context("in column: target length", terminated(uint64_str, tab)),
+            context("in column: target start", terminated(uint64_str, tab)),
+            context("in column: target end", uint64_str),
+        )),
+        |tup| {
+            let query = Locus::new(tup.0, tup.1, tup.2, tup.3);
+            let target = Locus::new(tup.5, tup.6, tup.7, tup.8);
+            (query, tup.4, target)
+        }
+    )(line)
+}
+
+
+/// Parse a tab that starts another column, rather than ending the line.
+fn column_tab_str<'a, E>(i: &'a str) -> IResult<&'a str, char, E>
+where
+    E: ParseError<&'a str>
+{
+    terminated(tab, peek(none_of("\r\n")))(i)
+}
+
+
+/// Append a parsed optional field.
+pub(crate) fn push_field(mut fields: Fields, field: &str) -> Fields {
+    fields.push(field);
+    fields
+}
+
+
+/// Parse a string as a paf line whose optional fields must all be tags.
+pub fn strict_paf_str<'a, E>(line: &'a str) -> IResult<&'a str, PAF, E>
+where
+    E: ParseError<&'a str>
+{
+    map(
+        tuple((
+            terminated(leading_columns_str, tab),
+            context("in column: number matches", terminated(uint64_str, tab)),
+            context("in column: alignment length", terminated(uint64_str, tab)),
+            context("in column: mapping quality", uint8_str),
+            fold_many0(
+                preceded(
+                    column_tab_str,
+                    context("in column: optional sam fields", cut(tag_field_str))
+                ),
+                Fields::new(),
+                push_field
+            ),
+            opt(tab),
+            opt(line_ending)
+        )),
+        |((query, strand, target), nmatch, alnlen, mq, fields, _, _)| {
+            PAF::new(query, strand, target, nmatch, alnlen, mq, fields)
+        }
+    )(line)
+}
+
+
+/// Parse a string as a paf line, tolerating missing trailing columns.
+///
+/// If the number of matches, alignment length or mapping quality columns
+/// are missing from the end of the mandatory columns, they default to 0,
+/// the longer of the two aligned spans, and 255 respectively. Optional
+/// columns need not be SAM-style tags.
+pub fn lenient_paf_str<'a, E>(line: &'a str) -> IResult<&'a str, PAF, E>
+where
+    E: ParseError<&'a str>
+{
+    map(
+        tuple((
+            leading_columns_str,
+            opt(preceded(
+                tab,
+                pair(
+                    context("in column: number matches", uint64_str),
+                    opt(preceded(
+                        tab,
+                        pair(
+                            context("in column: alignment length", uint64_str),
+                            opt(preceded(tab, context("in column: mapping quality", uint8_str)))
+                        )
+                    ))
+                )
+            )),
+            fold_many0(
+                preceded(
+                    column_tab_str,
+                    context("expected an utf-8 string", is_not("\t\r\n"))
+                ),
+                Fields::new(),
+                push_field
+            ),
+            opt(tab),
+            opt(line_ending)
+        )),
+        |((query, strand, target), trailing, fields, _, _)| {
+            let span = (query.end().saturating_sub(query.start()))
+                .max(target.end().saturating_sub(target.start()));
+
+            let (nmatch, alnlen, mq) = match trailing {
+                None => (0, span, 255),
+                Some((nmatch, None)) => (nmatch, span, 255),
+                Some((nmatch, Some((alnlen, None)))) => (nmatch, alnlen, 255),
+                Some((nmatch, Some((alnlen, Some(mq))))) => (nmatch, alnlen, mq),
+            };
+
+            PAF::new(query, strand, target, nmatch, alnlen, mq, fields)
+        }
+    )(line)
+}
+
+
 /// Parse a byte string as a complete paf line.
 pub fn paf_u8<'a, E>(line: &'a [u8]) -> IResult<&'a [u8], PAF, E>
 where
@@ -232,7 +592,7 @@ where
             context("in column: alignment length", terminated(uint64_u8, tab)),
             context("in column: mapping quality", uint8_u8),
             context("in column: optional sam fields", preceded(opt(tab), sam_fields_u8)),
-            opt(newline)
+            opt(line_ending)
         )),
         |tup| {
             let query = Locus::new(tup.0, tup.1, tup.2, tup.3);
@@ -241,6 +601,78 @@ where
         }
     )(line)
 }
+
+
+/// Skip the rest of a line and its line ending, returning the number of
+/// bytes skipped.
+///
+/// Without a line ending, the rest of the input is skipped.
+pub fn skip_line_str<'a, E>(i: &'a str) -> IResult<&'a str, usize, E>
+where
+    E: ParseError<&'a str>
+{
+    let n = i.find('\n').map_or(i.len(), |p| p + 1);
+    Ok((&i[n..], n))
+}
+
+
+/// Skip the rest of a line and its line ending, returning the number of
+/// bytes skipped.
+///
+/// Without a line ending, the rest of the input is skipped.
+pub fn skip_line_u8<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], usize, E>
+where
+    E: ParseError<&'a [u8]>
+{
+    let n = i.iter().position(|b| *b == b'\n').map_or(i.len(), |p| p + 1);
+    Ok((&i[n..], n))
+}
+
+
+/// A parsed value, or the error and the number of bytes skipped to recover.
+pub type Recovered<O, E> = Result<O, (E, usize)>;
+
+
+/// Apply `parser`, or on an error skip to the start of the next line.
+///
+/// This never fails, so a caller can keep parsing the lines after a
+/// malformed one. The error is returned with the number of bytes skipped,
+/// counted from the start of the failed line.
+pub fn recover_line_str<'a, O, E, F>(parser: F)
+    -> impl Fn(&'a str) -> IResult<&'a str, Recovered<O, E>, E>
+where
+    F: Fn(&'a str) -> IResult<&'a str, O, E>,
+    E: ParseError<&'a str>
+{
+    move |i: &'a str| match parser(i) {
+        Ok((rest, value)) => Ok((rest, Ok(value))),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            let (rest, n) = skip_line_str(i)?;
+            Ok((rest, Err((e, n))))
+        },
+        Err(nom::Err::Incomplete(needed)) => Err(nom::Err::Incomplete(needed)),
+    }
+}
+
+
+/// Apply `parser`, or on an error skip to the start of the next line.
+///
+/// See [`recover_line_str`].
+pub fn recover_line_u8<'a, O, E, F>(parser: F)
+    -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Recovered<O, E>, E>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], O, E>,
+    E: ParseError<&'a [u8]>
+{
+    move |i: &'a [u8]| match parser(i) {
+        Ok((rest, value)) => Ok((rest, Ok(value))),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            let (rest, n) = skip_line_u8(i)?;
+            Ok((rest, Err((e, n))))
+        },
+        Err(nom::Err::Incomplete(needed)) => Err(nom::Err::Incomplete(needed)),
+    }
+}
 //all_consuming(
 
 
@@ -249,6 +681,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use nom::error::ErrorKind;
 
     type TupStrErr = (&'static str, ErrorKind);
     type TupU8Err = (&'static [u8], ErrorKind);
@@ -308,6 +741,16 @@ mod tests {
             Ok(("\tone", Strand::Minus))
         );
 
+        assert_eq!(
+            strand::<&str, TupStrErr>("*\tone"),
+            Ok(("\tone", Strand::Unknown))
+        );
+
+        assert_eq!(
+            strand::<&str, TupStrErr>(".\tone"),
+            Ok(("\tone", Strand::Unknown))
+        );
+
         assert_eq!(
             strand::<&str, TupStrErr>("?\tone"),
             Err(nom::Err::Error(("?\tone", ErrorKind::OneOf)))
@@ -433,17 +876,17 @@ mod tests {
     fn test_sam_fields_str() {
         assert_eq!(
             sam_fields_str::<TupStrErr>("Hey\tDarcy"),
-            Ok(("", vec!["Hey".to_string(), "Darcy".to_string()]))
+            Ok(("", Fields::from(vec!["Hey".to_string(), "Darcy".to_string()])))
         );
 
         assert_eq!(
             sam_fields_str::<TupStrErr>("Hey\tDarcy\t"),
-            Ok(("\t", vec!["Hey".to_string(), "Darcy".to_string()]))
+            Ok(("\t", Fields::from(vec!["Hey".to_string(), "Darcy".to_string()])))
         );
 
         assert_eq!(
-            sam_fields_str::<TupStrErr>(&""),
-            Ok(("", vec![]))
+            sam_fields_str::<TupStrErr>(""),
+            Ok(("", Fields::new()))
         );
     }
 
@@ -451,17 +894,17 @@ mod tests {
     fn test_sam_fields_u8() {
         assert_eq!(
             sam_fields_u8::<TupU8Err>(&b"Hey\tDarcy"[..]),
-            Ok((&b""[..], vec!["Hey".to_string(), "Darcy".to_string()]))
+            Ok((&b""[..], Fields::from(vec!["Hey".to_string(), "Darcy".to_string()])))
         );
 
         assert_eq!(
             sam_fields_u8::<TupU8Err>(&b"Hey\tDarcy\t"[..]),
-            Ok((&b"\t"[..], vec!["Hey".to_string(), "Darcy".to_string()]))
+            Ok((&b"\t"[..], Fields::from(vec!["Hey".to_string(), "Darcy".to_string()])))
         );
 
         assert_eq!(
             sam_fields_u8::<TupU8Err>(&b""[..]),
-            Ok((&b""[..], vec![]))
+            Ok((&b""[..], Fields::new()))
         );
     }
 
@@ -541,6 +984,11 @@ mod tests {
             Ok(("", PAF::new(l1.clone(), Strand::Plus, l2.clone(), 1, 1, 1, vec!["one".to_string(), "two".to_string(), "three".to_string()])))
         );
 
+        assert_eq!(
+            paf_str::<TupStrErr>("seqid\t10\t0\t10\t+\tseqid2\t10\t0\t10\t1\t1\t1\tone\r\n"),
+            Ok(("", PAF::new(l1.clone(), Strand::Plus, l2.clone(), 1, 1, 1, vec!["one".to_string()])))
+        );
+
         assert_eq!(
             paf_str::<TupStrErr>("seqid\t10\t0\t10\t+\tseqid2\t10\t0\t10\t1\t1"),
             Err(nom::Err::Error(("", ErrorKind::Char)))
@@ -567,9 +1015,74 @@ mod tests {
             Ok((&b""[..], PAF::new(l1.clone(), Strand::Plus, l2.clone(), 1, 1, 1, vec!["one".to_string(), "two".to_string(), "three".to_string()])))
         );
 
+        assert_eq!(
+            paf_u8::<TupU8Err>(&b"seqid\t10\t0\t10\t+\tseqid2\t10\t0\t10\t1\t1\t1\r\n"[..]),
+            Ok((&b""[..], PAF::new(l1.clone(), Strand::Plus, l2.clone(), 1, 1, 1, Vec::new())))
+        );
+
         assert_eq!(
             paf_u8::<TupU8Err>(&b"seqid\t10\t0\t10\t+\tseqid2\t10\t0\t10\t1\t1"[..]),
             Err(nom::Err::Error((&b""[..], ErrorKind::Char)))
         );
     }
+
+    #[test]
+    fn test_strict_paf_str() {
+        let l1 = Locus::new("seqid".to_string(), 10, 0, 10);
+        let l2 = Locus::new("seqid2".to_string(), 10, 0, 10);
+
+        assert_eq!(
+            strict_paf_str::<TupStrErr>("seqid\t10\t0\t10\t+\tseqid2\t10\t0\t10\t1\t1\t1\ttp:A:P\tNM:i:0\t\n"),
+            Ok(("", PAF::new(l1.clone(), Strand::Plus, l2.clone(), 1, 1, 1, vec!["tp:A:P".to_string(), "NM:i:0".to_string()])))
+        );
+
+        assert_eq!(
+            strict_paf_str::<TupStrErr>("seqid\t10\t0\t10\t+\tseqid2\t10\t0\t10\t1\t1\t1\ttp:A:P\tone"),
+            Err(nom::Err::Failure(("one", ErrorKind::Verify)))
+        );
+    }
+
+    #[test]
+    fn test_lenient_paf_str() {
+        let l1 = Locus::new("seqid".to_string(), 10, 0, 10);
+        let l2 = Locus::new("seqid2".to_string(), 20, 0, 8);
+
+        assert_eq!(
+            lenient_paf_str::<TupStrErr>("seqid\t10\t0\t10\t+\tseqid2\t20\t0\t8\t7\t10\n"),
+            Ok(("", PAF::new(l1.clone(), Strand::Plus, l2.clone(), 7, 10, 255, Vec::new())))
+        );
+
+        assert_eq!(
+            lenient_paf_str::<TupStrErr>("seqid\t10\t0\t10\t+\tseqid2\t20\t0\t8"),
+            Ok(("", PAF::new(l1.clone(), Strand::Plus, l2.clone(), 0, 10, 255, Vec::new())))
+        );
+
+        assert_eq!(
+            lenient_paf_str::<TupStrErr>("seqid\t10\t0\t10\t+\tseqid2\t20\t0\t8\t7\t10\t3\tone\ttp:A:P"),
+            Ok(("", PAF::new(l1.clone(), Strand::Plus, l2.clone(), 7, 10, 3, vec!["one".to_string(), "tp:A:P".to_string()])))
+        );
+    }
+
+    #[test]
+    fn test_recover_line() {
+        let input = "q1\t10\t0\tx\t+\tt1\t20\t0\t10\t10\t10\t60\r\n\
+                     q2\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\n\
+                     q3\t10";
+
+        let parser = recover_line_str(strict_paf_str::<TupStrErr>);
+        let (rest, first) = parser(input).unwrap();
+        assert_eq!(first.unwrap_err().1, 33);
+
+        let (rest, second) = parser(rest).unwrap();
+        assert_eq!(second.unwrap().query().name(), "q2");
+
+        let (rest, third) = parser(rest).unwrap();
+        assert_eq!(third.unwrap_err().1, 5);
+        assert_eq!(rest, "");
+
+        let parser = recover_line_u8(paf_u8::<TupU8Err>);
+        let (rest, first) = parser(input.as_bytes()).unwrap();
+        assert!(first.is_err());
+        assert!(rest.starts_with(b"q2\t"));
+    }
 }
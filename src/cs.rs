@@ -0,0 +1,354 @@
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use nom::combinator::{cut, all_consuming};
+
+use crate::cigar::{Cigar, CigarKind, CigarOp};
+use crate::errors::Error;
+
+
+/// A single operation of a minimap2 `cs` difference string.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub enum CsOp {
+    /// A run of identical bases in short form (`:10`).
+    Match(u64),
+    /// A run of identical bases in long form (`=ACGT`).
+    Identical(String),
+    /// A substitution of a target base with a query base (`*ag`).
+    Substitution { target: char, query: char },
+    /// Bases present in the query but not the target (`+ag`).
+    Insertion(String),
+    /// Bases present in the target but not the query (`-ag`).
+    Deletion(String),
+    /// An intron on the target (`~gt10ag`).
+    Intron { donor: String, len: u64, acceptor: String },
+}
+
+
+impl CsOp {
+    /// The number of query bases spanned.
+    pub fn query_len(&self) -> u64 {
+        match self {
+            CsOp::Match(n) => *n,
+            CsOp::Identical(s) | CsOp::Insertion(s) => s.len() as u64,
+            CsOp::Substitution { .. } => 1,
+            CsOp::Deletion(_) | CsOp::Intron { .. } => 0,
+        }
+    }
+
+    /// The number of target bases spanned.
+    pub fn target_len(&self) -> u64 {
+        match self {
+            CsOp::Match(n) => *n,
+            CsOp::Identical(s) | CsOp::Deletion(s) => s.len() as u64,
+            CsOp::Substitution { .. } => 1,
+            CsOp::Insertion(_) => 0,
+            CsOp::Intron { len, .. } => *len,
+        }
+    }
+}
+
+
+impl fmt::Display for CsOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsOp::Match(n) => write!(f, ":{}", n),
+            CsOp::Identical(s) => write!(f, "={}", s),
+            CsOp::Substitution { target, query } => write!(f, "*{}{}", target, query),
+            CsOp::Insertion(s) => write!(f, "+{}", s),
+            CsOp::Deletion(s) => write!(f, "-{}", s),
+            CsOp::Intron { donor, len, acceptor } => write!(f, "~{}{}{}", donor, len, acceptor),
+        }
+    }
+}
+
+
+/// A minimap2 `cs` difference string.
+#[derive(Debug, Eq, PartialEq, Clone, Hash, Default)]
+pub struct Cs(Vec<CsOp>);
+
+
+impl Cs {
+    /// Construct a new `Cs` object.
+    pub fn new(ops: Vec<CsOp>) -> Self {
+        Cs(ops)
+    }
+
+    /// The operations, in target order.
+    pub fn ops(&self) -> &[CsOp] {
+        &self.0
+    }
+
+    /// The number of query bases spanned.
+    pub fn query_len(&self) -> u64 {
+        self.0.iter().map(CsOp::query_len).sum()
+    }
+
+    /// The number of target bases spanned.
+    pub fn target_len(&self) -> u64 {
+        self.0.iter().map(CsOp::target_len).sum()
+    }
+
+    /// Derive the equivalent CIGAR.
+    ///
+    /// With `extended`, matches and substitutions become `=` and `X`,
+    /// otherwise both become `M`. Introns become `N`.
+    pub fn to_cigar(&self, extended: bool) -> Cigar {
+        let mut ops: Vec<CigarOp> = Vec::new();
+        for op in &self.0 {
+            let (kind, len) = match op {
+                CsOp::Match(_) | CsOp::Identical(_) if extended => (CigarKind::Equal, op.target_len()),
+                CsOp::Substitution { .. } if extended => (CigarKind::Diff, 1),
+                CsOp::Match(_) | CsOp::Identical(_) | CsOp::Substitution { .. } => {
+                    (CigarKind::Match, op.target_len())
+                },
+                CsOp::Insertion(s) => (CigarKind::Insertion, s.len() as u64),
+                CsOp::Deletion(s) => (CigarKind::Deletion, s.len() as u64),
+                CsOp::Intron { len, .. } => (CigarKind::Skip, *len),
+            };
+
+            match ops.last_mut() {
+                Some(last) if last.kind() == kind => *last = CigarOp::new(kind, last.len() + len),
+                _ => ops.push(CigarOp::new(kind, len)),
+            }
+        }
+        Cigar::new(ops)
+    }
+
+    /// Derive a `cs` string from a CIGAR and the aligned sequences.
+    ///
+    /// `query` and `target` are the aligned regions only, with the query
+    /// reverse complemented for minus strand alignments. The long form
+    /// (`long`) spells out identical bases, the short form counts them.
+    /// Clipping and padding operations are skipped. Returns
+    /// `Error::InvalidAlignment` if the sequences are shorter than the CIGAR,
+    /// or an intron is shorter than its two 2 bp splice sites.
+    pub fn from_cigar<'a>(
+        cigar: &Cigar,
+        query: &'a str,
+        target: &'a str,
+        long: bool,
+    ) -> Result<Cs, Error> {
+        let query = query.as_bytes();
+        let target = target.as_bytes();
+        let (mut qi, mut ti) = (0usize, 0usize);
+        let mut ops: Vec<CsOp> = Vec::new();
+
+        let span = |seq: &'a [u8], start: usize, len: u64, what: &str| -> Result<&'a [u8], Error> {
+            usize::try_from(len).ok()
+                .and_then(|len| start.checked_add(len))
+                .and_then(|end| seq.get(start..end))
+                .ok_or_else(|| Error::InvalidAlignment {
+                    message: format!("the {} sequence is shorter than the CIGAR", what),
+                })
+        };
+        let take = |seq: &'a [u8], start: usize, len: u64, what: &str| -> Result<String, Error> {
+            span(seq, start, len, what).map(|s| String::from_utf8_lossy(s).to_string())
+        };
+
+        for op in cigar.ops() {
+            let len = op.len();
+            match op.kind() {
+                CigarKind::Match | CigarKind::Equal | CigarKind::Diff => {
+                    let q = take(query, qi, len, "query")?;
+                    let t = take(target, ti, len, "target")?;
+                    for (qb, tb) in q.chars().zip(t.chars()) {
+                        if qb.eq_ignore_ascii_case(&tb) {
+                            push_identical(&mut ops, tb.to_ascii_uppercase(), long);
+                        } else {
+                            ops.push(CsOp::Substitution {
+                                target: tb.to_ascii_lowercase(),
+                                query: qb.to_ascii_lowercase(),
+                            });
+                        }
+                    }
+                },
+                CigarKind::Insertion => {
+                    ops.push(CsOp::Insertion(take(query, qi, len, "query")?.to_ascii_lowercase()));
+                },
+                CigarKind::Deletion => {
+                    ops.push(CsOp::Deletion(take(target, ti, len, "target")?.to_ascii_lowercase()));
+                },
+                CigarKind::Skip => {
+                    // The splice sites are the first and last two bases, so
+                    // they can't overlap.
+                    if len < 4 {
+                        return Err(Error::InvalidAlignment {
+                            message: format!("the {}N intron is too short to have splice sites", len),
+                        });
+                    }
+                    let intron = span(target, ti, len, "target")?;
+                    let sites = |s: &[u8]| String::from_utf8_lossy(s).to_ascii_lowercase();
+                    ops.push(CsOp::Intron {
+                        donor: sites(&intron[..2]),
+                        len,
+                        acceptor: sites(&intron[intron.len() - 2..]),
+                    });
+                },
+                CigarKind::SoftClip | CigarKind::HardClip | CigarKind::Padding => {},
+            }
+
+            // Soft clips are not part of the aligned query region.
+            if op.kind().consumes_query() && op.kind() != CigarKind::SoftClip {
+                qi = advance(qi, len, "query")?;
+            }
+            if op.kind().consumes_target() {
+                ti = advance(ti, len, "target")?;
+            }
+        }
+
+        Ok(Cs(ops))
+    }
+}
+
+
+/// Move a sequence offset past an operation.
+fn advance(offset: usize, len: u64, what: &str) -> Result<usize, Error> {
+    usize::try_from(len).ok()
+        .and_then(|len| offset.checked_add(len))
+        .ok_or_else(|| Error::InvalidAlignment {
+            message: format!("the CIGAR spans more {} bases than can be addressed", what),
+        })
+}
+
+
+/// Extend the trailing run of identical bases, or start a new one.
+fn push_identical(ops: &mut Vec<CsOp>, base: char, long: bool) {
+    match ops.last_mut() {
+        Some(CsOp::Identical(s)) if long => s.push(base),
+        Some(CsOp::Match(n)) if !long => *n += 1,
+        _ if long => ops.push(CsOp::Identical(base.to_string())),
+        _ => ops.push(CsOp::Match(1)),
+    }
+}
+
+
+impl fmt::Display for Cs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for op in &self.0 {
+            write!(f, "{}", op)?;
+        }
+        Ok(())
+    }
+}
+
+
+impl FromStr for Cs {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, cs) = all_consuming(cut(crate::complete::cs_str))(s)
+            .map_err(|e| {
+                match e {
+                    nom::Err::Error(ei) | nom::Err::Failure(ei) => {
+                        crate::errors::convert_error_str(s, ei, None)
+                    },
+                    _ => unreachable!(),
+                }
+            })?;
+
+        Ok(cs)
+    }
+}
+
+
+/// Complement a single base, preserving case.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn complement(base: char) -> char {
+    match base {
+        'a' => 't', 'c' => 'g', 'g' => 'c', 't' => 'a',
+        'A' => 'T', 'C' => 'G', 'G' => 'C', 'T' => 'A',
+        'u' => 'a', 'U' => 'A',
+        c => c,
+    }
+}
+
+
+/// Reverse complement a sequence, preserving case.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn reverse_complement(seq: &str) -> String {
+    seq.chars().rev().map(complement).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cs_parse() {
+        let cs: Cs = ":10*ag=ACGT+tt-c~gt100ag:5".parse().unwrap();
+        assert_eq!(
+            cs.ops(),
+            &[
+                CsOp::Match(10),
+                CsOp::Substitution { target: 'a', query: 'g' },
+                CsOp::Identical("ACGT".to_string()),
+                CsOp::Insertion("tt".to_string()),
+                CsOp::Deletion("c".to_string()),
+                CsOp::Intron { donor: "gt".to_string(), len: 100, acceptor: "ag".to_string() },
+                CsOp::Match(5),
+            ]
+        );
+        assert_eq!(cs.query_len(), 22);
+        assert_eq!(cs.target_len(), 121);
+        assert_eq!(cs.to_string(), ":10*ag=ACGT+tt-c~gt100ag:5");
+
+        assert!(":10?ag".parse::<Cs>().is_err());
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement("aacG"), "Cgtt");
+    }
+
+    #[test]
+    fn test_cs_cigar_conversion() {
+        let cs: Cs = ":5*ag:3+tt-c~gt20ag:2".parse().unwrap();
+        assert_eq!(cs.to_cigar(false).to_string(), "9M2I1D20N2M");
+        assert_eq!(cs.to_cigar(true).to_string(), "5=1X3=2I1D20N2=");
+
+        let target = "ACGTAACGTCGTAAAAAAAAAAAAAAAAAGTC";
+        let query = "ACGTAGCGTTTTC";
+        let cigar: Cigar = "9M2I1D20N2M".parse().unwrap();
+
+        let short = Cs::from_cigar(&cigar, query, target, false).unwrap();
+        assert_eq!(short.to_string(), ":5*ag:3+tt-c~gt20ag:2");
+        assert_eq!(short.to_cigar(false), cigar);
+
+        let long = Cs::from_cigar(&cigar, query, target, true).unwrap();
+        assert_eq!(long.to_string(), "=ACGTA*ag=CGT+tt-c~gt20ag=TC");
+
+        assert!(Cs::from_cigar(&cigar, "ACG", target, false).is_err());
+    }
+
+    #[test]
+    fn test_cs_short_introns() {
+        let target = "ACGTAC";
+
+        let cigar: Cigar = "1M4N1M".parse().unwrap();
+        let cs = Cs::from_cigar(&cigar, "AC", target, false).unwrap();
+        assert_eq!(cs.to_string(), ":1~cg4ta:1");
+        assert_eq!(cs.to_string().parse::<Cs>().unwrap(), cs);
+
+        for cigar in &["1M3N1M", "1M1N1M", "1M0N1M"] {
+            let cigar: Cigar = cigar.parse().unwrap();
+            assert!(matches!(
+                Cs::from_cigar(&cigar, "AC", target, false),
+                Err(Error::InvalidAlignment { .. })
+            ));
+        }
+
+        // Lengths past the end of the sequences are errors, not overflows.
+        let cigar = Cigar::new(vec![
+            CigarOp::new(CigarKind::Match, 1),
+            CigarOp::new(CigarKind::Deletion, u64::MAX),
+        ]);
+        assert!(Cs::from_cigar(&cigar, "AC", target, false).is_err());
+    }
+}
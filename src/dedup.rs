@@ -0,0 +1,122 @@
+//! Removal of duplicate alignment records.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::paf::{Strand, PAF};
+
+
+/// Remove duplicate records, keeping the first of each.
+///
+/// If `tolerance` is given, records with the same query, target, and strand
+/// whose four coordinates all differ by at most `tolerance` are also treated
+/// as duplicates, and the copy with the highest [`PAF::score`] is kept
+/// (ties keep the earliest). Survivors are returned in input order.
+pub fn dedup(records: Vec<PAF>, tolerance: Option<u64>) -> Vec<PAF> {
+    let mut keep = vec![true; records.len()];
+
+    let mut seen: HashSet<&PAF> = HashSet::new();
+    for (i, record) in records.iter().enumerate() {
+        if !seen.insert(record) {
+            keep[i] = false;
+        }
+    }
+
+    if let Some(tolerance) = tolerance {
+        mark_near_duplicates(&records, tolerance, &mut keep);
+    }
+
+    records
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(r, k)| if k { Some(r) } else { None })
+        .collect()
+}
+
+
+/// Mark all but the best of each cluster of near-duplicates as removed.
+fn mark_near_duplicates(records: &[PAF], tolerance: u64, keep: &mut [bool]) {
+    let mut groups: HashMap<(&str, &str, Strand), Vec<usize>> = HashMap::new();
+    for (i, record) in records.iter().enumerate().filter(|(i, _)| keep[*i]) {
+        let key = (record.query().name(), record.target().name(), record.strand());
+        groups.entry(key).or_default().push(i);
+    }
+
+    for (_, mut group) in groups {
+        group.sort_by_key(|i| (records[*i].query().start(), *i));
+
+        // The query start of the first record in each cluster, which keeps
+        // the clusters ordered, and the index of its current best record.
+        let mut representatives: Vec<(u64, usize)> = Vec::new();
+        for i in group {
+            let record = &records[i];
+            let first = representatives
+                .partition_point(|(start, _)| start.saturating_add(tolerance) < record.query().start());
+
+            let matched = representatives[first..]
+                .iter()
+                .position(|(_, r)| within(&records[*r], record, tolerance))
+                .map(|p| first + p);
+
+            match matched {
+                Some(p) => {
+                    let current = representatives[p].1;
+                    let better = record.score() > records[current].score()
+                        || (record.score() == records[current].score() && i < current);
+                    if better {
+                        keep[current] = false;
+                        representatives[p].1 = i;
+                    } else {
+                        keep[i] = false;
+                    }
+                },
+                None => representatives.push((record.query().start(), i)),
+            }
+        }
+    }
+}
+
+
+/// Whether all coordinates of two records are within `tolerance`.
+///
+/// Compares absolute differences, so any tolerance is safe.
+fn within(a: &PAF, b: &PAF, tolerance: u64) -> bool {
+    let close = |x: u64, y: u64| x.max(y) - x.min(y) <= tolerance;
+    close(a.query().start(), b.query().start())
+        && close(a.query().end(), b.query().end())
+        && close(a.target().start(), b.target().start())
+        && close(a.target().end(), b.target().end())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_dedup() {
+        let records = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60"),
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60"),
+            paf("q1\t100\t2\t100\t+\tt1\t1000\t1\t100\t95\t98\t60"),
+            paf("q1\t100\t2\t100\t-\tt1\t1000\t1\t100\t95\t98\t60"),
+            paf("q1\t100\t50\t100\t+\tt1\t1000\t50\t100\t50\t50\t60"),
+        ];
+
+        assert_eq!(dedup(records.clone(), None).len(), 4);
+
+        let near = dedup(records.clone(), Some(5));
+        assert_eq!(near.len(), 3);
+        assert_eq!(near[0].query().start(), 2);
+        assert_eq!(near[0].strand(), Strand::Plus);
+        assert_eq!(near[1].strand(), Strand::Minus);
+        assert_eq!(near[2].query().start(), 50);
+
+        // Any tolerance at all, without overflowing.
+        let all = dedup(records, Some(u64::MAX));
+        assert_eq!(all.len(), 2);
+    }
+}
@@ -0,0 +1,259 @@
+//! Per-base depth of primary alignments on the targets.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::paf::PAF;
+
+
+/// A run of target bases covered by the same number of alignments.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub struct DepthRun<'a> {
+    /// The target sequence name.
+    pub target: &'a str,
+    /// The 0-based start of the run.
+    pub start: u64,
+    /// The exclusive end of the run, as in BedGraph.
+    pub end: u64,
+    /// The number of primary alignments covering each base, at least 1.
+    pub depth: u32,
+}
+
+
+/// Writes the run as a BedGraph line, without a line ending.
+impl fmt::Display for DepthRun<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}\t{}", self.target, self.start, self.end, self.depth)
+    }
+}
+
+
+/// The run-length encoded depth of primary alignments on each target.
+///
+/// Runs are sorted by target name then position. Adjacent runs have
+/// different depths, and uncovered bases are omitted.
+pub fn depth_runs(records: &[PAF]) -> Vec<DepthRun<'_>> {
+    let mut events: BTreeMap<&str, Vec<(u64, i64)>> = BTreeMap::new();
+    for record in records.iter().filter(|r| r.is_primary()) {
+        let target = record.target();
+        if target.end() > target.start() {
+            let e = events.entry(target.name()).or_default();
+            e.push((target.start(), 1));
+            e.push((target.end(), -1));
+        }
+    }
+
+    let mut runs = Vec::new();
+    for (target, mut events) in events {
+        events.sort_unstable();
+
+        let mut depth: i64 = 0;
+        let mut i = 0;
+        while i < events.len() {
+            let pos = events[i].0;
+            while i < events.len() && events[i].0 == pos {
+                depth += events[i].1;
+                i += 1;
+            }
+
+            let next = match events.get(i) {
+                Some(&(next, _)) => next,
+                None => break,
+            };
+
+            if depth > 0 {
+                match runs.last_mut() {
+                    Some(DepthRun { target: t, end, depth: d, .. })
+                        if *t == target && *end == pos && *d as i64 == depth => *end = next,
+                    _ => runs.push(DepthRun { target, start: pos, end: next, depth: depth as u32 }),
+                }
+            }
+        }
+    }
+
+    runs
+}
+
+
+/// Write the depth of primary alignments as BedGraph lines.
+pub fn write_bedgraph<W: Write>(records: &[PAF], mut writer: W) -> Result<(), Error> {
+    for run in depth_runs(records) {
+        writeln!(writer, "{}", run).context(Io)?;
+    }
+    Ok(())
+}
+
+
+/// Drop records until no target base is covered more than `max_depth` times.
+///
+/// Records are considered from the lowest [`PAF::score`] up, with ties going
+/// to the shorter alignment and then the later record. Each is dropped if
+/// any base it covers is still deeper than `max_depth`, so records outside
+/// deep regions are always kept. All records count towards the depth, so
+/// filter out secondary alignments first if they should not. The kept
+/// records are returned in their input order.
+pub fn cap_coverage(records: &[PAF], max_depth: u32) -> Vec<PAF> {
+    let mut by_target: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        by_target.entry(record.target().name()).or_default().push(i);
+    }
+
+    let mut keep = vec![true; records.len()];
+    for (_, indices) in by_target {
+        let mut bounds: Vec<u64> = Vec::with_capacity(2 * indices.len());
+        for &i in &indices {
+            bounds.push(records[i].target().start());
+            bounds.push(records[i].target().end());
+        }
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let span = |i: usize| {
+            let target = records[i].target();
+            let start = bounds.binary_search(&target.start()).unwrap_or_else(|x| x);
+            let end = bounds.binary_search(&target.end()).unwrap_or_else(|x| x);
+            (start, end)
+        };
+
+        let mut tree = DepthTree::new(bounds.len().max(1));
+        for &i in &indices {
+            let (start, end) = span(i);
+            tree.add(start, end, 1);
+        }
+
+        let mut order = indices;
+        order.sort_by(|&a, &b| {
+            let (ra, rb) = (&records[a], &records[b]);
+            ra.score().cmp(&rb.score())
+                .then(ra.alnlen().cmp(&rb.alnlen()))
+                .then(b.cmp(&a))
+        });
+
+        for i in order {
+            let (start, end) = span(i);
+            if start < end && tree.max(start, end) > i64::from(max_depth) {
+                tree.add(start, end, -1);
+                keep[i] = false;
+            }
+        }
+    }
+
+    records
+        .iter()
+        .zip(keep)
+        .filter(|(_, k)| *k)
+        .map(|(r, _)| r.clone())
+        .collect()
+}
+
+
+/// A segment tree over positions `0..n`, supporting adding to a range and
+/// finding the maximum over a range.
+struct DepthTree {
+    n: usize,
+    max: Vec<i64>,
+    pending: Vec<i64>,
+}
+
+
+impl DepthTree {
+    fn new(n: usize) -> Self {
+        DepthTree { n, max: vec![0; 4 * n], pending: vec![0; 4 * n] }
+    }
+
+    /// Add `delta` to positions `[start, end)`.
+    fn add(&mut self, start: usize, end: usize, delta: i64) {
+        if start < end {
+            self.add_node(1, 0, self.n, start, end, delta);
+        }
+    }
+
+    /// The maximum over positions `[start, end)`.
+    fn max(&self, start: usize, end: usize) -> i64 {
+        self.max_node(1, 0, self.n, start, end)
+    }
+
+    fn add_node(&mut self, node: usize, lo: usize, hi: usize, start: usize, end: usize, delta: i64) {
+        if end <= lo || hi <= start {
+            return;
+        }
+        if start <= lo && hi <= end {
+            self.max[node] += delta;
+            self.pending[node] += delta;
+            return;
+        }
+
+        let mid = (lo + hi) / 2;
+        self.add_node(2 * node, lo, mid, start, end, delta);
+        self.add_node(2 * node + 1, mid, hi, start, end, delta);
+        self.max[node] = self.pending[node] + self.max[2 * node].max(self.max[2 * node + 1]);
+    }
+
+    fn max_node(&self, node: usize, lo: usize, hi: usize, start: usize, end: usize) -> i64 {
+        if end <= lo || hi <= start {
+            return i64::MIN;
+        }
+        if start <= lo && hi <= end {
+            return self.max[node];
+        }
+
+        let mid = (lo + hi) / 2;
+        let child = self.max_node(2 * node, lo, mid, start, end)
+            .max(self.max_node(2 * node + 1, mid, hi, start, end));
+        self.pending[node] + child
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_depth_runs() {
+        let records = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\ttp:A:P"),
+            paf("q2\t100\t0\t50\t+\tt1\t1000\t50\t100\t45\t50\t60"),
+            paf("q3\t100\t0\t50\t+\tt1\t1000\t100\t150\t45\t50\t60"),
+            paf("q4\t100\t0\t50\t+\tt1\t1000\t120\t170\t45\t50\t0\ttp:A:S"),
+            paf("q5\t100\t0\t50\t+\tt0\t1000\t200\t250\t45\t50\t60"),
+        ];
+
+        let mut output = Vec::new();
+        write_bedgraph(&records, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "t0\t200\t250\t1\n\
+             t1\t0\t50\t1\n\
+             t1\t50\t100\t2\n\
+             t1\t100\t150\t1\n"
+        );
+    }
+
+    #[test]
+    fn test_cap_coverage() {
+        let records = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60"),
+            paf("q2\t100\t0\t100\t+\tt1\t1000\t0\t100\t80\t100\t60"),
+            paf("q3\t100\t0\t60\t+\tt1\t1000\t40\t100\t50\t60\t60"),
+            paf("q4\t100\t0\t50\t+\tt1\t1000\t200\t250\t10\t50\t60"),
+            paf("q5\t100\t0\t50\t+\tt2\t1000\t0\t50\t10\t50\t60"),
+            paf("q6\t100\t0\t50\t+\tt2\t1000\t50\t100\t10\t50\t60"),
+        ];
+
+        let names = |records: &[PAF]| -> Vec<String> {
+            records.iter().map(|r| r.query().name().to_string()).collect()
+        };
+        assert_eq!(names(&cap_coverage(&records, 2)), vec!["q1", "q2", "q4", "q5", "q6"]);
+        assert_eq!(names(&cap_coverage(&records, 1)), vec!["q1", "q4", "q5", "q6"]);
+        assert_eq!(cap_coverage(&records, 0).len(), 0);
+    }
+}
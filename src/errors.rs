@@ -1,11 +1,18 @@
-use std::iter::repeat;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 use snafu::Snafu;
 use nom::error::{VerboseError, VerboseErrorKind};
-use nom::combinator::{cut, all_consuming};
 use nom::Offset;
 
+/// The errors raised while reading, parsing and writing PAF.
+///
+/// New variants may be added in future releases. Use the accessor methods
+/// to get at position information without matching on every variant.
 #[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+#[non_exhaustive]
 pub enum Error {
     #[snafu(display(
         "Error while parsing character. Expected any of '{}' but got {}.",
@@ -33,6 +40,89 @@ pub enum Error {
     EmptyLine { line_num: usize },
     #[snafu(display("Error while parsing line: expected paf line but got empty input."))]
     Empty,
+    #[snafu(display("Error while parsing BED line {}: {}", line_num, message))]
+    ParseBed { line_num: usize, message: String },
+    #[snafu(display("Error while parsing FASTA line {}: {}", line_num, message))]
+    ParseFasta { line_num: usize, message: String },
+    #[snafu(display("Error while parsing name mapping line {}: {}", line_num, message))]
+    ParseNameMap { line_num: usize, message: String },
+    #[snafu(display("No new name was given for '{}'.", name))]
+    MissingName { name: String },
+    #[snafu(display(
+        "Invalid interval {}:{}-{} on a sequence of length {}{}.",
+        name,
+        start,
+        end,
+        length,
+        line_num.map(|l| format!(" at line {}", l)).unwrap_or_default()
+    ))]
+    InvalidInterval { line_num: Option<usize>, name: String, length: u64, start: u64, end: u64 },
+    #[snafu(display("Sequences do not match the alignment: {}", message))]
+    InvalidAlignment { message: String },
+    #[snafu(display("Invalid optional field '{}': {}.", field, message))]
+    InvalidField { field: String, message: String },
+    #[snafu(display("Input is not sorted by {} at line {}.", by, line_num))]
+    Unsorted { line_num: usize, by: String },
+    #[cfg(feature = "std")]
+    #[snafu(display("'{}' is not sorted by {} at line {}.", path.display(), by, line_num))]
+    UnsortedFile { path: std::path::PathBuf, line_num: usize, by: String },
+    #[snafu(display("Batch {} was sent more than once.", seq))]
+    DuplicateBatch { seq: u64 },
+    #[snafu(display("Records are not sorted by {} at record index {}.", by, index))]
+    UnsortedRecords { index: usize, by: String },
+    #[snafu(display("Cannot score the alignment of '{}': {}", query, message))]
+    Rescore { query: String, message: String },
+    #[snafu(display("Unknown column '{}'.", name))]
+    UnknownColumn { name: String },
+    #[cfg(feature = "std")]
+    #[snafu(display("Could not open '{}': {}", path.display(), source))]
+    OpenPath { path: std::path::PathBuf, source: std::io::Error },
+    #[cfg(feature = "std")]
+    #[snafu(display("IO error: {}", source))]
+    Io { source: std::io::Error },
+}
+
+
+impl Error {
+    /// The 1-based line number the error occurred on, if known.
+    pub fn line_number(&self) -> Option<usize> {
+        match self {
+            Error::Parse { line_num, .. }
+            | Error::EmptyLine { line_num }
+            | Error::ParseBed { line_num, .. }
+            | Error::ParseFasta { line_num, .. }
+            | Error::ParseNameMap { line_num, .. }
+            | Error::Unsorted { line_num, .. } => Some(*line_num),
+            #[cfg(feature = "std")]
+            Error::UnsortedFile { line_num, .. } => Some(*line_num),
+            Error::InvalidInterval { line_num, .. } => *line_num,
+            _ => None,
+        }
+    }
+
+    /// The 0-based column within the line where parsing failed, if known.
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            Error::Parse { column, .. } | Error::ParseLine { column, .. } => Some(*column),
+            _ => None,
+        }
+    }
+
+    /// The text of the line that failed to parse, if known.
+    pub fn line(&self) -> Option<&str> {
+        match self {
+            Error::Parse { line, .. } | Error::ParseLine { line, .. } => Some(line),
+            _ => None,
+        }
+    }
+
+    /// Descriptions of what the parser expected, outermost last.
+    pub fn details(&self) -> Option<&[String]> {
+        match self {
+            Error::Parse { details, .. } | Error::ParseLine { details, .. } => Some(details),
+            _ => None,
+        }
+    }
 }
 
 
@@ -47,7 +137,7 @@ fn join_parse_details(line: &str, column: &usize, details: &[String]) -> String
 
     // This adds spaces before the indicator so that it aligns with the
     // troublesome line.
-    output += &repeat(' ').take(new_column).collect::<String>();
+    output += &" ".repeat(new_column);
     output += "^ ";
 
     // This just joins the strings.
@@ -79,7 +169,7 @@ fn check_empty_input(
 
 /// Find the line number and the column offset.
 /// This handles case that input is multiline.
-fn find_offset(initial: &usize, lines: &Vec<String>) -> (usize, usize) {
+fn find_offset(initial: &usize, lines: &[String]) -> (usize, usize) {
     let mut line_no = 0;
     let mut offset = *initial;
 
@@ -101,7 +191,7 @@ fn find_offset(initial: &usize, lines: &Vec<String>) -> (usize, usize) {
 fn push_errorkind(details: &mut Vec<String>, kind: &VerboseErrorKind) {
     match kind {
         VerboseErrorKind::Char(c) => {
-            details.push(format!("expected character '{}'", c.to_string()));
+            details.push(format!("expected character '{}'", c));
         }
         VerboseErrorKind::Context(s) => {
             details.push(s.to_string());
@@ -151,16 +241,72 @@ pub fn convert_error_str(
             super::errors::Error::Parse {
                 line_num: line_num + l,
                 line: lines[line_num].clone(),
-                column: column,
-                details: details
+                column,
+                details
             }
         },
         None => {
             super::errors::Error::ParseLine {
                 line: lines[line_num].clone(),
-                column: column,
-                details: details
+                column,
+                details
             }
         },
     }
 }
+
+
+
+/// transforms a nom `VerboseError` over bytes into a trace with input position information
+///
+/// Invalid utf-8 in the input is replaced, and error positions are moved back
+/// to the nearest character boundary of the replaced text.
+#[cfg(feature = "std")]
+pub(crate) fn convert_error_u8(
+    input: &[u8],
+    error: VerboseError<&[u8]>,
+    line_num_offset: Option<usize>,
+) -> crate::errors::Error {
+    let text = String::from_utf8_lossy(input);
+
+    let errors = error.errors
+        .into_iter()
+        .map(|(substring, kind)| {
+            let mut offset = input.offset(substring).min(text.len());
+            while !text.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            (&text[offset..], kind)
+        })
+        .collect();
+
+    convert_error_str(&text, VerboseError { errors }, line_num_offset)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_accessors() {
+        let err = crate::reader::parse_line(
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\tx",
+            3,
+            crate::reader::ParseMode::Strict,
+        ).unwrap_err();
+
+        assert_eq!(err.line_number(), Some(3));
+        assert_eq!(err.column(), Some(30));
+        assert_eq!(err.line(), Some("q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\tx"));
+        assert!(!err.details().unwrap().is_empty());
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_source() {
+        let err = Error::Io { source: std::io::Error::other("boom") };
+        assert_eq!(err.line_number(), None);
+        assert_eq!(err.source().unwrap().to_string(), "boom");
+    }
+}
@@ -0,0 +1,354 @@
+//! Export records as delimited text or JSON Lines with a chosen set of
+//! columns.
+
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::record::PafRecord;
+use crate::tags::TagValue;
+
+
+/// A column that can be exported.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub enum Column {
+    /// The query sequence name, column 1 (`qname`).
+    QueryName,
+    /// The query sequence length, column 2 (`qlen`).
+    QueryLength,
+    /// The query start, column 3 (`qstart`).
+    QueryStart,
+    /// The query end, column 4 (`qend`).
+    QueryEnd,
+    /// The relative strand, column 5 (`strand`).
+    Strand,
+    /// The target sequence name, column 6 (`tname`).
+    TargetName,
+    /// The target sequence length, column 7 (`tlen`).
+    TargetLength,
+    /// The target start, column 8 (`tstart`).
+    TargetStart,
+    /// The target end, column 9 (`tend`).
+    TargetEnd,
+    /// The number of matching bases, column 10 (`nmatch`).
+    NMatch,
+    /// The alignment length, column 11 (`alnlen`).
+    AlnLen,
+    /// The mapping quality, column 12 (`mapq`).
+    Mapq,
+    /// Column 10 over column 11, computed rather than read (`identity`).
+    Identity,
+    /// The value of the named optional tag, e.g. `NM`.
+    Tag(String),
+}
+
+
+impl Column {
+    /// All of the mandatory PAF columns, in file order.
+    pub fn mandatory() -> Vec<Column> {
+        vec![
+            Column::QueryName,
+            Column::QueryLength,
+            Column::QueryStart,
+            Column::QueryEnd,
+            Column::Strand,
+            Column::TargetName,
+            Column::TargetLength,
+            Column::TargetStart,
+            Column::TargetEnd,
+            Column::NMatch,
+            Column::AlnLen,
+            Column::Mapq,
+        ]
+    }
+
+    /// Parse a comma separated list of column names.
+    pub fn parse_list(s: &str) -> Result<Vec<Column>, Error> {
+        s.split(',').map(|c| c.trim().parse()).collect()
+    }
+
+    /// The value of the column for a record.
+    pub(crate) fn value<R: PafRecord + ?Sized>(&self, record: &R) -> Value {
+        match self {
+            Column::QueryName => Value::Str(record.query_name().to_string()),
+            Column::QueryLength => Value::UInt(record.query_length()),
+            Column::QueryStart => Value::UInt(record.query_start()),
+            Column::QueryEnd => Value::UInt(record.query_end()),
+            Column::Strand => Value::Str(record.strand().to_string()),
+            Column::TargetName => Value::Str(record.target_name().to_string()),
+            Column::TargetLength => Value::UInt(record.target_length()),
+            Column::TargetStart => Value::UInt(record.target_start()),
+            Column::TargetEnd => Value::UInt(record.target_end()),
+            Column::NMatch => Value::UInt(record.nmatch()),
+            Column::AlnLen => Value::UInt(record.alnlen()),
+            Column::Mapq => Value::UInt(u64::from(record.mapq())),
+            Column::Identity => Value::Float(record.identity()),
+            Column::Tag(name) => match record.tag(name) {
+                Some(TagValue::Int(i)) => Value::Int(i),
+                Some(TagValue::Float(f)) => Value::Float(f),
+                Some(v) => Value::Str(v.to_string()),
+                None => Value::Missing,
+            },
+        }
+    }
+}
+
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Column::QueryName => "qname",
+            Column::QueryLength => "qlen",
+            Column::QueryStart => "qstart",
+            Column::QueryEnd => "qend",
+            Column::Strand => "strand",
+            Column::TargetName => "tname",
+            Column::TargetLength => "tlen",
+            Column::TargetStart => "tstart",
+            Column::TargetEnd => "tend",
+            Column::NMatch => "nmatch",
+            Column::AlnLen => "alnlen",
+            Column::Mapq => "mapq",
+            Column::Identity => "identity",
+            Column::Tag(t) => t,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+
+impl FromStr for Column {
+    type Err = Error;
+
+    /// Parse a column name. Two character names are taken to be tags.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let column = match s {
+            "qname" => Column::QueryName,
+            "qlen" => Column::QueryLength,
+            "qstart" => Column::QueryStart,
+            "qend" => Column::QueryEnd,
+            "strand" => Column::Strand,
+            "tname" => Column::TargetName,
+            "tlen" => Column::TargetLength,
+            "tstart" => Column::TargetStart,
+            "tend" => Column::TargetEnd,
+            "nmatch" => Column::NMatch,
+            "alnlen" => Column::AlnLen,
+            "mapq" => Column::Mapq,
+            "identity" => Column::Identity,
+            t if t.len() == 2 && t.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                Column::Tag(t.to_string())
+            },
+            t => return Err(Error::UnknownColumn { name: t.to_string() }),
+        };
+        Ok(column)
+    }
+}
+
+
+/// An exported cell value.
+pub(crate) enum Value {
+    Str(String),
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Missing,
+}
+
+
+/// Write records as delimited text, e.g. CSV or TSV.
+pub struct DelimitedWriter<W: Write> {
+    inner: W,
+    columns: Vec<Column>,
+    delimiter: char,
+}
+
+
+impl<W: Write> DelimitedWriter<W> {
+    /// Construct a writer with an arbitrary delimiter.
+    pub fn new(inner: W, columns: Vec<Column>, delimiter: char) -> Self {
+        DelimitedWriter { inner, columns, delimiter }
+    }
+
+    /// Construct a comma separated writer.
+    pub fn csv(inner: W, columns: Vec<Column>) -> Self {
+        DelimitedWriter::new(inner, columns, ',')
+    }
+
+    /// Construct a tab separated writer.
+    pub fn tsv(inner: W, columns: Vec<Column>) -> Self {
+        DelimitedWriter::new(inner, columns, '\t')
+    }
+
+    /// Write a header line of column names.
+    pub fn write_header(&mut self) -> Result<(), Error> {
+        let cells: Vec<String> = self.columns
+            .iter()
+            .map(|c| self.quote(&c.to_string()))
+            .collect();
+        self.write_line(&cells)
+    }
+
+    /// Write the selected columns of a single record.
+    pub fn write_record<R: PafRecord + ?Sized>(&mut self, record: &R) -> Result<(), Error> {
+        let cells: Vec<String> = self.columns
+            .iter()
+            .map(|c| match c.value(record) {
+                Value::Str(s) => self.quote(&s),
+                Value::UInt(i) => i.to_string(),
+                Value::Int(i) => i.to_string(),
+                Value::Float(f) => f.to_string(),
+                Value::Missing => String::new(),
+            })
+            .collect();
+        self.write_line(&cells)
+    }
+
+    /// Flush the underlying sink.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush().context(Io)
+    }
+
+    /// Consume the writer, returning the underlying sink.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_line(&mut self, cells: &[String]) -> Result<(), Error> {
+        let line = cells.join(&self.delimiter.to_string());
+        writeln!(self.inner, "{}", line).context(Io)
+    }
+
+    /// Quote a cell if it contains the delimiter, a quote, or a newline.
+    fn quote(&self, s: &str) -> String {
+        if s.contains([self.delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    }
+}
+
+
+/// Write records as JSON Lines, one object per record.
+pub struct JsonLinesWriter<W: Write> {
+    inner: W,
+    columns: Vec<Column>,
+}
+
+
+impl<W: Write> JsonLinesWriter<W> {
+    /// Construct a new writer.
+    pub fn new(inner: W, columns: Vec<Column>) -> Self {
+        JsonLinesWriter { inner, columns }
+    }
+
+    /// Write the selected columns of a single record as a JSON object.
+    pub fn write_record<R: PafRecord + ?Sized>(&mut self, record: &R) -> Result<(), Error> {
+        let mut line = String::from("{");
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+
+            line.push_str(&json_string(&column.to_string()));
+            line.push(':');
+
+            match column.value(record) {
+                Value::Str(s) => line.push_str(&json_string(&s)),
+                Value::UInt(v) => line.push_str(&v.to_string()),
+                Value::Int(v) => line.push_str(&v.to_string()),
+                Value::Float(v) if v.is_finite() => line.push_str(&v.to_string()),
+                Value::Float(_) | Value::Missing => line.push_str("null"),
+            }
+        }
+        line.push('}');
+
+        writeln!(self.inner, "{}", line).context(Io)
+    }
+
+    /// Flush the underlying sink.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush().context(Io)
+    }
+
+    /// Consume the writer, returning the underlying sink.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+
+/// Format a string as a quoted and escaped JSON string.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut output = String::with_capacity(s.len() + 2);
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paf::PAF;
+
+    fn record() -> PAF {
+        "q,1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tNM:i:10\ttp:A:P"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        assert_eq!(
+            Column::parse_list("qname, tname,identity,NM").unwrap(),
+            vec![
+                Column::QueryName,
+                Column::TargetName,
+                Column::Identity,
+                Column::Tag("NM".to_string()),
+            ]
+        );
+        assert!(Column::parse_list("qname,bogus").is_err());
+    }
+
+    #[test]
+    fn test_csv() {
+        let columns = Column::parse_list("qname,tname,identity,mapq,de").unwrap();
+        let mut writer = DelimitedWriter::csv(Vec::new(), columns);
+        writer.write_header().unwrap();
+        writer.write_record(&record()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "qname,tname,identity,mapq,de\n\"q,1\",t1,0.9,60,\n"
+        );
+    }
+
+    #[test]
+    fn test_jsonl() {
+        let columns = Column::parse_list("qname,tstart,NM,tp,de").unwrap();
+        let mut writer = JsonLinesWriter::new(Vec::new(), columns);
+        writer.write_record(&record()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "{\"qname\":\"q,1\",\"tstart\":0,\"NM\":10,\"tp\":\"P\",\"de\":null}\n"
+        );
+    }
+}
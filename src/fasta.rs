@@ -0,0 +1,93 @@
+//! Reading sequences from FASTA, for operations that need the aligned
+//! bases rather than just the coordinates.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::io::BufRead;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+
+
+/// A source of sequences by name.
+///
+/// Implemented for maps of names to sequences, such as those returned by
+/// [`read_fasta`]. Implement it to fetch sequences from an indexed file or
+/// other store instead.
+pub trait Sequences {
+    /// The whole sequence with `name`, if known.
+    fn sequence(&self, name: &str) -> Option<&str>;
+}
+
+
+impl<S: BuildHasher> Sequences for HashMap<String, String, S> {
+    fn sequence(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|s| s.as_str())
+    }
+}
+
+
+impl Sequences for BTreeMap<String, String> {
+    fn sequence(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|s| s.as_str())
+    }
+}
+
+
+/// Read all sequences from a FASTA source.
+///
+/// The name is the header up to the first whitespace. Blank lines are
+/// skipped, and sequence lines are joined without changing their case.
+pub fn read_fasta<R: BufRead>(reader: R) -> Result<HashMap<String, String>, Error> {
+    let mut output = HashMap::new();
+    let mut current: Option<(String, String)> = None;
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.context(Io)?;
+        let line = line.trim_end();
+
+        if let Some(header) = line.strip_prefix('>') {
+            if let Some((name, seq)) = current.take() {
+                output.insert(name, seq);
+            }
+            let name = header.split_whitespace().next().ok_or_else(|| Error::ParseFasta {
+                line_num: i + 1,
+                message: "missing sequence name".to_string(),
+            })?;
+            current = Some((name.to_string(), String::new()));
+        } else if !line.is_empty() {
+            match current.as_mut() {
+                Some((_, seq)) => seq.push_str(line),
+                None => return Err(Error::ParseFasta {
+                    line_num: i + 1,
+                    message: "sequence before the first header".to_string(),
+                }),
+            }
+        }
+    }
+
+    if let Some((name, seq)) = current {
+        output.insert(name, seq);
+    }
+    Ok(output)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_fasta() {
+        let input = ">seq1 description\nACGT\nacgt\n\n>seq2\nNNNN\n";
+        let seqs = read_fasta(input.as_bytes()).unwrap();
+
+        assert_eq!(seqs.sequence("seq1"), Some("ACGTacgt"));
+        assert_eq!(seqs.sequence("seq2"), Some("NNNN"));
+        assert_eq!(seqs.sequence("seq3"), None);
+
+        let err = read_fasta("ACGT\n>seq1\n".as_bytes()).unwrap_err();
+        assert_eq!(err.line_number(), Some(1));
+    }
+}
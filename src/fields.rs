@@ -0,0 +1,257 @@
+//! Packed storage for the optional fields of a record.
+
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::Index;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::errors::Error;
+
+
+/// The optional fields of a record, packed into a single buffer.
+///
+/// Fields are stored tab separated in one string with the end offset of
+/// each, so a record holds two allocations however many tags it has,
+/// rather than one per tag.
+#[derive(Debug, Eq, PartialEq, Clone, Hash, Default)]
+pub struct Fields {
+    buf: String,
+    ends: Vec<u32>,
+}
+
+
+impl Fields {
+    /// Construct an empty set of fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of fields.
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Whether there are no fields.
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Get the field at index `i`.
+    pub fn get(&self, i: usize) -> Option<&str> {
+        let end = *self.ends.get(i)? as usize;
+        let start = if i == 0 { 0 } else { self.ends[i - 1] as usize + 1 };
+        Some(&self.buf[start..end])
+    }
+
+    /// Append a field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`Fields::try_push`] would return an error.
+    pub fn push(&mut self, field: &str) {
+        if let Err(e) = self.try_push(field) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Append a field, unless it can't be written as a PAF column.
+    ///
+    /// Returns `Error::InvalidField` if the field is empty or contains a tab
+    /// or line break, as it would not be read back as the same field, or if
+    /// the packed fields would exceed 4 GiB.
+    pub fn try_push(&mut self, field: &str) -> Result<(), Error> {
+        let invalid = |message: &str| Error::InvalidField {
+            field: field.to_string(),
+            message: message.to_string(),
+        };
+
+        if field.is_empty() {
+            return Err(invalid("fields can't be empty"));
+        }
+        if field.contains(&['\t', '\r', '\n'][..]) {
+            return Err(invalid("fields can't contain tabs or line breaks"));
+        }
+
+        let sep = if self.ends.is_empty() { 0 } else { 1 };
+        let end = u32::try_from(self.buf.len() + sep + field.len())
+            .map_err(|_| invalid("optional fields must be less than 4 GiB"))?;
+
+        if sep == 1 {
+            self.buf.push('\t');
+        }
+        self.buf.push_str(field);
+        self.ends.push(end);
+        Ok(())
+    }
+
+    /// Iterate over the fields.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { fields: self, front: 0, back: self.len() }
+    }
+
+    /// The fields joined by tabs, as written in a PAF line.
+    pub fn as_str(&self) -> &str {
+        &self.buf
+    }
+
+    /// Copy the fields into separate strings.
+    pub fn to_vec(&self) -> Vec<String> {
+        self.iter().map(String::from).collect()
+    }
+}
+
+
+impl fmt::Display for Fields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.buf)
+    }
+}
+
+
+/// Index the fields like a slice, e.g. `record.fields()[0]`.
+///
+/// # Panics
+///
+/// Panics if `i` is out of bounds.
+impl Index<usize> for Fields {
+    type Output = str;
+
+    fn index(&self, i: usize) -> &str {
+        match self.get(i) {
+            Some(field) => field,
+            None => panic!("index {} out of bounds for {} fields", i, self.len()),
+        }
+    }
+}
+
+
+/// Fields are ordered as a sequence of strings, like `Vec<String>`.
+impl Ord for Fields {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+
+impl PartialOrd for Fields {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+impl<S: AsRef<str>> PartialEq<[S]> for Fields {
+    fn eq(&self, other: &[S]) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a == b.as_ref())
+    }
+}
+
+
+impl<S: AsRef<str>> PartialEq<Vec<S>> for Fields {
+    fn eq(&self, other: &Vec<S>) -> bool {
+        self == other.as_slice()
+    }
+}
+
+
+/// Collect fields with [`Fields::push`], which panics on invalid fields.
+impl<S: AsRef<str>> FromIterator<S> for Fields {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut fields = Fields::new();
+        for field in iter {
+            fields.push(field.as_ref());
+        }
+        fields
+    }
+}
+
+
+impl From<Vec<String>> for Fields {
+    fn from(fields: Vec<String>) -> Self {
+        fields.into_iter().collect()
+    }
+}
+
+
+impl<'a> IntoIterator for &'a Fields {
+    type Item = &'a str;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+
+/// Iterator returned by [`Fields::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    fields: &'a Fields,
+    front: usize,
+    back: usize,
+}
+
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.fields.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.back - self.front;
+        (n, Some(n))
+    }
+}
+
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.fields.get(self.back)
+    }
+}
+
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        let mut fields: Fields = vec!["tp:A:P", "NM:i:0", "cg:Z:10M"].into_iter().collect();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields.get(1), Some("NM:i:0"));
+        assert_eq!(fields.get(2), Some("cg:Z:10M"));
+        assert_eq!(fields.get(3), None);
+        assert_eq!(&fields[2], "cg:Z:10M");
+        assert_eq!(fields.as_str(), "tp:A:P\tNM:i:0\tcg:Z:10M");
+        assert_eq!(fields.iter().next_back(), Some("cg:Z:10M"));
+        assert_eq!(fields, vec!["tp:A:P", "NM:i:0", "cg:Z:10M"]);
+
+        // Fields that wouldn't read back the same are rejected.
+        assert!(fields.try_push("").is_err());
+        assert!(fields.try_push("xa:Z:a\tb").is_err());
+        assert!(Fields::new().try_push("").is_err());
+        assert_eq!(fields.len(), 3);
+
+        let a: Fields = vec!["b"].into_iter().collect();
+        let b: Fields = vec!["a", "z"].into_iter().collect();
+        assert!(a > b);
+        assert!(Fields::new().is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! A small expression language for filtering records at run time.
+//!
+//! Expressions compare columns and tags with numbers or quoted strings, and
+//! combine comparisons with `&&`, `||`, `!` and parentheses, e.g.
+//! `mapq >= 30 && alnlen > 5000 && tag('de') < 0.05`.
+//!
+//! Columns use the names from [`Column`]: `qname`, `qlen`, `qstart`,
+//! `qend`, `strand`, `tname`, `tlen`, `tstart`, `tend`, `nmatch`, `alnlen`,
+//! `mapq` and `identity`. Tags are written `tag('NM')`, or as a bare two
+//! character name such as `NM`. A comparison involving a missing tag, or a
+//! number with a string, is false.
+
+use std::str::FromStr;
+
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{char, multispace0};
+use nom::combinator::{all_consuming, cut, map, map_res};
+use nom::error::{context, VerboseError};
+use nom::multi::many0;
+use nom::number::complete::recognize_float;
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+
+use crate::errors::Error;
+use crate::export::{Column, Value};
+use crate::record::PafRecord;
+
+
+type Res<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
+
+
+/// A comparison operator.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+
+/// One side of a comparison.
+#[derive(Debug, PartialEq, Clone)]
+enum Operand {
+    Column(Column),
+    Number(f64),
+    Str(String),
+}
+
+
+#[derive(Debug, PartialEq, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, Op, Operand),
+}
+
+
+/// A compiled filter expression.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Filter(Expr);
+
+
+impl Filter {
+    /// Whether the record passes the filter.
+    pub fn matches<R: PafRecord + ?Sized>(&self, record: &R) -> bool {
+        eval(&self.0, record)
+    }
+}
+
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, expr) = all_consuming(cut(terminated(or_expr, multispace0)))(s)
+            .map_err(|e| {
+                match e {
+                    nom::Err::Error(ei) | nom::Err::Failure(ei) => {
+                        crate::errors::convert_error_str(s, ei, None)
+                    },
+                    _ => unreachable!(),
+                }
+            })?;
+
+        Ok(Filter(expr))
+    }
+}
+
+
+fn eval<R: PafRecord + ?Sized>(expr: &Expr, record: &R) -> bool {
+    match expr {
+        Expr::Or(a, b) => eval(a, record) || eval(b, record),
+        Expr::And(a, b) => eval(a, record) && eval(b, record),
+        Expr::Not(a) => !eval(a, record),
+        Expr::Compare(a, op, b) => compare(resolve(a, record), *op, resolve(b, record)),
+    }
+}
+
+
+/// A resolved operand, either a number or a string.
+enum Resolved {
+    Number(f64),
+    Str(String),
+    Missing,
+}
+
+
+fn resolve<R: PafRecord + ?Sized>(operand: &Operand, record: &R) -> Resolved {
+    match operand {
+        Operand::Number(n) => Resolved::Number(*n),
+        Operand::Str(s) => Resolved::Str(s.clone()),
+        Operand::Column(c) => match c.value(record) {
+            Value::UInt(i) => Resolved::Number(i as f64),
+            Value::Int(i) => Resolved::Number(i as f64),
+            Value::Float(f) => Resolved::Number(f),
+            Value::Str(s) => Resolved::Str(s),
+            Value::Missing => Resolved::Missing,
+        },
+    }
+}
+
+
+fn compare(a: Resolved, op: Op, b: Resolved) -> bool {
+    let ordering = match (a, b) {
+        (Resolved::Number(a), Resolved::Number(b)) => a.partial_cmp(&b),
+        (Resolved::Str(a), Resolved::Str(b)) => Some(a.cmp(&b)),
+        _ => None,
+    };
+
+    match ordering {
+        Some(o) => match op {
+            Op::Eq => o.is_eq(),
+            Op::Ne => o.is_ne(),
+            Op::Lt => o.is_lt(),
+            Op::Le => o.is_le(),
+            Op::Gt => o.is_gt(),
+            Op::Ge => o.is_ge(),
+        },
+        None => false,
+    }
+}
+
+
+fn ws<'a, O, F>(parser: F) -> impl Fn(&'a str) -> Res<'a, O>
+where
+    F: Fn(&'a str) -> Res<'a, O>,
+{
+    delimited(multispace0, parser, multispace0)
+}
+
+
+fn quoted(input: &str) -> Res<'_, String> {
+    context(
+        "quoted string",
+        map(
+            alt((
+                delimited(char('\''), take_while(|c| c != '\''), char('\'')),
+                delimited(char('"'), take_while(|c| c != '"'), char('"')),
+            )),
+            |s: &str| s.to_string(),
+        ),
+    )(input)
+}
+
+
+fn operand(input: &str) -> Res<'_, Operand> {
+    let tag_call = preceded(
+        pair(tag("tag"), multispace0),
+        delimited(char('('), ws(quoted), char(')')),
+    );
+    let column = map_res(
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+        |s: &str| s.parse::<Column>(),
+    );
+
+    context(
+        "column, tag, number or string",
+        ws(alt((
+            map(tag_call, |t| Operand::Column(Column::Tag(t))),
+            map(map_res(recognize_float, |s: &str| s.parse::<f64>()), Operand::Number),
+            map(quoted, Operand::Str),
+            map(column, Operand::Column),
+        ))),
+    )(input)
+}
+
+
+fn op(input: &str) -> Res<'_, Op> {
+    context(
+        "comparison operator",
+        alt((
+            map(tag(">="), |_| Op::Ge),
+            map(tag("<="), |_| Op::Le),
+            map(tag("=="), |_| Op::Eq),
+            map(tag("!="), |_| Op::Ne),
+            map(tag(">"), |_| Op::Gt),
+            map(tag("<"), |_| Op::Lt),
+        )),
+    )(input)
+}
+
+
+fn atom(input: &str) -> Res<'_, Expr> {
+    ws(alt((
+        delimited(char('('), or_expr, cut(char(')'))),
+        map(preceded(char('!'), atom), |e| Expr::Not(Box::new(e))),
+        map(tuple((operand, op, cut(operand))), |(a, o, b)| Expr::Compare(a, o, b)),
+    )))(input)
+}
+
+
+fn and_expr(input: &str) -> Res<'_, Expr> {
+    let (input, first) = atom(input)?;
+    let (input, rest) = many0(preceded(tag("&&"), cut(atom)))(input)?;
+    let expr = rest.into_iter().fold(first, |a, b| Expr::And(Box::new(a), Box::new(b)));
+    Ok((input, expr))
+}
+
+
+fn or_expr(input: &str) -> Res<'_, Expr> {
+    let (input, first) = and_expr(input)?;
+    let (input, rest) = many0(preceded(tag("||"), cut(and_expr)))(input)?;
+    let expr = rest.into_iter().fold(first, |a, b| Expr::Or(Box::new(a), Box::new(b)));
+    Ok((input, expr))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paf::PAF;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_filter() {
+        let good = paf("q1\t10000\t0\t9000\t+\tt1\t20000\t0\t9000\t8800\t9000\t60\tde:f:0.01\ttp:A:P");
+        let bad = paf("q2\t10000\t0\t9000\t-\tt1\t20000\t0\t9000\t8000\t9000\t10\tde:f:0.1");
+        let no_tag = paf("q3\t10000\t0\t9000\t+\tchr2\t20000\t0\t9000\t8800\t9000\t60");
+
+        let filter: Filter = "mapq >= 30 && alnlen > 5000 && tag('de') < 0.05".parse().unwrap();
+        assert!(filter.matches(&good));
+        assert!(!filter.matches(&bad));
+        assert!(!filter.matches(&no_tag));
+
+        let filter: Filter = "!(strand == '-') && (tname == \"chr2\" || tp == 'P')".parse().unwrap();
+        assert!(filter.matches(&good));
+        assert!(!filter.matches(&bad));
+        assert!(filter.matches(&no_tag));
+
+        assert!("mapq >=".parse::<Filter>().is_err());
+        assert!("mapq > 1 &&".parse::<Filter>().is_err());
+        assert!("bogus > 1".parse::<Filter>().is_err());
+    }
+}
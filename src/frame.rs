@@ -0,0 +1,346 @@
+//! A column-oriented container for large alignment sets.
+//!
+//! Sequence names are interned once, and each mandatory column is stored as
+//! its own vector, which is far more compact than a `Vec<PAF>` and allows
+//! filters and summaries to be computed column-at-a-time.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+
+use crate::errors::Error;
+use crate::fields::Fields;
+use crate::paf::{Locus, Strand, PAF};
+use crate::reader::PafReader;
+use crate::tags::{Tag, TagValue};
+
+
+/// A struct-of-arrays table of PAF records.
+#[derive(Debug, Clone, Default)]
+pub struct PafFrame {
+    names: Vec<String>,
+    name_ids: HashMap<String, u32>,
+    query_name: Vec<u32>,
+    query_length: Vec<u64>,
+    query_start: Vec<u64>,
+    query_end: Vec<u64>,
+    strand: Vec<Strand>,
+    target_name: Vec<u32>,
+    target_length: Vec<u64>,
+    target_start: Vec<u64>,
+    target_end: Vec<u64>,
+    nmatch: Vec<u64>,
+    alnlen: Vec<u64>,
+    mapq: Vec<u8>,
+    tags: BTreeMap<String, Vec<Option<TagValue>>>,
+}
+
+
+impl PafFrame {
+    /// Construct an empty frame keeping the tags named in `tags` as columns.
+    ///
+    /// All other optional fields are discarded when records are added.
+    pub fn new(tags: &[&str]) -> Self {
+        PafFrame {
+            tags: tags.iter().map(|t| (t.to_string(), Vec::new())).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Read all records from `reader` into a new frame.
+    pub fn from_reader<R: BufRead>(reader: PafReader<R>, tags: &[&str]) -> Result<Self, Error> {
+        let mut frame = PafFrame::new(tags);
+        for record in reader {
+            frame.push(&record?);
+        }
+        Ok(frame)
+    }
+
+    /// The number of records.
+    pub fn len(&self) -> usize {
+        self.mapq.len()
+    }
+
+    /// Whether the frame has no records.
+    pub fn is_empty(&self) -> bool {
+        self.mapq.is_empty()
+    }
+
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.name_ids.get(name) {
+            return *id;
+        }
+
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.name_ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// Append a record to the frame.
+    pub fn push(&mut self, record: &PAF) {
+        let qid = self.intern(record.query().name());
+        let tid = self.intern(record.target().name());
+
+        self.query_name.push(qid);
+        self.query_length.push(record.query().length());
+        self.query_start.push(record.query().start());
+        self.query_end.push(record.query().end());
+        self.strand.push(record.strand());
+        self.target_name.push(tid);
+        self.target_length.push(record.target().length());
+        self.target_start.push(record.target().start());
+        self.target_end.push(record.target().end());
+        self.nmatch.push(record.nmatch());
+        self.alnlen.push(record.alnlen());
+        self.mapq.push(record.mapq());
+
+        for (name, column) in self.tags.iter_mut() {
+            column.push(record.tag(name));
+        }
+    }
+
+    /// The interned sequence name for `id`.
+    pub fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    /// The interned id for a sequence name, if present.
+    pub fn name_id(&self, name: &str) -> Option<u32> {
+        self.name_ids.get(name).copied()
+    }
+
+    /// The interned query name ids.
+    pub fn query_names(&self) -> &[u32] {
+        &self.query_name
+    }
+
+    /// The query sequence lengths.
+    pub fn query_lengths(&self) -> &[u64] {
+        &self.query_length
+    }
+
+    /// The query start coordinates.
+    pub fn query_starts(&self) -> &[u64] {
+        &self.query_start
+    }
+
+    /// The query end coordinates.
+    pub fn query_ends(&self) -> &[u64] {
+        &self.query_end
+    }
+
+    /// The relative strands.
+    pub fn strands(&self) -> &[Strand] {
+        &self.strand
+    }
+
+    /// The interned target name ids.
+    pub fn target_names(&self) -> &[u32] {
+        &self.target_name
+    }
+
+    /// The target sequence lengths.
+    pub fn target_lengths(&self) -> &[u64] {
+        &self.target_length
+    }
+
+    /// The target start coordinates.
+    pub fn target_starts(&self) -> &[u64] {
+        &self.target_start
+    }
+
+    /// The target end coordinates.
+    pub fn target_ends(&self) -> &[u64] {
+        &self.target_end
+    }
+
+    /// The numbers of matching bases.
+    pub fn nmatches(&self) -> &[u64] {
+        &self.nmatch
+    }
+
+    /// The alignment lengths.
+    pub fn alnlens(&self) -> &[u64] {
+        &self.alnlen
+    }
+
+    /// The mapping qualities.
+    pub fn mapqs(&self) -> &[u8] {
+        &self.mapq
+    }
+
+    /// The column for a tag kept at construction.
+    pub fn tag_column(&self, name: &str) -> Option<&[Option<TagValue>]> {
+        self.tags.get(name).map(|c| c.as_slice())
+    }
+
+    /// The per-record identity, `nmatch / alnlen`.
+    pub fn identity(&self) -> Vec<f64> {
+        self.nmatch
+            .iter()
+            .zip(&self.alnlen)
+            .map(|(m, a)| if *a == 0 { 0.0 } else { *m as f64 / *a as f64 })
+            .collect()
+    }
+
+    /// A mask of records with mapping quality of at least `mapq`.
+    pub fn mask_mapq(&self, mapq: u8) -> Vec<bool> {
+        self.mapq.iter().map(|m| *m >= mapq).collect()
+    }
+
+    /// A mask of records with alignment length of at least `alnlen`.
+    pub fn mask_alnlen(&self, alnlen: u64) -> Vec<bool> {
+        self.alnlen.iter().map(|a| *a >= alnlen).collect()
+    }
+
+    /// A mask of records aligned to the target named `name`.
+    pub fn mask_target(&self, name: &str) -> Vec<bool> {
+        match self.name_id(name) {
+            Some(id) => self.target_name.iter().map(|t| *t == id).collect(),
+            None => vec![false; self.len()],
+        }
+    }
+
+    /// Select the records where `mask` is true into a new frame.
+    ///
+    /// The name table is shared with the original, so ids remain valid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mask` is not the same length as the frame.
+    pub fn filter(&self, mask: &[bool]) -> PafFrame {
+        assert_eq!(mask.len(), self.len(), "mask length must match the frame length");
+
+        fn select<T: Clone>(column: &[T], mask: &[bool]) -> Vec<T> {
+            column
+                .iter()
+                .zip(mask)
+                .filter(|(_, keep)| **keep)
+                .map(|(v, _)| v.clone())
+                .collect()
+        }
+
+        PafFrame {
+            names: self.names.clone(),
+            name_ids: self.name_ids.clone(),
+            query_name: select(&self.query_name, mask),
+            query_length: select(&self.query_length, mask),
+            query_start: select(&self.query_start, mask),
+            query_end: select(&self.query_end, mask),
+            strand: select(&self.strand, mask),
+            target_name: select(&self.target_name, mask),
+            target_length: select(&self.target_length, mask),
+            target_start: select(&self.target_start, mask),
+            target_end: select(&self.target_end, mask),
+            nmatch: select(&self.nmatch, mask),
+            alnlen: select(&self.alnlen, mask),
+            mapq: select(&self.mapq, mask),
+            tags: self.tags.iter().map(|(k, v)| (k.clone(), select(v, mask))).collect(),
+        }
+    }
+
+    /// The sum of the alignment lengths.
+    pub fn total_alnlen(&self) -> u64 {
+        self.alnlen.iter().sum()
+    }
+
+    /// The sum of the matching bases.
+    pub fn total_nmatch(&self) -> u64 {
+        self.nmatch.iter().sum()
+    }
+
+    /// The identity over all records, weighted by alignment length.
+    pub fn mean_identity(&self) -> Option<f64> {
+        let alnlen = self.total_alnlen();
+        if alnlen == 0 {
+            None
+        } else {
+            Some(self.total_nmatch() as f64 / alnlen as f64)
+        }
+    }
+
+    /// Sum the alignment lengths per target, keyed by target name.
+    pub fn alnlen_by_target(&self) -> BTreeMap<&str, u64> {
+        let mut output = BTreeMap::new();
+        for (tid, alnlen) in self.target_name.iter().zip(&self.alnlen) {
+            *output.entry(self.name(*tid)).or_insert(0) += alnlen;
+        }
+        output
+    }
+
+    /// Reconstruct the record at row `i`, with only the kept tags.
+    pub fn row(&self, i: usize) -> PAF {
+        let query = Locus::new(
+            self.name(self.query_name[i]).to_string(),
+            self.query_length[i],
+            self.query_start[i],
+            self.query_end[i],
+        );
+        let target = Locus::new(
+            self.name(self.target_name[i]).to_string(),
+            self.target_length[i],
+            self.target_start[i],
+            self.target_end[i],
+        );
+        let fields: Fields = self.tags
+            .iter()
+            .filter_map(|(name, column)| {
+                column[i].clone().map(|v| Tag::new(name.clone(), v).to_string())
+            })
+            .collect();
+
+        PAF::new(
+            query,
+            self.strand[i],
+            target,
+            self.nmatch[i],
+            self.alnlen[i],
+            self.mapq[i],
+            fields,
+        )
+    }
+}
+
+
+impl<'a> Extend<&'a PAF> for PafFrame {
+    fn extend<T: IntoIterator<Item = &'a PAF>>(&mut self, iter: T) {
+        for record in iter {
+            self.push(record);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "\
+q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tNM:i:10\ttp:A:P
+q2\t100\t0\t50\t-\tt2\t1000\t10\t60\t50\t50\t10\ttp:A:S
+q1\t100\t0\t100\t+\tt2\t1000\t500\t600\t80\t100\t0\tNM:i:20
+";
+
+    #[test]
+    fn test_frame() {
+        let frame = PafFrame::from_reader(PafReader::new(INPUT.as_bytes()), &["NM"]).unwrap();
+
+        assert_eq!(frame.len(), 3);
+        assert_eq!(frame.query_names(), &[0, 2, 0]);
+        assert_eq!(frame.name(1), "t1");
+        assert_eq!(frame.total_alnlen(), 250);
+        assert_eq!(frame.mean_identity(), Some(220.0 / 250.0));
+        assert_eq!(
+            frame.tag_column("NM").unwrap(),
+            &[Some(TagValue::Int(10)), None, Some(TagValue::Int(20))]
+        );
+
+        let filtered = frame.filter(&frame.mask_mapq(10));
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(filtered.alnlen_by_target().get("t2"), Some(&50));
+        assert_eq!(
+            filtered.row(0).to_string(),
+            "q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tNM:i:10"
+        );
+    }
+}
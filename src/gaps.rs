@@ -0,0 +1,168 @@
+//! Reporting the regions of targets and queries covered, or not covered, by
+//! alignments.
+
+use std::collections::BTreeMap;
+
+use crate::bed::BedRecord;
+use crate::paf::{Locus, PAF};
+use crate::set::{merge_spans, merged_intervals};
+
+
+/// The parts of a query covered by its alignments.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct QueryCoverage {
+    /// Sorted, non-overlapping covered intervals, in forward coordinates.
+    pub intervals: Vec<(u64, u64)>,
+    /// The total length of the intervals.
+    pub covered: u64,
+    /// The query length.
+    pub length: u64,
+}
+
+
+impl QueryCoverage {
+    /// The number of query bases not covered by any alignment.
+    pub fn unaligned(&self) -> u64 {
+        self.length.saturating_sub(self.covered)
+    }
+
+    /// The fraction of the query covered, or 0 for an empty query.
+    pub fn fraction(&self) -> f64 {
+        if self.length == 0 {
+            0.0
+        } else {
+            self.covered as f64 / self.length as f64
+        }
+    }
+}
+
+
+/// Merge the query intervals of the records for a single query.
+///
+/// Every record counts, whatever its name, so group records by query
+/// first, e.g. with [`index_by_query`]. Overlapping alignments, such as
+/// secondary ones, are only counted once.
+///
+/// [`index_by_query`]: crate::group::index_by_query
+pub fn query_covered_intervals(records: &[PAF]) -> QueryCoverage {
+    let mut intervals: Vec<(u64, u64)> = records
+        .iter()
+        .map(|r| (r.query().start(), r.query().end()))
+        .filter(|(start, end)| end > start)
+        .collect();
+    merge_spans(&mut intervals);
+
+    QueryCoverage {
+        covered: intervals.iter().map(|(s, e)| e - s).sum(),
+        length: records.iter().map(|r| r.query().length()).max().unwrap_or(0),
+        intervals,
+    }
+}
+
+
+/// The unaligned intervals of each target at least `min_size` bases long.
+///
+/// Every record counts towards coverage, so filter out secondary or low
+/// quality alignments first if they should not hide a gap. Targets with no
+/// records at all are not reported, as their names are not known.
+pub fn target_gaps(records: &[PAF], min_size: u64) -> Vec<BedRecord> {
+    gaps(records, PAF::target, min_size)
+}
+
+
+/// The unaligned intervals of each query at least `min_size` bases long.
+///
+/// Intervals are in forward query coordinates, whatever the strand.
+pub fn query_gaps(records: &[PAF], min_size: u64) -> Vec<BedRecord> {
+    gaps(records, PAF::query, min_size)
+}
+
+
+/// The complement of the loci chosen by `locus`, sorted by name and start.
+fn gaps<F>(records: &[PAF], locus: F, min_size: u64) -> Vec<BedRecord>
+where
+    F: Fn(&PAF) -> &Locus,
+{
+    let mut lengths: BTreeMap<&str, u64> = BTreeMap::new();
+    for record in records {
+        let l = locus(record);
+        let length = lengths.entry(l.name()).or_default();
+        *length = (*length).max(l.length());
+    }
+    let covered = merged_intervals(records, &locus);
+
+    let mut output = Vec::new();
+    for (name, length) in lengths {
+        let mut start = 0;
+        let spans = covered.get(name).map(|s| s.as_slice()).unwrap_or(&[]);
+        for &(s, e) in spans.iter().chain(std::iter::once(&(length, length))) {
+            let end = s.min(length);
+            if end > start && end - start >= min_size.max(1) {
+                output.push(BedRecord::new(name.to_string(), start, end));
+            }
+            start = start.max(e);
+        }
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_gaps() {
+        let records = vec![
+            paf("q1\t1000\t0\t400\t+\tt1\t5000\t100\t500\t400\t400\t60"),
+            paf("q1\t1000\t500\t1000\t-\tt1\t5000\t450\t950\t500\t500\t60"),
+            paf("q2\t300\t0\t300\t+\tt1\t5000\t960\t1260\t300\t300\t60"),
+            paf("q3\t200\t20\t200\t+\tt2\t200\t0\t180\t180\t180\t60"),
+        ];
+
+        let intervals = |gaps: Vec<BedRecord>| -> Vec<(String, u64, u64)> {
+            gaps.into_iter().map(|b| (b.chrom, b.start, b.end)).collect()
+        };
+        assert_eq!(
+            intervals(target_gaps(&records, 1)),
+            vec![
+                ("t1".to_string(), 0, 100),
+                ("t1".to_string(), 950, 960),
+                ("t1".to_string(), 1260, 5000),
+                ("t2".to_string(), 180, 200),
+            ]
+        );
+        assert_eq!(
+            intervals(target_gaps(&records, 50)),
+            vec![("t1".to_string(), 0, 100), ("t1".to_string(), 1260, 5000)]
+        );
+        assert_eq!(
+            intervals(query_gaps(&records, 1)),
+            vec![("q1".to_string(), 400, 500), ("q3".to_string(), 0, 20)]
+        );
+    }
+
+    #[test]
+    fn test_query_covered_intervals() {
+        let records = vec![
+            paf("q1\t1000\t0\t400\t+\tt1\t5000\t100\t500\t400\t400\t60"),
+            paf("q1\t1000\t500\t1000\t-\tt1\t5000\t450\t950\t500\t500\t60"),
+            paf("q1\t1000\t300\t450\t+\tt2\t5000\t0\t150\t150\t150\t0"),
+            paf("q1\t1000\t400\t500\t+\tt2\t5000\t0\t100\t100\t100\t0"),
+        ];
+
+        let coverage = query_covered_intervals(&records[..2]);
+        assert_eq!(coverage.intervals, vec![(0, 400), (500, 1000)]);
+        assert_eq!((coverage.covered, coverage.unaligned()), (900, 100));
+        assert_eq!(coverage.fraction(), 0.9);
+
+        let coverage = query_covered_intervals(&records);
+        assert_eq!(coverage.intervals, vec![(0, 1000)]);
+        assert_eq!(coverage.unaligned(), 0);
+        assert_eq!(query_covered_intervals(&[]), QueryCoverage::default());
+    }
+}
@@ -0,0 +1,74 @@
+//! Grouping of records by sequence name.
+
+use std::collections::HashMap;
+
+use crate::paf::{Locus, PAF};
+
+
+/// Group records by target name, preserving input order within each group.
+pub fn index_by_target<I>(records: I) -> HashMap<String, Vec<PAF>>
+where
+    I: IntoIterator<Item = PAF>,
+{
+    index_by(records, PAF::target)
+}
+
+
+/// Group records by query name, preserving input order within each group.
+pub fn index_by_query<I>(records: I) -> HashMap<String, Vec<PAF>>
+where
+    I: IntoIterator<Item = PAF>,
+{
+    index_by(records, PAF::query)
+}
+
+
+/// Group records by the name of a locus.
+///
+/// The name is only copied the first time it is seen, so grouping many
+/// records against few sequences doesn't allocate a key per record.
+fn index_by<I, F>(records: I, locus: F) -> HashMap<String, Vec<PAF>>
+where
+    I: IntoIterator<Item = PAF>,
+    F: Fn(&PAF) -> &Locus,
+{
+    let mut groups: HashMap<String, Vec<PAF>> = HashMap::new();
+    for record in records {
+        match groups.get_mut(locus(&record).name()) {
+            Some(group) => group.push(record),
+            None => {
+                let name = locus(&record).name().to_string();
+                groups.insert(name, vec![record]);
+            },
+        }
+    }
+    groups
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_index_by_target() {
+        let records = vec![
+            paf("q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60"),
+            paf("q2\t10\t0\t10\t+\tt2\t20\t0\t10\t10\t10\t60"),
+            paf("q3\t10\t0\t10\t+\tt1\t20\t5\t15\t10\t10\t60"),
+        ];
+
+        let by_target = index_by_target(records.clone());
+        assert_eq!(by_target.len(), 2);
+        assert_eq!(by_target["t1"], vec![records[0].clone(), records[2].clone()]);
+        assert_eq!(by_target["t2"], vec![records[1].clone()]);
+
+        let by_query = index_by_query(records);
+        assert_eq!(by_query.len(), 3);
+        assert_eq!(by_query["q2"][0].target().name(), "t2");
+    }
+}
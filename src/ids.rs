@@ -0,0 +1,165 @@
+//! Dense integer IDs for sequence names, for building graphs from
+//! alignments.
+//!
+//! Queries and targets share one [`NameTable`], so a sequence that appears
+//! as both gets the same ID and the IDs can index the nodes of an overlap
+//! or adjacency graph directly.
+
+use std::collections::HashMap;
+
+use crate::errors::Error;
+use crate::paf::PAF;
+
+
+/// A two-way mapping between sequence names and IDs `0..len()`.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct NameTable {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+}
+
+
+impl NameTable {
+    /// Construct an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the ID of `name`, assigning the next free ID if it is new.
+    pub fn id(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// Get the ID of `name` without assigning one.
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    /// The name with the given ID.
+    pub fn name(&self, id: usize) -> Option<&str> {
+        self.names.get(id).map(|n| n.as_str())
+    }
+
+    /// All names, indexed by ID.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The number of distinct names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Whether no names have been assigned.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Assign IDs to the query and target of a record.
+    pub fn assign(&mut self, record: PAF) -> IdRecord {
+        IdRecord {
+            qid: self.id(record.query().name()),
+            tid: self.id(record.target().name()),
+            record,
+        }
+    }
+}
+
+
+/// A record with the IDs of its query and target.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IdRecord {
+    /// The ID of the query name in the [`NameTable`].
+    pub qid: usize,
+    /// The ID of the target name in the same table.
+    pub tid: usize,
+    /// The record itself.
+    pub record: PAF,
+}
+
+
+/// Assigns IDs to records as they are read.
+///
+/// Wraps any iterator of parse results, such as a
+/// [`PafReader`](crate::PafReader). The table grows as new names are seen
+/// and can be taken back with [`WithIds::into_names`] once reading is done.
+#[derive(Debug, Clone)]
+pub struct WithIds<I> {
+    iter: I,
+    names: NameTable,
+}
+
+
+impl<I> WithIds<I> {
+    /// Wrap an iterator, starting with an empty table.
+    pub fn new(iter: I) -> Self {
+        WithIds::with_names(iter, NameTable::new())
+    }
+
+    /// Wrap an iterator, continuing from an existing table.
+    pub fn with_names(iter: I, names: NameTable) -> Self {
+        WithIds { iter, names }
+    }
+
+    /// The names seen so far.
+    pub fn names(&self) -> &NameTable {
+        &self.names
+    }
+
+    /// Consume the adapter, returning the table.
+    pub fn into_names(self) -> NameTable {
+        self.names
+    }
+}
+
+
+impl<I: Iterator<Item = Result<PAF, Error>>> Iterator for WithIds<I> {
+    type Item = Result<IdRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.iter.next()?;
+        Some(record.map(|r| self.names.assign(r)))
+    }
+}
+
+
+/// Assign IDs to a set of records, returning them with the table.
+pub fn assign_ids<I: IntoIterator<Item = PAF>>(records: I) -> (Vec<IdRecord>, NameTable) {
+    let mut names = NameTable::new();
+    let records = records.into_iter().map(|r| names.assign(r)).collect();
+    (records, names)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::PafReader;
+
+    #[test]
+    fn test_with_ids() {
+        let input = "r1\t100\t0\t50\t+\tr2\t100\t50\t100\t50\t50\t60\n\
+                     r2\t100\t0\t50\t+\tr3\t100\t50\t100\t50\t50\t60\n\
+                     r1\t100\t60\t90\t-\tr3\t100\t0\t30\t30\t30\t60\n";
+
+        let mut reader = WithIds::new(PafReader::new(input.as_bytes()));
+        let edges: Vec<(usize, usize)> = reader
+            .by_ref()
+            .map(|r| r.map(|r| (r.qid, r.tid)))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(edges, vec![(0, 1), (1, 2), (0, 2)]);
+
+        let names = reader.into_names();
+        assert_eq!(names.names(), ["r1", "r2", "r3"]);
+        assert_eq!(names.get("r3"), Some(2));
+        assert_eq!(names.name(1), Some("r2"));
+        assert_eq!(names.get("r4"), None);
+    }
+}
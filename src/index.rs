@@ -0,0 +1,366 @@
+//! A tabix-style region index for coordinate-sorted, bgzip compressed PAF.
+//!
+//! For each target sequence the index stores a linear table giving, for
+//! every 16 kb window, the smallest virtual offset of a record overlapping
+//! that window. A region query seeks to the offset of its first window and
+//! scans forward until records start past the end of the region.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use snafu::ResultExt;
+
+use crate::bgzf::BgzfReader;
+use crate::errors::{Error, Io, OpenPath};
+use crate::paf::PAF;
+use crate::reader::{parse_line, ParseMode};
+
+
+/// The width of each linear index window, as in tabix.
+pub const WINDOW: u64 = 1 << 14;
+
+const MAGIC: &[u8; 4] = b"PAI\x01";
+const EMPTY: u64 = u64::MAX;
+
+
+/// The linear index for a single target sequence.
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct TargetIndex {
+    name: String,
+    linear: Vec<u64>,
+}
+
+
+/// A region index over a bgzip compressed PAF file sorted by target.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct PafIndex {
+    targets: Vec<TargetIndex>,
+    ids: HashMap<String, usize>,
+}
+
+
+impl PafIndex {
+    /// The conventional index path for a PAF file, `<path>.pai`.
+    pub fn index_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut path = path.as_ref().as_os_str().to_owned();
+        path.push(".pai");
+        PathBuf::from(path)
+    }
+
+    /// Build an index by reading a whole BGZF stream.
+    ///
+    /// Returns `Error::Unsorted` unless records are grouped by target and
+    /// sorted by target start within each group, and
+    /// `Error::InvalidInterval` for a record that doesn't fit in its target.
+    pub fn build<R: Read>(mut reader: BgzfReader<R>) -> Result<Self, Error> {
+        let mut index = PafIndex::default();
+        let mut line = String::new();
+        let mut line_num = 0;
+        let mut previous: Option<(usize, u64)> = None;
+
+        loop {
+            let offset = reader.virtual_offset();
+            line.clear();
+            if reader.read_line(&mut line).context(Io)? == 0 {
+                break;
+            }
+            line_num += 1;
+
+            let trimmed = line.trim_end_matches(&['\r', '\n'][..]);
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let record = parse_line(trimmed, line_num, ParseMode::Strict)?;
+            let target = record.target();
+            target.validate().map_err(|e| match e {
+                Error::InvalidInterval { name, length, start, end, .. } => {
+                    Error::InvalidInterval { line_num: Some(line_num), name, length, start, end }
+                },
+                e => e,
+            })?;
+
+            let id = match index.ids.get(target.name()) {
+                Some(id) => *id,
+                None => {
+                    index.targets.push(TargetIndex { name: target.name().to_string(), linear: Vec::new() });
+                    index.ids.insert(target.name().to_string(), index.targets.len() - 1);
+                    index.targets.len() - 1
+                },
+            };
+
+            match previous {
+                Some((pid, _)) if pid != id && id < index.targets.len() - 1 => {
+                    return Err(Error::Unsorted { line_num, by: "target".to_string() });
+                },
+                Some((pid, pstart)) if pid == id && target.start() < pstart => {
+                    return Err(Error::Unsorted { line_num, by: "target start".to_string() });
+                },
+                _ => {},
+            }
+            previous = Some((id, target.start()));
+
+            let first = target.start() / WINDOW;
+            let last = target.end().max(target.start().saturating_add(1)).saturating_sub(1) / WINDOW;
+            let linear = &mut index.targets[id].linear;
+            if linear.len() <= last as usize {
+                linear.resize(last as usize + 1, EMPTY);
+            }
+            for slot in &mut linear[first as usize..=last as usize] {
+                if *slot == EMPTY {
+                    *slot = offset;
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Build an index for a bgzip compressed PAF file.
+    pub fn build_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).context(OpenPath { path })?;
+        PafIndex::build(BgzfReader::new(BufReader::new(file)))
+    }
+
+    /// The target names in the index, in file order.
+    pub fn target_names(&self) -> impl Iterator<Item = &str> {
+        self.targets.iter().map(|t| t.name.as_str())
+    }
+
+    /// The virtual offset to start scanning from for a region.
+    ///
+    /// Returns `None` if no record can overlap the region.
+    pub fn start_offset(&self, name: &str, start: u64) -> Option<u64> {
+        let target = &self.targets[*self.ids.get(name)?];
+        let window = (start / WINDOW) as usize;
+        target.linear.get(window..)?.iter().copied().find(|o| *o != EMPTY)
+    }
+
+    /// Serialise the index.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&(self.targets.len() as u32).to_le_bytes());
+        for target in &self.targets {
+            buf.extend_from_slice(&(target.name.len() as u32).to_le_bytes());
+            buf.extend_from_slice(target.name.as_bytes());
+            buf.extend_from_slice(&(target.linear.len() as u32).to_le_bytes());
+            for offset in &target.linear {
+                buf.extend_from_slice(&offset.to_le_bytes());
+            }
+        }
+        writer.write_all(&buf).context(Io)?;
+        writer.flush().context(Io)
+    }
+
+    /// Deserialise an index written by [`PafIndex::write`].
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).context(Io)?;
+
+        let invalid = || Error::Io {
+            source: std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed PAF index"),
+        };
+
+        let mut pos: usize = 0;
+        let mut take = |n: usize| -> Result<&[u8], Error> {
+            let end = pos.checked_add(n).ok_or_else(invalid)?;
+            let slice = buf.get(pos..end).ok_or_else(invalid)?;
+            pos = end;
+            Ok(slice)
+        };
+        let read_u32 = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize;
+
+        if take(4)? != MAGIC {
+            return Err(invalid());
+        }
+
+        let mut index = PafIndex::default();
+        let ntargets = read_u32(take(4)?);
+        for id in 0..ntargets {
+            let len = read_u32(take(4)?);
+            let name = String::from_utf8(take(len)?.to_vec()).map_err(|_| invalid())?;
+            let nwindows = read_u32(take(4)?);
+            // Take all of the offsets first, so that a corrupt count can't
+            // allocate more than the file holds.
+            let offsets = take(nwindows.checked_mul(8).ok_or_else(invalid)?)?;
+            let linear = offsets
+                .chunks_exact(8)
+                .map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+                .collect();
+            index.ids.insert(name.clone(), id);
+            index.targets.push(TargetIndex { name, linear });
+        }
+
+        Ok(index)
+    }
+
+    /// Write the index to a file.
+    pub fn write_path<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let file = File::create(path).context(OpenPath { path })?;
+        self.write(BufWriter::new(file))
+    }
+
+    /// Read the index from a file.
+    pub fn read_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).context(OpenPath { path })?;
+        PafIndex::read(BufReader::new(file))
+    }
+}
+
+
+/// Random access to the records of an indexed, bgzip compressed PAF file.
+pub struct IndexedReader<R> {
+    reader: BgzfReader<R>,
+    index: PafIndex,
+}
+
+
+impl IndexedReader<BufReader<File>> {
+    /// Open a bgzip compressed PAF file and its `<path>.pai` index.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let index = PafIndex::read_path(PafIndex::index_path(path))?;
+        let file = File::open(path).context(OpenPath { path })?;
+        Ok(IndexedReader::new(BufReader::new(file), index))
+    }
+}
+
+
+impl<R: Read + Seek> IndexedReader<R> {
+    /// Construct a reader from a compressed source and its index.
+    pub fn new(inner: R, index: PafIndex) -> Self {
+        IndexedReader { reader: BgzfReader::new(inner), index }
+    }
+
+    /// The index used by this reader.
+    pub fn index(&self) -> &PafIndex {
+        &self.index
+    }
+
+    /// Fetch the records aligned to `name` that overlap `[start, end)`.
+    ///
+    /// Only the blocks containing candidate records are decompressed. Parse
+    /// errors are reported with a line number of 0, as the position in the
+    /// file is not known.
+    pub fn query(&mut self, name: &str, start: u64, end: u64) -> Result<Vec<PAF>, Error> {
+        let offset = match self.index.start_offset(name, start) {
+            Some(o) => o,
+            None => return Ok(Vec::new()),
+        };
+
+        self.reader.seek_virtual(offset).context(Io)?;
+
+        let mut output = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line).context(Io)? == 0 {
+                break;
+            }
+
+            let trimmed = line.trim_end_matches(&['\r', '\n'][..]);
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let record = parse_line(trimmed, 0, ParseMode::Strict)?;
+            let target = record.target();
+            if target.name() != name || target.start() >= end {
+                break;
+            }
+
+            if target.end() > start || (target.start() == target.end() && target.start() >= start) {
+                output.push(record);
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bgzf::BgzfWriter;
+    use std::io::Cursor;
+
+    fn line(qname: &str, tname: &str, start: u64, end: u64) -> String {
+        format!(
+            "{}\t1000\t0\t{}\t+\t{}\t10000000\t{}\t{}\t{}\t{}\t60\n",
+            qname, end - start, tname, start, end, end - start, end - start
+        )
+    }
+
+    fn compressed(lines: &[String]) -> Vec<u8> {
+        let mut writer = BgzfWriter::new(Vec::new());
+        for l in lines {
+            writer.write_all(l.as_bytes()).unwrap();
+            // Force many small blocks so that seeking is exercised.
+            writer.flush_block().unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_query() {
+        let lines = vec![
+            line("a", "chr1", 0, 100),
+            line("b", "chr1", 10, 100_000),
+            line("c", "chr1", 50_000, 50_100),
+            line("d", "chr1", 70_000, 70_100),
+            line("e", "chr2", 0, 100),
+            line("f", "chr2", 60_000, 60_100),
+        ];
+        let data = compressed(&lines);
+
+        let index = PafIndex::build(BgzfReader::new(Cursor::new(&data))).unwrap();
+        assert_eq!(index.target_names().collect::<Vec<_>>(), vec!["chr1", "chr2"]);
+
+        let mut serialised = Vec::new();
+        index.write(&mut serialised).unwrap();
+        let index = PafIndex::read(&serialised[..]).unwrap();
+        assert!(PafIndex::read(&serialised[..serialised.len() - 1]).is_err());
+
+        let mut corrupt = MAGIC.to_vec();
+        corrupt.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0, b'a', 255, 255, 255, 255]);
+        assert!(PafIndex::read(&corrupt[..]).is_err());
+
+        let mut reader = IndexedReader::new(Cursor::new(&data), index);
+        let names = |records: Vec<PAF>| -> Vec<String> {
+            records.iter().map(|r| r.query().name().to_string()).collect()
+        };
+
+        assert_eq!(names(reader.query("chr1", 60_000, 70_050).unwrap()), vec!["b", "d"]);
+        assert_eq!(names(reader.query("chr1", 0, 20).unwrap()), vec!["a", "b"]);
+        assert_eq!(names(reader.query("chr2", 50_000, 1_000_000).unwrap()), vec!["f"]);
+        assert!(reader.query("chr2", 200, 300).unwrap().is_empty());
+        assert!(reader.query("chr3", 0, 100).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let lines = vec![
+            line("a", "chr1", 500, 600),
+            line("b", "chr1", 0, 100),
+        ];
+        let data = compressed(&lines);
+
+        match PafIndex::build(BgzfReader::new(Cursor::new(&data))) {
+            Err(Error::Unsorted { line_num, by }) => assert_eq!((line_num, by.as_str()), (2, "target start")),
+            e => panic!("Expected an unsorted error, got {:?}", e),
+        }
+
+        let data = compressed(&[line("a", "chr1", 0, 100), line("b", "chr1", 10, 20_000_000)]);
+        match PafIndex::build(BgzfReader::new(Cursor::new(&data))) {
+            Err(Error::InvalidInterval { line_num, .. }) => assert_eq!(line_num, Some(2)),
+            e => panic!("Expected an invalid interval error, got {:?}", e),
+        }
+    }
+}
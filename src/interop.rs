@@ -0,0 +1,100 @@
+//! Conversions to and from the `bio-types` interval and strand types.
+//!
+//! Enabled with the `bio-types` feature.
+
+use bio_types::genome::{AbstractInterval, Interval, Position};
+use bio_types::strand::{ReqStrand, Strand as BioStrand};
+use std::ops::Range;
+
+use crate::paf::{Locus, Strand};
+
+
+impl From<Strand> for BioStrand {
+    fn from(strand: Strand) -> Self {
+        match strand {
+            Strand::Plus => BioStrand::Forward,
+            Strand::Minus => BioStrand::Reverse,
+            Strand::Unknown => BioStrand::Unknown,
+        }
+    }
+}
+
+
+impl From<BioStrand> for Strand {
+    fn from(strand: BioStrand) -> Self {
+        match strand {
+            BioStrand::Forward => Strand::Plus,
+            BioStrand::Reverse => Strand::Minus,
+            BioStrand::Unknown => Strand::Unknown,
+        }
+    }
+}
+
+
+impl From<ReqStrand> for Strand {
+    fn from(strand: ReqStrand) -> Self {
+        match strand {
+            ReqStrand::Forward => Strand::Plus,
+            ReqStrand::Reverse => Strand::Minus,
+        }
+    }
+}
+
+
+impl AbstractInterval for Locus {
+    fn contig(&self) -> &str {
+        self.name()
+    }
+
+    fn range(&self) -> Range<Position> {
+        self.start()..self.end()
+    }
+}
+
+
+impl From<&Locus> for Interval {
+    fn from(locus: &Locus) -> Self {
+        Interval::new(locus.name().to_string(), locus.start()..locus.end())
+    }
+}
+
+
+impl From<Locus> for Interval {
+    fn from(locus: Locus) -> Self {
+        Interval::from(&locus)
+    }
+}
+
+
+impl Locus {
+    /// Construct a `Locus` from a `bio-types` interval.
+    ///
+    /// Intervals don't record the sequence length, so it must be supplied.
+    pub fn from_interval<I: AbstractInterval>(interval: &I, length: u64) -> Self {
+        let range = interval.range();
+        Locus::new(interval.contig().to_string(), length, range.start, range.end)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locus_interval_round_trip() {
+        let locus = Locus::new("chr1".to_string(), 1000, 10, 20);
+        let interval = Interval::from(&locus);
+        assert_eq!(interval.contig(), "chr1");
+        assert_eq!(interval.range(), 10..20);
+        assert_eq!(Locus::from_interval(&interval, 1000), locus);
+    }
+
+    #[test]
+    fn test_strand_conversion() {
+        for strand in &[Strand::Plus, Strand::Minus, Strand::Unknown] {
+            assert_eq!(Strand::from(BioStrand::from(*strand)), *strand);
+        }
+        assert_eq!(Strand::from(ReqStrand::Reverse), Strand::Minus);
+    }
+}
@@ -0,0 +1,154 @@
+//! Swapping the query and target of a record.
+
+use crate::cigar::{Cigar, CigarKind, CigarOp};
+use crate::cs::{complement, reverse_complement, Cs, CsOp};
+use crate::paf::{Strand, PAF};
+
+
+impl PAF {
+    /// Exchange the query and target of the record.
+    ///
+    /// The relative strand is unchanged. In the `cg` tag insertions and
+    /// deletions are exchanged, and in the `cs` tag so are substituted bases.
+    /// For minus strand records both are reversed, and `cs` sequences reverse
+    /// complemented, as they must run along the new target.
+    ///
+    /// Introns cannot be expressed on the query, so `N` operations become
+    /// insertions and a `cs` tag containing introns is dropped. Tags that
+    /// are not valid `Z` type `cg` or `cs` tags are kept unchanged.
+    pub fn invert(&self) -> PAF {
+        let minus = self.strand() == Strand::Minus;
+
+        let fields: Vec<String> = self.fields()
+            .iter()
+            .filter_map(|f| {
+                if let Some(cg) = f.strip_prefix("cg:Z:") {
+                    match cg.parse::<Cigar>() {
+                        Ok(cigar) => Some(format!("cg:Z:{}", invert_cigar(&cigar, minus))),
+                        Err(_) => Some(f.to_string()),
+                    }
+                } else if let Some(cs) = f.strip_prefix("cs:Z:") {
+                    match cs.parse::<Cs>() {
+                        Ok(cs) => invert_cs(&cs, minus).map(|cs| format!("cs:Z:{}", cs)),
+                        Err(_) => Some(f.to_string()),
+                    }
+                } else {
+                    Some(f.to_string())
+                }
+            })
+            .collect();
+
+        PAF::new(
+            self.target().clone(),
+            self.strand(),
+            self.query().clone(),
+            self.nmatch(),
+            self.alnlen(),
+            self.mapq(),
+            fields,
+        )
+    }
+}
+
+
+/// Exchange insertions and deletions, reversing for minus strand records.
+pub(crate) fn invert_cigar(cigar: &Cigar, minus: bool) -> Cigar {
+    let mut ops: Vec<CigarOp> = cigar.ops()
+        .iter()
+        .map(|op| {
+            let kind = match op.kind() {
+                CigarKind::Insertion => CigarKind::Deletion,
+                CigarKind::Deletion | CigarKind::Skip => CigarKind::Insertion,
+                k => k,
+            };
+            CigarOp::new(kind, op.len())
+        })
+        .collect();
+
+    if minus {
+        ops.reverse();
+    }
+
+    Cigar::new(ops)
+}
+
+
+/// Exchange query and target bases, returning `None` if there are introns.
+pub(crate) fn invert_cs(cs: &Cs, minus: bool) -> Option<Cs> {
+    let orient = |s: &str| if minus { reverse_complement(s) } else { s.to_string() };
+    let base = |c: char| if minus { complement(c) } else { c };
+
+    let mut ops = Vec::with_capacity(cs.ops().len());
+    for op in cs.ops() {
+        let inverted = match op {
+            CsOp::Match(n) => CsOp::Match(*n),
+            CsOp::Identical(s) => CsOp::Identical(orient(s)),
+            CsOp::Substitution { target, query } => CsOp::Substitution {
+                target: base(*query),
+                query: base(*target),
+            },
+            CsOp::Insertion(s) => CsOp::Deletion(orient(s)),
+            CsOp::Deletion(s) => CsOp::Insertion(orient(s)),
+            CsOp::Intron { .. } => return None,
+        };
+        ops.push(inverted);
+    }
+
+    if minus {
+        ops.reverse();
+    }
+
+    Some(Cs::new(ops))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_invert_plus() {
+        let record = paf("q\t100\t10\t30\t+\tt\t200\t50\t73\t18\t25\t60\tNM:i:7\tcg:Z:5M2I3M5D10M\tcs:Z::5+ac:3-ggtca:4*ag:5");
+        let inverted = record.invert();
+
+        assert_eq!(
+            inverted.to_string(),
+            "t\t200\t50\t73\t+\tq\t100\t10\t30\t18\t25\t60\tNM:i:7\tcg:Z:5M2D3M5I10M\tcs:Z::5-ac:3+ggtca:4*ga:5"
+        );
+        assert_eq!(inverted.invert(), record);
+    }
+
+    #[test]
+    fn test_invert_minus() {
+        let record = paf("q\t100\t10\t20\t-\tt\t200\t50\t61\t9\t11\t60\tcg:Z:3M1D7M\tcs:Z::2*ag-c=ACGTAAC");
+        let inverted = record.invert();
+
+        assert_eq!(inverted.cigar().unwrap().to_string(), "7M1I3M");
+        assert_eq!(inverted.tag("cs").unwrap().as_str(), Some("=GTTACGT+g*ct:2"));
+        assert_eq!(inverted.invert(), record);
+    }
+
+    #[test]
+    fn test_invert_spliced() {
+        let record = paf("q\t100\t0\t20\t+\tt\t200\t0\t120\t20\t20\t60\tcg:Z:10M100N10M\tcs:Z::10~gt96ag:10");
+        let inverted = record.invert();
+
+        assert_eq!(inverted.cigar().unwrap().to_string(), "10M100I10M");
+        assert_eq!(inverted.tag("cs"), None);
+    }
+
+    #[test]
+    fn test_invert_malformed_tags() {
+        let record = paf("q\t100\t0\t20\t+\tt\t200\t0\t20\t20\t20\t60\tcg:\tcs:Z\tcg:i:5");
+        let inverted = record.invert();
+
+        assert_eq!(
+            inverted.to_string(),
+            "t\t200\t0\t20\t+\tq\t100\t0\t20\t20\t20\t60\tcg:\tcs:Z\tcg:i:5"
+        );
+    }
+}
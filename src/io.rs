@@ -0,0 +1,270 @@
+//! Convenience functions for reading and writing PAF files at paths.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io, OpenPath};
+use crate::paf::PAF;
+use crate::reader::PafReader;
+use crate::writer::PafWriter;
+
+
+/// Open a file for buffered reading.
+///
+/// With the `gzip` feature, gzip and bgzip compressed files are detected
+/// from their magic bytes and decompressed transparently.
+pub fn open_path<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>, Error> {
+    let path = path.as_ref();
+    let file = File::open(path).context(OpenPath { path })?;
+    let mut reader = BufReader::new(file);
+
+    if is_gzipped(&mut reader)? {
+        return gzip_reader(reader);
+    }
+
+    Ok(Box::new(reader))
+}
+
+
+/// Check for the gzip magic bytes without consuming them.
+fn is_gzipped<R: BufRead>(reader: &mut R) -> Result<bool, Error> {
+    let buf = reader.fill_buf().context(Io)?;
+    Ok(buf.starts_with(&[0x1f, 0x8b]))
+}
+
+
+#[cfg(feature = "gzip")]
+fn gzip_reader(reader: BufReader<File>) -> Result<Box<dyn BufRead>, Error> {
+    // Multi-member decoding also handles bgzip, which is a series of
+    // concatenated gzip members.
+    let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+
+#[cfg(not(feature = "gzip"))]
+fn gzip_reader(_reader: BufReader<File>) -> Result<Box<dyn BufRead>, Error> {
+    Err(Error::Io {
+        source: std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "input is gzip compressed, but the `gzip` feature is disabled",
+        ),
+    })
+}
+
+
+impl PAF {
+    /// Open a PAF file, returning an iterator over its records.
+    ///
+    /// Compressed files are handled as for [`open_path`].
+    pub fn read_path<P: AsRef<Path>>(path: P) -> Result<PafReader<Box<dyn BufRead>>, Error> {
+        Ok(PafReader::new(open_path(path)?))
+    }
+}
+
+
+/// The compression applied to written files.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub enum Compression {
+    None,
+    /// Standard gzip, requiring the `gzip` feature.
+    Gzip,
+    /// Blocked gzip, as written by `bgzip`, requiring the `gzip` feature.
+    Bgzip,
+}
+
+
+impl Compression {
+    /// Choose a compression from a path's extension.
+    ///
+    /// `.gz` selects gzip and `.bgz` selects bgzip.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("bgz") => Compression::Bgzip,
+            _ => Compression::None,
+        }
+    }
+}
+
+
+/// A sink that may compress its output.
+///
+/// Call [`CompressedWriter::finish`] to write any trailing compression data
+/// and surface errors, rather than relying on drop.
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "gzip")]
+    Bgzip(crate::bgzf::BgzfWriter<W>),
+}
+
+
+impl<W: Write> CompressedWriter<W> {
+    /// Wrap a sink with the given compression.
+    pub fn new(inner: W, compression: Compression) -> Result<Self, Error> {
+        match compression {
+            Compression::None => Ok(CompressedWriter::Plain(inner)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(CompressedWriter::Gzip(
+                flate2::write::GzEncoder::new(inner, flate2::Compression::default())
+            )),
+            #[cfg(feature = "gzip")]
+            Compression::Bgzip => Ok(CompressedWriter::Bgzip(crate::bgzf::BgzfWriter::new(inner))),
+            #[cfg(not(feature = "gzip"))]
+            _ => Err(Error::Io {
+                source: std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "compressed output requires the `gzip` feature",
+                ),
+            }),
+        }
+    }
+
+    /// Finish compression and flush, returning the underlying sink.
+    #[cfg_attr(not(feature = "gzip"), allow(clippy::infallible_destructuring_match))]
+    pub fn finish(self) -> Result<W, Error> {
+        let mut inner = match self {
+            CompressedWriter::Plain(w) => w,
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Gzip(w) => w.finish().context(Io)?,
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Bgzip(w) => w.finish().context(Io)?,
+        };
+        inner.flush().context(Io)?;
+        Ok(inner)
+    }
+}
+
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(w) => w.write(buf),
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Gzip(w) => w.write(buf),
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Bgzip(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressedWriter::Plain(w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Gzip(w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Bgzip(w) => w.flush(),
+        }
+    }
+}
+
+
+/// Create a file for buffered, optionally compressed, writing.
+///
+/// If `compression` is `None` it is chosen from the file extension.
+pub fn create_path<P: AsRef<Path>>(
+    path: P,
+    compression: Option<Compression>,
+) -> Result<CompressedWriter<BufWriter<File>>, Error> {
+    let path = path.as_ref();
+    let compression = compression.unwrap_or_else(|| Compression::from_path(path));
+    let file = File::create(path).context(OpenPath { path })?;
+    CompressedWriter::new(BufWriter::new(file), compression)
+}
+
+
+impl PafWriter<CompressedWriter<BufWriter<File>>> {
+    /// Create a PAF file, compressing it according to its extension.
+    pub fn to_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(PafWriter::new(create_path(path, None)?))
+    }
+
+    /// Finish any compression and flush the file.
+    pub fn finish(self) -> Result<(), Error> {
+        self.into_inner().finish().map(|_| ())
+    }
+}
+
+
+/// Read all records from a PAF file into memory.
+pub fn read_all<P: AsRef<Path>>(path: P) -> Result<Vec<PAF>, Error> {
+    PAF::read_path(path)?.collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const INPUT: &str = "\
+q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60
+q2\t100\t0\t100\t-\tt2\t1000\t10\t110\t90\t100\t60
+";
+
+    #[test]
+    fn test_read_all() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(INPUT.as_bytes()).unwrap();
+
+        let records = read_all(file.path()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].target().name(), "t2");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_read_gzipped() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(INPUT.as_bytes()).unwrap();
+        file.write_all(&encoder.finish().unwrap()).unwrap();
+
+        let records: Vec<PAF> = PAF::read_path(file.path())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn test_compression_from_path() {
+        assert_eq!(Compression::from_path("a.paf"), Compression::None);
+        assert_eq!(Compression::from_path("a.paf.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_path("a.paf.bgz"), Compression::Bgzip);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_write_compressed_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for name in &["out.paf", "out.paf.gz", "out.paf.bgz"] {
+            let path = dir.path().join(name);
+            let mut writer = PafWriter::to_path(&path).unwrap();
+            for line in INPUT.lines() {
+                writer.write_record(&line.parse::<PAF>().unwrap()).unwrap();
+            }
+            writer.finish().unwrap();
+
+            let records = read_all(&path).unwrap();
+            assert_eq!(records.len(), 2, "{}", name);
+        }
+
+        let raw = std::fs::read(dir.path().join("out.paf.bgz")).unwrap();
+        assert!(raw.ends_with(&crate::bgzf::EOF_BLOCK));
+    }
+
+    #[test]
+    fn test_missing_path() {
+        match read_all("/does/not/exist.paf") {
+            Err(Error::OpenPath { path, .. }) => assert_eq!(path, Path::new("/does/not/exist.paf")),
+            e => panic!("Expected an open error, got {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::paf::PAF;
+use crate::sealed::Sealed;
+
+
+/// Adapters for iterators over PAF records.
+///
+/// This trait is sealed and is implemented for every `Iterator<Item = PAF>`.
+pub trait PafIterExt: Iterator<Item = PAF> + Sealed + Sized {
+    /// Keep only records with a mapping quality of at least `mapq`.
+    fn min_mapq(self, mapq: u8) -> MinMapq<Self> {
+        MinMapq { iter: self, mapq }
+    }
+
+    /// Keep only records with an alignment length of at least `alnlen`.
+    fn min_alnlen(self, alnlen: u64) -> MinAlnlen<Self> {
+        MinAlnlen { iter: self, alnlen }
+    }
+
+    /// Group runs of consecutive records with the same query name.
+    ///
+    /// Input should be grouped by query, as aligners write it. Records for
+    /// a query that are not adjacent will be yielded in separate groups.
+    fn group_by_query(self) -> GroupByQuery<Self> {
+        GroupByQuery { iter: self, next: None }
+    }
+
+    /// Drop every record for queries that map ambiguously.
+    ///
+    /// Records must be grouped by query. For each query the ratio of the
+    /// secondary to primary score is computed and the whole group is
+    /// discarded if it exceeds `ratio`. See [`secondary_ratio`].
+    fn max_secondary_ratio(self, ratio: f64) -> MaxSecondaryRatio<Self> {
+        MaxSecondaryRatio { groups: self.group_by_query(), ratio, pending: Vec::new().into_iter() }
+    }
+}
+
+
+impl<I> PafIterExt for I where I: Iterator<Item = PAF> {}
+
+
+/// Iterator returned by [`PafIterExt::min_mapq`].
+#[derive(Debug, Clone)]
+pub struct MinMapq<I> {
+    iter: I,
+    mapq: u8,
+}
+
+
+impl<I: Iterator<Item = PAF>> Iterator for MinMapq<I> {
+    type Item = PAF;
+
+    fn next(&mut self) -> Option<PAF> {
+        let mapq = self.mapq;
+        self.iter.find(|r| r.mapq() >= mapq)
+    }
+}
+
+
+/// Iterator returned by [`PafIterExt::min_alnlen`].
+#[derive(Debug, Clone)]
+pub struct MinAlnlen<I> {
+    iter: I,
+    alnlen: u64,
+}
+
+
+impl<I: Iterator<Item = PAF>> Iterator for MinAlnlen<I> {
+    type Item = PAF;
+
+    fn next(&mut self) -> Option<PAF> {
+        let alnlen = self.alnlen;
+        self.iter.find(|r| r.alnlen() >= alnlen)
+    }
+}
+
+
+/// Iterator returned by [`PafIterExt::group_by_query`].
+#[derive(Debug, Clone)]
+pub struct GroupByQuery<I> {
+    iter: I,
+    next: Option<PAF>,
+}
+
+
+impl<I: Iterator<Item = PAF>> Iterator for GroupByQuery<I> {
+    type Item = Vec<PAF>;
+
+    fn next(&mut self) -> Option<Vec<PAF>> {
+        let first = self.next.take().or_else(|| self.iter.next())?;
+        let mut group = vec![first];
+
+        for record in &mut self.iter {
+            if record.query().name() == group[0].query().name() {
+                group.push(record);
+            } else {
+                self.next = Some(record);
+                break;
+            }
+        }
+
+        Some(group)
+    }
+}
+
+
+/// The ratio of the secondary to primary score for a group of records.
+///
+/// The primary is the record with the highest [`PAF::score`]. If it has
+/// `s1` and `s2` chaining score tags, the ratio is `s2 / s1`. Otherwise it
+/// is the second highest score in the group divided by the highest, or 0
+/// for a group with a single record.
+pub fn secondary_ratio(group: &[PAF]) -> f64 {
+    let primary = match group.iter().max_by_key(|r| r.score()) {
+        Some(p) => p,
+        None => return 0.0,
+    };
+
+    if let (Some(s1), Some(s2)) = (primary.s1(), primary.s2()) {
+        return if s1 > 0 { s2 as f64 / s1 as f64 } else { 0.0 };
+    }
+
+    let mut scores: Vec<i64> = group.iter().map(|r| r.score()).collect();
+    scores.sort_unstable_by(|a, b| b.cmp(a));
+    match scores.as_slice() {
+        [best, second, ..] if *best > 0 => *second as f64 / *best as f64,
+        _ => 0.0,
+    }
+}
+
+
+/// Iterator returned by [`PafIterExt::max_secondary_ratio`].
+#[derive(Debug, Clone)]
+pub struct MaxSecondaryRatio<I> {
+    groups: GroupByQuery<I>,
+    ratio: f64,
+    pending: std::vec::IntoIter<PAF>,
+}
+
+
+impl<I: Iterator<Item = PAF>> Iterator for MaxSecondaryRatio<I> {
+    type Item = PAF;
+
+    fn next(&mut self) -> Option<PAF> {
+        loop {
+            if let Some(record) = self.pending.next() {
+                return Some(record);
+            }
+
+            let group = self.groups.next()?;
+            if secondary_ratio(&group) <= self.ratio {
+                self.pending = group.into_iter();
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        let records: Vec<PAF> = vec![
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60".parse().unwrap(),
+            "q2\t10\t0\t5\t+\tt1\t20\t0\t5\t5\t5\t60".parse().unwrap(),
+            "q3\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t0".parse().unwrap(),
+        ];
+
+        let names: Vec<String> = records
+            .into_iter()
+            .min_mapq(30)
+            .min_alnlen(10)
+            .map(|r| r.query().name().to_string())
+            .collect();
+
+        assert_eq!(names, vec!["q1".to_string()]);
+    }
+
+    #[test]
+    fn test_max_secondary_ratio() {
+        let records: Vec<PAF> = vec![
+            // Chaining scores show q1 is unique.
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ts1:i:100\ts2:i:20".parse().unwrap(),
+            // q2 has two equally good hits by AS.
+            "q2\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t0\tAS:i:50".parse().unwrap(),
+            "q2\t10\t0\t10\t+\tt2\t20\t0\t10\t10\t10\t0\tAS:i:48".parse().unwrap(),
+            // q3 has a clearly better primary.
+            "q3\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\tAS:i:50".parse().unwrap(),
+            "q3\t10\t0\t10\t+\tt2\t20\t0\t10\t10\t10\t0\tAS:i:10".parse().unwrap(),
+        ];
+
+        let groups: Vec<usize> = records.clone().into_iter().group_by_query().map(|g| g.len()).collect();
+        assert_eq!(groups, vec![1, 2, 2]);
+
+        let names: Vec<String> = records
+            .into_iter()
+            .max_secondary_ratio(0.8)
+            .map(|r| r.query().name().to_string())
+            .collect();
+
+        assert_eq!(names, vec!["q1", "q3", "q3"]);
+    }
+}
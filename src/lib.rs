@@ -1,9 +1,141 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub(crate) mod paf;
 pub(crate) mod errors;
-pub(crate) mod complete;
+pub mod complete;
+#[cfg(feature = "std")]
+pub mod streaming;
+pub(crate) mod tags;
+pub(crate) mod fields;
+pub(crate) mod cigar;
+pub(crate) mod cs;
+pub(crate) mod record;
+#[cfg(feature = "std")]
+pub(crate) mod reader;
+#[cfg(feature = "std")]
+pub(crate) mod writer;
+#[cfg(feature = "std")]
+pub mod iter;
+#[cfg(feature = "std")]
+pub mod prelude;
+#[cfg(feature = "std")]
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod set;
+#[cfg(feature = "std")]
+pub mod minimal;
+#[cfg(feature = "std")]
+pub mod ids;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod filter;
+pub mod sort;
+#[cfg(any(feature = "testing", feature = "arbitrary"))]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod dedup;
+#[cfg(feature = "std")]
+pub mod sample;
+#[cfg(feature = "std")]
+pub mod group;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod matrix;
+#[cfg(feature = "std")]
+pub mod depth;
+#[cfg(feature = "std")]
+pub mod window;
+#[cfg(feature = "std")]
+pub mod mapq;
+#[cfg(feature = "std")]
+pub mod secondary;
+#[cfg(feature = "std")]
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod rename;
+#[cfg(feature = "std")]
+pub mod escape;
+#[cfg(feature = "fs")]
+pub mod split;
+#[cfg(feature = "fs")]
+pub mod merge;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(feature = "std")]
+pub mod bed;
+#[cfg(feature = "std")]
+pub mod fasta;
+#[cfg(feature = "std")]
+pub mod psl;
+#[cfg(feature = "std")]
+pub mod liftover;
+#[cfg(feature = "std")]
+pub mod gaps;
+#[cfg(feature = "fs")]
+pub mod io;
+#[cfg(feature = "gzip")]
+pub mod bgzf;
+#[cfg(all(feature = "gzip", feature = "fs"))]
+pub mod index;
+#[cfg(feature = "bio-types")]
+mod interop;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+mod slice;
+#[cfg(feature = "std")]
+mod blast;
+#[cfg(feature = "std")]
+mod serialize;
+#[cfg(feature = "std")]
+mod invert;
+#[cfg(feature = "std")]
+pub mod rescore;
+#[cfg(feature = "std")]
+pub mod repair;
+#[cfg(feature = "std")]
+pub mod rearrange;
+#[cfg(feature = "std")]
+pub mod sv;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "plot")]
+pub mod plot;
+#[cfg(feature = "std")]
+pub mod overlap;
+#[cfg(feature = "std")]
+pub mod trim;
+#[cfg(feature = "std")]
+pub mod tile;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+mod sealed;
 
 pub use crate::paf::PAF;
 pub use crate::paf::Locus;
+pub use crate::paf::Strand;
+pub use crate::paf::parse_str;
+pub use crate::tags::{Tag, TagArray, TagValue};
+pub use crate::fields::Fields;
+pub use crate::cigar::{Block, Cigar, CigarOp, CigarKind};
+pub use crate::cs::{Cs, CsOp};
+pub use crate::record::PafRecord;
+pub use crate::errors::Error;
+#[cfg(feature = "std")]
+pub use crate::reader::{PafReader, ParseMode, Progress};
+#[cfg(feature = "std")]
+pub use crate::writer::{PafWriter, TagSelection};
+#[cfg(feature = "std")]
+pub use crate::iter::PafIterExt;
+#[cfg(feature = "fs")]
+pub use crate::io::read_all;
 
 //use std::io::BufRead;
 //use std::io::Read;
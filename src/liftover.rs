@@ -0,0 +1,163 @@
+//! Project intervals from query coordinates to target coordinates through
+//! CIGAR-bearing alignments, like `paftools liftover`.
+
+use std::collections::HashMap;
+
+use crate::bed::BedRecord;
+use crate::cigar::{blocks, Block};
+use crate::paf::{Strand, PAF};
+
+
+/// Lift BED intervals on the query assembly over to the target assembly.
+///
+/// Each interval is projected through every alignment of its sequence that
+/// has a `cg` tag. Within one alignment, projected pieces separated by a gap
+/// of at most `max_gap` target bases are joined, and larger gaps split the
+/// output. Records without a valid CIGAR are ignored. BED strand columns are
+/// flipped for minus strand alignments.
+pub fn liftover(records: &[PAF], intervals: &[BedRecord], max_gap: u64) -> Vec<BedRecord> {
+    let mut by_query: HashMap<&str, Vec<(&PAF, Vec<Block>)>> = HashMap::new();
+    for record in records {
+        if let Some(Ok(record_blocks)) = record.cigar().map(|c| blocks(record, &c)) {
+            by_query
+                .entry(record.query().name())
+                .or_default()
+                .push((record, record_blocks));
+        }
+    }
+
+    let mut output = Vec::new();
+    for interval in intervals {
+        let alignments = match by_query.get(interval.chrom.as_str()) {
+            Some(a) => a,
+            None => continue,
+        };
+
+        for (record, record_blocks) in alignments {
+            if interval.end <= record.query().start() || interval.start >= record.query().end() {
+                continue;
+            }
+
+            let pieces = project(
+                record.strand(),
+                record_blocks,
+                interval.start,
+                interval.end,
+                max_gap,
+            );
+
+            for (start, end) in pieces {
+                output.push(lifted(interval, record, start, end));
+            }
+        }
+    }
+
+    output
+}
+
+
+/// Project `[start, end)` through the blocks, joining pieces within `max_gap`.
+fn project(
+    strand: Strand,
+    record_blocks: &[Block],
+    start: u64,
+    end: u64,
+    max_gap: u64,
+) -> Vec<(u64, u64)> {
+    let mut pieces: Vec<(u64, u64)> = record_blocks
+        .iter()
+        .filter(|b| b.query_start < end && b.query_end > start)
+        .map(|b| {
+            let qs = start.max(b.query_start);
+            let qe = end.min(b.query_end);
+            match strand {
+                Strand::Minus => (
+                    b.target_start + (b.query_end - qe),
+                    b.target_start + (b.query_end - qs),
+                ),
+                _ => (
+                    b.target_start + (qs - b.query_start),
+                    b.target_start + (qe - b.query_start),
+                ),
+            }
+        })
+        .collect();
+
+    // Blocks run along the target, but sort anyway so only neighbours join.
+    pieces.sort_unstable();
+
+    let mut output: Vec<(u64, u64)> = Vec::new();
+    for (s, e) in pieces {
+        match output.last_mut() {
+            Some(last) if s <= last.1 + max_gap => last.1 = last.1.max(e),
+            _ => output.push((s, e)),
+        }
+    }
+    output
+}
+
+
+/// Build the output interval, carrying over the name and extra columns.
+fn lifted(interval: &BedRecord, record: &PAF, start: u64, end: u64) -> BedRecord {
+    let mut rest = interval.rest.clone();
+    if record.strand() == Strand::Minus {
+        if let Some(strand) = rest.get_mut(1) {
+            match strand.as_str() {
+                "+" => *strand = "-".to_string(),
+                "-" => *strand = "+".to_string(),
+                _ => {},
+            }
+        }
+    }
+
+    BedRecord {
+        chrom: record.target().name().to_string(),
+        start,
+        end,
+        name: interval.name.clone(),
+        rest,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_liftover_plus() {
+        let records = vec![
+            paf("q\t100\t10\t30\t+\tt\t100\t50\t73\t18\t25\t60\tcg:Z:5M2I3M5D10M"),
+        ];
+        let intervals = vec![BedRecord::new("q".to_string(), 12, 27)];
+
+        let split: Vec<(u64, u64)> = liftover(&records, &intervals, 0)
+            .iter()
+            .map(|b| (b.start, b.end))
+            .collect();
+        assert_eq!(split, vec![(52, 58), (63, 70)]);
+
+        let joined = liftover(&records, &intervals, 5);
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[0].to_string(), "t\t52\t70");
+    }
+
+    #[test]
+    fn test_liftover_minus() {
+        let records = vec![
+            paf("q\t100\t10\t30\t-\tt\t100\t50\t73\t18\t25\t60\tcg:Z:5M2I3M5D10M"),
+            paf("q\t100\t10\t30\t-\tu\t100\t50\t70\t20\t20\t60"),
+        ];
+        let mut interval = BedRecord::new("q".to_string(), 26, 30);
+        interval.name = Some("feature".to_string());
+        interval.rest = vec!["0".to_string(), "+".to_string()];
+
+        let lifted = liftover(&records, &[interval], 0);
+        assert_eq!(lifted.len(), 1);
+        assert_eq!(lifted[0].to_string(), "t\t50\t54\tfeature\t0\t-");
+    }
+}
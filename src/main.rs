@@ -1,12 +1,168 @@
-use std::str::FromStr;
+use std::fmt;
+use std::io::{BufRead, BufWriter, Write};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+use paf::filter::Filter;
+use paf::stats::Stats;
+use paf::Error;
 
-    let input = "seqid\t10\t0\t10\t+\tseqid2\t10\t0\t10\t1\t1\t1000\n";
-    match paf::PAF::from_str(input) {
-        Ok(p) => println!("{}", p),
-        Err(e) => println!("{}", e),
+
+const USAGE: &str = "Usage: paf stats [FILE]
+       paf filter EXPR [FILE]
+       paf plot [FILE]
+
+stats   Print N50 statistics of the alignment lengths and covered target
+        regions.
+filter  Print the records matching an expression, e.g.
+        \"mapq >= 30 && alnlen > 5000 && tag('de') < 0.05\".
+plot    Print an SVG dot plot of the alignments. Needs the plot feature.
+
+Reads from stdin if FILE is missing or '-', and writes to stdout. Exits
+with 1 if the input cannot be read or parsed, and 2 for bad arguments.";
+
+
+/// An error, with the name of the input it was raised for.
+struct Failure {
+    input: Option<String>,
+    error: Error,
+}
+
+
+impl Failure {
+    fn new(input: Option<&str>, error: Error) -> Self {
+        Failure { input: input.map(String::from), error }
+    }
+
+    /// Whether the failure is only that stdout was closed, e.g. by `head`.
+    fn is_broken_pipe(&self) -> bool {
+        matches!(&self.error, Error::Io { source } if source.kind() == std::io::ErrorKind::BrokenPipe)
+    }
+}
+
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "paf: ")?;
+        if let Some(input) = &self.input {
+            write!(f, "{}", input)?;
+            if let Some(line) = self.error.line_number() {
+                write!(f, ":{}", line)?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+
+/// The name of an input for error messages.
+fn input_name(path: Option<&str>) -> &str {
+    match path {
+        None | Some("-") => "<stdin>",
+        Some(path) => path,
+    }
+}
+
+
+fn open(path: Option<&str>) -> Result<Box<dyn BufRead>, Error> {
+    match path {
+        None | Some("-") => Ok(Box::new(std::io::BufReader::new(std::io::stdin()))),
+        Some(path) => paf::io::open_path(path),
+    }
+}
+
+
+fn io_error(source: std::io::Error) -> Failure {
+    Failure::new(None, Error::Io { source })
+}
+
+
+fn stats(path: Option<&str>) -> Result<(), Failure> {
+    let input = Some(input_name(path));
+    let records = open(path)
+        .and_then(|r| paf::PafReader::new(r).collect::<Result<Vec<_>, _>>())
+        .map_err(|e| Failure::new(input, e))?;
+
+    let stdout = std::io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    for (name, stats) in &[
+        ("alignments", Stats::alignment_lengths(&records)),
+        ("covered", Stats::covered_regions(&records)),
+    ] {
+        for line in stats.to_string().lines() {
+            writeln!(output, "{}\t{}", name, line).map_err(io_error)?;
+        }
+    }
+    output.flush().map_err(io_error)
+}
+
+
+fn filter(filter: &Filter, path: Option<&str>) -> Result<(), Failure> {
+    let input = Some(input_name(path));
+
+    let stdout = std::io::stdout();
+    let mut writer = paf::PafWriter::new(BufWriter::new(stdout.lock()));
+    let reader = open(path).map_err(|e| Failure::new(input, e))?;
+    for record in paf::PafReader::new(reader) {
+        let record = record.map_err(|e| Failure::new(input, e))?;
+        if filter.matches(&record) {
+            writer.write_record(&record).map_err(|e| Failure::new(None, e))?;
+        }
+    }
+    writer.flush().map_err(|e| Failure::new(None, e))
+}
+
+
+#[cfg(feature = "plot")]
+fn plot(path: Option<&str>) -> Result<(), Failure> {
+    use paf::plot::{DotPlot, PlotOptions};
+
+    let input = Some(input_name(path));
+    let records = open(path)
+        .and_then(|r| paf::PafReader::new(r).collect::<Result<Vec<_>, _>>())
+        .map_err(|e| Failure::new(input, e))?;
+
+    let stdout = std::io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    DotPlot::new(&records)
+        .write_svg(&PlotOptions::default(), &mut output)
+        .map_err(|e| Failure::new(None, e))?;
+    output.flush().map_err(io_error)
+}
+
+
+#[cfg(not(feature = "plot"))]
+fn plot(_path: Option<&str>) -> Result<(), Failure> {
+    eprintln!("paf: plot needs the plot feature");
+    std::process::exit(2);
+}
+
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arg = |i: usize| args.get(i).map(|a| a.as_str());
+
+    let result = match (arg(0), arg(1)) {
+        (Some("stats"), path) => stats(path),
+        (Some("plot"), path) => plot(path),
+        (Some("filter"), Some(expr)) => match expr.parse::<Filter>() {
+            Ok(f) => filter(&f, arg(2)),
+            Err(e) => {
+                eprintln!("paf: invalid expression: {}", e);
+                std::process::exit(2);
+            },
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        },
     };
 
-    Ok(())
+    match result {
+        Ok(()) => {},
+        Err(e) if e.is_broken_pipe() => {},
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    }
 }
@@ -0,0 +1,76 @@
+//! Recalibration of mapping qualities from chaining scores.
+//!
+//! Different aligners use incompatible MAPQ scales, which makes filtering a
+//! merged set of alignments on MAPQ meaningless. These functions recompute a
+//! minimap2-style MAPQ from the `s1` and `s2` chaining score tags so that all
+//! records share one scale.
+
+use crate::paf::PAF;
+
+
+/// minimap2's default minimum chaining score (`-m`).
+pub const DEFAULT_MIN_CHAIN_SCORE: i64 = 40;
+
+/// The highest MAPQ assigned, as in minimap2.
+pub const MAX_MAPQ: u8 = 60;
+
+
+/// Compute a MAPQ for a record following minimap2's formula.
+///
+/// The score is `40 * identity * (1 - s2 / s1) * ln(s1)`, clamped to
+/// `[0, 60]`, where `s2` is raised to at least `min_chain_score`. A missing
+/// `s2` tag is treated as no secondary chain. Returns `None` if the record
+/// has no positive `s1` tag.
+pub fn compute_mapq(record: &PAF, min_chain_score: i64) -> Option<u8> {
+    let s1 = record.s1().filter(|s| *s > 0)? as f64;
+    let s2 = record.s2()
+        .unwrap_or(0)
+        .max(min_chain_score) as f64;
+
+    let x = (s2 / s1).min(1.0);
+    let mapq = 40.0 * record.identity() * (1.0 - x) * s1.ln();
+    Some(mapq.clamp(0.0, MAX_MAPQ as f64) as u8)
+}
+
+
+/// Replace the MAPQ of each record with [`compute_mapq`].
+///
+/// Records without an `s1` tag keep their original MAPQ.
+pub fn recalibrate(records: &mut [PAF], min_chain_score: i64) {
+    for record in records {
+        if let Some(mapq) = compute_mapq(record, min_chain_score) {
+            record.set_mapq(mapq);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_compute_mapq() {
+        let unique = paf("q1\t1000\t0\t1000\t+\tt1\t5000\t0\t1000\t1000\t1000\t3\ts1:i:900\ts2:i:0");
+        assert_eq!(compute_mapq(&unique, DEFAULT_MIN_CHAIN_SCORE), Some(60));
+
+        let repeat = paf("q1\t1000\t0\t1000\t+\tt1\t5000\t0\t1000\t1000\t1000\t3\ts1:i:900\ts2:i:900");
+        assert_eq!(compute_mapq(&repeat, DEFAULT_MIN_CHAIN_SCORE), Some(0));
+
+        // 40 * 0.5 * (1 - 100 / 200) * ln(200) = 52.98
+        let partial = paf("q1\t1000\t0\t1000\t+\tt1\t5000\t0\t1000\t500\t1000\t3\ts1:i:200\ts2:i:100");
+        assert_eq!(compute_mapq(&partial, DEFAULT_MIN_CHAIN_SCORE), Some(52));
+
+        let missing = paf("q1\t1000\t0\t1000\t+\tt1\t5000\t0\t1000\t1000\t1000\t3");
+        assert_eq!(compute_mapq(&missing, DEFAULT_MIN_CHAIN_SCORE), None);
+
+        let mut records = vec![partial, missing];
+        recalibrate(&mut records, DEFAULT_MIN_CHAIN_SCORE);
+        assert_eq!(records[0].mapq(), 52);
+        assert_eq!(records[1].mapq(), 3);
+    }
+}
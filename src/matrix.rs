@@ -0,0 +1,189 @@
+//! Pairwise identity and coverage between genomes from an all-vs-all
+//! alignment of several assemblies.
+//!
+//! Each sequence is assigned to a genome by a name prefix or an explicit
+//! mapping. Every ordered pair of genomes is then summarised from its
+//! one-to-one blocks, as for [`ani`](crate::stats::ani), so duplicated or
+//! overlapping alignments are only counted once.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::paf::PAF;
+use crate::stats::{ani_of_blocks, one_to_one_blocks, Ani};
+
+
+/// How sequence names are assigned to genomes.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Genomes {
+    /// The part of the name before the first separator, e.g. `sampleA` for
+    /// `sampleA#chr1` with `#`. Names without the separator are ignored.
+    Prefix(char),
+    /// An explicit map from sequence name to genome. Names not in the map
+    /// are ignored.
+    Map(HashMap<String, String>),
+}
+
+
+impl Genomes {
+    /// The genome of a sequence, if it has one.
+    pub fn genome<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        match self {
+            Genomes::Prefix(sep) => name.split_once(*sep).map(|(genome, _)| genome),
+            Genomes::Map(map) => map.get(name).map(|g| g.as_str()),
+        }
+    }
+}
+
+
+/// Which value an [`IdentityMatrix`] writes.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum MatrixValue {
+    /// The length weighted identity of the one-to-one blocks.
+    Identity,
+    /// The fraction of the row genome covered by one-to-one blocks.
+    Coverage,
+}
+
+
+/// Identity and coverage between every ordered pair of genomes.
+///
+/// Rows are query genomes and columns are target genomes. Alignments of a
+/// genome to itself are ignored, and the diagonal is always 1.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct IdentityMatrix {
+    /// The genome names, sorted.
+    pub genomes: Vec<String>,
+    /// The total length of the sequences seen for each genome.
+    pub lengths: Vec<u64>,
+    /// The one-to-one summary of each aligned (query, target) pair of
+    /// genome indices.
+    pub pairs: BTreeMap<(usize, usize), Ani>,
+    /// The number of query genome bases covered for each pair.
+    pub covered: BTreeMap<(usize, usize), u64>,
+}
+
+
+impl IdentityMatrix {
+    /// Summarise all-vs-all records, assigning sequences to genomes.
+    pub fn new(records: &[PAF], genomes: &Genomes) -> Self {
+        let mut sequences: BTreeMap<&str, BTreeMap<&str, u64>> = BTreeMap::new();
+        for record in records {
+            for locus in [record.query(), record.target()] {
+                if let Some(genome) = genomes.genome(locus.name()) {
+                    sequences.entry(genome).or_default().insert(locus.name(), locus.length());
+                }
+            }
+        }
+
+        let names: Vec<&str> = sequences.keys().copied().collect();
+        let index: HashMap<&str, usize> = names.iter().enumerate().map(|(i, g)| (*g, i)).collect();
+
+        let mut by_pair: HashMap<(usize, usize), Vec<PAF>> = HashMap::new();
+        for record in records {
+            let query = genomes.genome(record.query().name()).map(|g| index[g]);
+            let target = genomes.genome(record.target().name()).map(|g| index[g]);
+            if let (Some(q), Some(t)) = (query, target) {
+                if q != t {
+                    by_pair.entry((q, t)).or_default().push(record.clone());
+                }
+            }
+        }
+
+        let mut matrix = IdentityMatrix {
+            genomes: names.iter().map(|g| g.to_string()).collect(),
+            lengths: sequences.values().map(|s| s.values().sum()).collect(),
+            ..IdentityMatrix::default()
+        };
+
+        for (pair, group) in by_pair {
+            // Blocks have query and target swapped.
+            let blocks = one_to_one_blocks(&group);
+            if let Some(ani) = ani_of_blocks(&blocks) {
+                let covered = blocks.iter().map(|b| b.target().end() - b.target().start()).sum();
+                matrix.pairs.insert(pair, ani);
+                matrix.covered.insert(pair, covered);
+            }
+        }
+        matrix
+    }
+
+    /// The identity of a pair of genome indices, 0 if they are unaligned.
+    pub fn identity(&self, query: usize, target: usize) -> f64 {
+        if query == target {
+            return 1.0;
+        }
+        self.pairs.get(&(query, target)).map_or(0.0, |a| a.ani)
+    }
+
+    /// The fraction of the query genome covered, 0 if they are unaligned.
+    pub fn coverage(&self, query: usize, target: usize) -> f64 {
+        if query == target {
+            return 1.0;
+        }
+        match (self.covered.get(&(query, target)), self.lengths.get(query)) {
+            (Some(covered), Some(&length)) if length > 0 => *covered as f64 / length as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// Write one value as a square TSV matrix with a header row, for
+    /// clustering or drawing dendrograms.
+    pub fn write_tsv<W: Write>(&self, value: MatrixValue, mut writer: W) -> Result<(), Error> {
+        writeln!(writer, "genome\t{}", self.genomes.join("\t")).context(Io)?;
+
+        for (q, name) in self.genomes.iter().enumerate() {
+            let row: Vec<String> = (0..self.genomes.len())
+                .map(|t| match value {
+                    MatrixValue::Identity => self.identity(q, t),
+                    MatrixValue::Coverage => self.coverage(q, t),
+                })
+                .map(|v| v.to_string())
+                .collect();
+            writeln!(writer, "{}\t{}", name, row.join("\t")).context(Io)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_identity_matrix() {
+        let records = vec![
+            paf("A#1\t1000\t0\t800\t+\tB#1\t1000\t0\t800\t760\t800\t60\tcg:Z:800M"),
+            // A duplicate of part of the block above is only counted once.
+            paf("A#1\t1000\t0\t400\t+\tB#1\t1000\t0\t400\t380\t400\t60\tcg:Z:400M"),
+            paf("B#1\t1000\t0\t800\t+\tA#1\t1000\t0\t800\t760\t800\t60"),
+            paf("A#1\t1000\t0\t500\t+\tC#1\t2000\t0\t500\t400\t500\t60"),
+            paf("A#1\t1000\t0\t1000\t+\tA#1\t1000\t0\t1000\t1000\t1000\t60"),
+            paf("chrUn\t1000\t0\t1000\t+\tA#1\t1000\t0\t1000\t1000\t1000\t60"),
+        ];
+        let genomes = Genomes::Prefix('#');
+        let matrix = IdentityMatrix::new(&records, &genomes);
+
+        assert_eq!(matrix.genomes, vec!["A", "B", "C"]);
+        assert_eq!(matrix.lengths, vec![1000, 1000, 2000]);
+        assert_eq!(matrix.pairs[&(0, 1)].aligned_bases, 800);
+        assert_eq!(matrix.identity(0, 1), 0.95);
+        assert_eq!(matrix.coverage(0, 2), 0.5);
+        assert_eq!(matrix.identity(2, 0), 0.0);
+
+        let mut output = Vec::new();
+        matrix.write_tsv(MatrixValue::Coverage, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "genome\tA\tB\tC\nA\t1\t0.8\t0.5\nB\t0.8\t1\t0\nC\t0\t0\t1\n"
+        );
+    }
+}
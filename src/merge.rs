@@ -0,0 +1,238 @@
+//! Merging several PAF files into one sorted stream.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::escape::escape_name;
+use crate::fields::Fields;
+use crate::paf::PAF;
+use crate::reader::PafReader;
+use crate::sort::{external_sort, merge_sorted, SortBy};
+use crate::tags::is_tag;
+use crate::writer::PafWriter;
+
+
+/// The default memory limit for sorting unsorted inputs, 256 MiB.
+pub const DEFAULT_MEM_LIMIT: usize = 256 * 1024 * 1024;
+
+
+/// Merges PAF files into a single sorted stream.
+#[derive(Debug, Clone)]
+pub struct Merger {
+    by: SortBy,
+    presorted: bool,
+    dedup: bool,
+    source_tag: Option<String>,
+    mem_limit: usize,
+}
+
+
+impl Default for Merger {
+    fn default() -> Self {
+        Merger {
+            by: SortBy::Target,
+            presorted: false,
+            dedup: true,
+            source_tag: None,
+            mem_limit: DEFAULT_MEM_LIMIT,
+        }
+    }
+}
+
+
+impl Merger {
+    /// Construct a merger that sorts by target and removes duplicates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the ordering of the output.
+    pub fn with_sort_by(mut self, by: SortBy) -> Self {
+        self.by = by;
+        self
+    }
+
+    /// Declare that every input is already sorted, so they are merged
+    /// directly without sorting each first.
+    ///
+    /// Merging returns `Error::UnsortedFile` if an input turns out not to be.
+    pub fn with_presorted(mut self, presorted: bool) -> Self {
+        self.presorted = presorted;
+        self
+    }
+
+    /// Drop records identical to an earlier one, including across inputs.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Add a tag, e.g. `sf`, holding the file name each record came from.
+    ///
+    /// Tags are added after deduplication, so a record in several inputs
+    /// is tagged with the first. Any existing tag of the same name is
+    /// replaced. Whitespace, control characters and `%` in the file name
+    /// are percent escaped, as by [`escape_name`].
+    pub fn with_source_tag(mut self, tag: Option<&str>) -> Self {
+        self.source_tag = tag.map(|t| t.to_string());
+        self
+    }
+
+    /// Set the approximate memory used when sorting each input.
+    pub fn with_mem_limit(mut self, mem_limit: usize) -> Self {
+        self.mem_limit = mem_limit;
+        self
+    }
+
+    /// Merge the files at `paths` into `output`.
+    pub fn merge<P, W>(&self, paths: &[P], output: W) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+        W: Write,
+    {
+        let mut readers = Vec::with_capacity(paths.len());
+        for path in paths {
+            let input = crate::io::open_path(path)?;
+            let input: Box<dyn BufRead> = if self.presorted {
+                input
+            } else {
+                Box::new(self.sorted_copy(input)?)
+            };
+            readers.push(PafReader::new(input));
+        }
+
+        let sources: Vec<String> = paths
+            .iter()
+            .map(|p| {
+                let path = p.as_ref();
+                let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                escape_name(&name).into_owned()
+            })
+            .collect();
+
+        let mut writer = PafWriter::new(output);
+        let mut pending: Vec<(usize, PAF)> = Vec::new();
+
+        let emit = |run, record: PAF| {
+            let same_key = pending
+                .first()
+                .is_some_and(|(_, p)| self.by.compare(p, &record).is_eq());
+
+            if !same_key {
+                self.flush(&mut pending, &sources, &mut writer)?;
+            } else if self.dedup && pending.iter().any(|(_, p)| *p == record) {
+                return Ok(());
+            }
+
+            pending.push((run, record));
+            Ok(())
+        };
+        let unsorted = |run: usize, line_num| Error::UnsortedFile {
+            path: paths[run].as_ref().to_path_buf(),
+            line_num,
+            by: self.by.to_string(),
+        };
+
+        merge_sorted(&mut readers, self.by, emit, unsorted)?;
+
+        self.flush(&mut pending, &sources, &mut writer)?;
+        writer.flush()
+    }
+
+    /// Sort an input into an anonymous temporary file.
+    fn sorted_copy(&self, input: Box<dyn BufRead>) -> Result<BufReader<File>, Error> {
+        let mut file = tempfile::tempfile().context(Io)?;
+        external_sort(input, &mut file, self.by, self.mem_limit)?;
+        file.seek(SeekFrom::Start(0)).context(Io)?;
+        Ok(BufReader::new(file))
+    }
+
+    /// Write out records sharing a sort key, which may contain duplicates.
+    fn flush<W: Write>(
+        &self,
+        pending: &mut Vec<(usize, PAF)>,
+        sources: &[String],
+        writer: &mut PafWriter<W>,
+    ) -> Result<(), Error> {
+        for (run, mut record) in pending.drain(..) {
+            if let Some(tag) = &self.source_tag {
+                let mut fields: Fields = record.fields()
+                    .iter()
+                    .filter(|f| !is_tag(f, tag))
+                    .collect();
+                fields.push(&format!("{}:Z:{}", tag, sources[run]));
+                *record.fields_mut() = fields;
+            }
+            writer.write_record(&record)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Merge PAF files into one stream sorted by target, removing duplicates.
+///
+/// Inputs may be unsorted. See [`Merger`] for more options.
+pub fn merge<P, W>(paths: &[P], output: W) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    Merger::new().merge(paths, output)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.paf");
+        let b = dir.path().join("b.paf");
+        std::fs::write(&a, "q2\t100\t0\t100\t+\tt1\t1000\t500\t600\t90\t100\t60\n\
+                            q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\n").unwrap();
+        std::fs::write(&b, "q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\n\
+                            q3\t100\t0\t100\t+\tt0\t1000\t0\t100\t90\t100\t60\n").unwrap();
+
+        let mut output = Vec::new();
+        Merger::new()
+            .with_source_tag(Some("sf"))
+            .merge(&[&a, &b], &mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "q3\t100\t0\t100\t+\tt0\t1000\t0\t100\t90\t100\t60\tsf:Z:b.paf\n\
+             q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tsf:Z:a.paf\n\
+             q2\t100\t0\t100\t+\tt1\t1000\t500\t600\t90\t100\t60\tsf:Z:a.paf\n"
+        );
+
+        let result = Merger::new().with_presorted(true).merge(&[&a, &b], Vec::new());
+        match result {
+            Err(Error::UnsortedFile { path, line_num, .. }) => assert_eq!((path, line_num), (b, 2)),
+            r => panic!("expected an unsorted file error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_merge_source_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a\tb%.paf");
+        std::fs::write(&a, "q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tsf:Z:old\tNM:i:1\n").unwrap();
+
+        let mut output = Vec::new();
+        Merger::new()
+            .with_source_tag(Some("sf"))
+            .merge(&[&a], &mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tNM:i:1\tsf:Z:a%09b%25.paf\n"
+        );
+    }
+}
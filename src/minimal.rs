@@ -0,0 +1,271 @@
+//! A compact projection of records, without optional fields.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::paf::{Locus, Strand, PAF};
+use crate::record::PafRecord;
+
+
+/// Shares one allocation between all copies of a sequence name.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    names: HashSet<Arc<str>>,
+}
+
+
+impl Interner {
+    /// Construct an empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the shared copy of `name`, adding it if it is new.
+    pub fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(existing) = self.names.get(name) {
+            return Arc::clone(existing);
+        }
+        let name: Arc<str> = Arc::from(name);
+        self.names.insert(Arc::clone(&name));
+        name
+    }
+
+    /// The number of distinct names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Whether no names have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+
+/// The numeric columns, stored as `u32` when they all fit.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+enum Coords {
+    Small([u32; 8]),
+    Large(Box<[u64; 8]>),
+}
+
+
+impl Coords {
+    fn new(values: [u64; 8]) -> Self {
+        if values.iter().all(|v| *v <= u32::MAX as u64) {
+            let mut small = [0; 8];
+            for (s, v) in small.iter_mut().zip(&values) {
+                *s = *v as u32;
+            }
+            Coords::Small(small)
+        } else {
+            Coords::Large(Box::new(values))
+        }
+    }
+
+    fn get(&self, i: usize) -> u64 {
+        match self {
+            Coords::Small(values) => values[i] as u64,
+            Coords::Large(values) => values[i],
+        }
+    }
+}
+
+
+const QLEN: usize = 0;
+const QSTART: usize = 1;
+const QEND: usize = 2;
+const TLEN: usize = 3;
+const TSTART: usize = 4;
+const TEND: usize = 5;
+const NMATCH: usize = 6;
+const ALNLEN: usize = 7;
+
+
+/// The 12 mandatory columns of a record in a compact form.
+///
+/// Names are shared through an [`Interner`] and coordinates are stored as
+/// `u32` where possible, so large sets of records for coverage-style
+/// analyses take a fraction of the memory of [`PAF`].
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct MinimalPaf {
+    query: Arc<str>,
+    target: Arc<str>,
+    coords: Coords,
+    strand: Strand,
+    mapq: u8,
+}
+
+
+impl MinimalPaf {
+    /// The query sequence name.
+    pub fn query_name(&self) -> &str {
+        &self.query
+    }
+
+    /// The query sequence length.
+    pub fn query_length(&self) -> u64 {
+        self.coords.get(QLEN)
+    }
+
+    /// The 0-based start of the aligned query region.
+    pub fn query_start(&self) -> u64 {
+        self.coords.get(QSTART)
+    }
+
+    /// The 0-based, exclusive end of the aligned query region.
+    pub fn query_end(&self) -> u64 {
+        self.coords.get(QEND)
+    }
+
+    /// The relative strand of the query and target.
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    /// The target sequence name.
+    pub fn target_name(&self) -> &str {
+        &self.target
+    }
+
+    /// The target sequence length.
+    pub fn target_length(&self) -> u64 {
+        self.coords.get(TLEN)
+    }
+
+    /// The 0-based start of the aligned target region.
+    pub fn target_start(&self) -> u64 {
+        self.coords.get(TSTART)
+    }
+
+    /// The 0-based, exclusive end of the aligned target region.
+    pub fn target_end(&self) -> u64 {
+        self.coords.get(TEND)
+    }
+
+    /// The number of matching bases in the alignment.
+    pub fn nmatch(&self) -> u64 {
+        self.coords.get(NMATCH)
+    }
+
+    /// The number of bases in the alignment, including gaps.
+    pub fn alnlen(&self) -> u64 {
+        self.coords.get(ALNLEN)
+    }
+
+    /// The mapping quality (255 for missing).
+    pub fn mapq(&self) -> u8 {
+        self.mapq
+    }
+
+    /// Expand back into a full record, without optional fields.
+    pub fn to_paf(&self) -> PAF {
+        PAF::new(
+            Locus::new(self.query.to_string(), self.query_length(), self.query_start(), self.query_end()),
+            self.strand,
+            Locus::new(self.target.to_string(), self.target_length(), self.target_start(), self.target_end()),
+            self.nmatch(),
+            self.alnlen(),
+            self.mapq,
+            Vec::new(),
+        )
+    }
+}
+
+
+impl PafRecord for MinimalPaf {
+    fn query_name(&self) -> &str {
+        MinimalPaf::query_name(self)
+    }
+
+    fn query_length(&self) -> u64 {
+        MinimalPaf::query_length(self)
+    }
+
+    fn query_start(&self) -> u64 {
+        MinimalPaf::query_start(self)
+    }
+
+    fn query_end(&self) -> u64 {
+        MinimalPaf::query_end(self)
+    }
+
+    fn strand(&self) -> Strand {
+        MinimalPaf::strand(self)
+    }
+
+    fn target_name(&self) -> &str {
+        MinimalPaf::target_name(self)
+    }
+
+    fn target_length(&self) -> u64 {
+        MinimalPaf::target_length(self)
+    }
+
+    fn target_start(&self) -> u64 {
+        MinimalPaf::target_start(self)
+    }
+
+    fn target_end(&self) -> u64 {
+        MinimalPaf::target_end(self)
+    }
+
+    fn nmatch(&self) -> u64 {
+        MinimalPaf::nmatch(self)
+    }
+
+    fn alnlen(&self) -> u64 {
+        MinimalPaf::alnlen(self)
+    }
+
+    fn mapq(&self) -> u8 {
+        MinimalPaf::mapq(self)
+    }
+}
+
+
+impl PAF {
+    /// Project the record onto its mandatory columns, dropping any tags.
+    pub fn minimize(&self, interner: &mut Interner) -> MinimalPaf {
+        let (query, target) = (self.query(), self.target());
+        MinimalPaf {
+            query: interner.intern(query.name()),
+            target: interner.intern(target.name()),
+            coords: Coords::new([
+                query.length(),
+                query.start(),
+                query.end(),
+                target.length(),
+                target.start(),
+                target.end(),
+                self.nmatch(),
+                self.alnlen(),
+            ]),
+            strand: self.strand(),
+            mapq: self.mapq(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize() {
+        let mut interner = Interner::new();
+        let small: PAF = "q1\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t60\tcg:Z:10M".parse().unwrap();
+        let large: PAF = "q2\t10\t0\t10\t+\tt1\t8000000000\t7000000000\t7000000010\t9\t10\t60".parse().unwrap();
+
+        let a = small.minimize(&mut interner);
+        let b = large.minimize(&mut interner);
+        assert_eq!(interner.len(), 3);
+        assert!(Arc::ptr_eq(&a.target, &b.target));
+
+        assert_eq!(a.target_start(), 5);
+        assert_eq!(a.strand(), Strand::Minus);
+        assert_eq!(a.to_paf().to_string(), "q1\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t60");
+        assert_eq!(b.to_paf(), large);
+    }
+}
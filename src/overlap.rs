@@ -0,0 +1,156 @@
+//! A view of all-vs-all read overlaps, as from `minimap2 -x ava-ont`.
+
+use crate::paf::{Strand, PAF};
+
+
+/// How two overlapping reads relate to each other.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub enum OverlapKind {
+    /// The overlap stops short of the read ends, e.g. from a repeat.
+    Internal,
+    /// The query is contained in the target.
+    QueryContained,
+    /// The target is contained in the query.
+    TargetContained,
+    /// The end of the query overlaps the start of the target.
+    QueryToTarget,
+    /// The end of the target overlaps the start of the query.
+    TargetToQuery,
+}
+
+
+/// An overlap between two reads, with overhangs precomputed.
+///
+/// Query coordinates are oriented to the target strand, so for minus
+/// strand overlaps they count from the end of the query.
+#[derive(Debug, Clone, Copy)]
+pub struct OverlapRecord<'a> {
+    record: &'a PAF,
+    query_start: u64,
+    query_end: u64,
+    left_overhang: u64,
+    right_overhang: u64,
+}
+
+
+impl<'a> OverlapRecord<'a> {
+    /// Construct the view of a record.
+    pub fn new(record: &'a PAF) -> Self {
+        let query = record.query();
+        let target = record.target();
+
+        let (query_start, query_end) = match record.strand() {
+            Strand::Minus => (
+                query.length().saturating_sub(query.end()),
+                query.length().saturating_sub(query.start()),
+            ),
+            _ => (query.start(), query.end()),
+        };
+
+        let left_overhang = query_start.min(target.start());
+        let right_overhang = query.length().saturating_sub(query_end)
+            .min(target.length().saturating_sub(target.end()));
+
+        OverlapRecord { record, query_start, query_end, left_overhang, right_overhang }
+    }
+
+    /// The underlying record.
+    pub fn record(&self) -> &'a PAF {
+        self.record
+    }
+
+    /// The start of the query overlap, oriented to the target strand.
+    pub fn query_start(&self) -> u64 {
+        self.query_start
+    }
+
+    /// The end of the query overlap, oriented to the target strand.
+    pub fn query_end(&self) -> u64 {
+        self.query_end
+    }
+
+    /// Unaligned bases before the overlap present on both reads.
+    pub fn left_overhang(&self) -> u64 {
+        self.left_overhang
+    }
+
+    /// Unaligned bases after the overlap present on both reads.
+    pub fn right_overhang(&self) -> u64 {
+        self.right_overhang
+    }
+
+    /// The divergence estimate in the `dv` tag.
+    pub fn divergence(&self) -> Option<f64> {
+        self.record.dv()
+    }
+
+    /// The number of minimizers on the chain in the `cm` tag.
+    pub fn minimizers(&self) -> Option<i64> {
+        self.record.cm()
+    }
+
+    /// Classify the overlap, allowing overhangs of up to `max_overhang`.
+    ///
+    /// This follows the rules used by miniasm to build its string graph.
+    pub fn kind(&self, max_overhang: u64) -> OverlapKind {
+        if self.left_overhang > max_overhang || self.right_overhang > max_overhang {
+            return OverlapKind::Internal;
+        }
+
+        let query_tail = self.record.query().length().saturating_sub(self.query_end);
+        let target = self.record.target();
+        let target_tail = target.length().saturating_sub(target.end());
+
+        if self.query_start <= target.start() && query_tail <= target_tail {
+            OverlapKind::QueryContained
+        } else if self.query_start >= target.start() && query_tail >= target_tail {
+            OverlapKind::TargetContained
+        } else if self.query_start > target.start() {
+            OverlapKind::QueryToTarget
+        } else {
+            OverlapKind::TargetToQuery
+        }
+    }
+}
+
+
+impl PAF {
+    /// View the record as an overlap between two reads.
+    pub fn as_overlap(&self) -> OverlapRecord<'_> {
+        OverlapRecord::new(self)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_overlap() {
+        // The last 400 bases of r1 overlap the first 400 of r2.
+        let record = paf("r1\t1000\t600\t1000\t+\tr2\t2000\t0\t400\t350\t400\t0\tdv:f:0.05\tcm:i:40");
+        let overlap = record.as_overlap();
+        assert_eq!((overlap.left_overhang(), overlap.right_overhang()), (0, 0));
+        assert_eq!(overlap.kind(10), OverlapKind::QueryToTarget);
+        assert_eq!(overlap.divergence(), Some(0.05));
+        assert_eq!(overlap.minimizers(), Some(40));
+
+        // The same overlap with r1 reverse complemented.
+        let record = paf("r1\t1000\t0\t400\t-\tr2\t2000\t0\t400\t350\t400\t0");
+        let overlap = record.as_overlap();
+        assert_eq!((overlap.query_start(), overlap.query_end()), (600, 1000));
+        assert_eq!(overlap.kind(10), OverlapKind::QueryToTarget);
+
+        let record = paf("r1\t1000\t0\t1000\t+\tr2\t2000\t500\t1500\t900\t1000\t0");
+        assert_eq!(record.as_overlap().kind(10), OverlapKind::QueryContained);
+
+        let record = paf("r1\t1000\t200\t600\t+\tr2\t2000\t500\t900\t350\t400\t0");
+        assert_eq!(record.as_overlap().left_overhang(), 200);
+        assert_eq!(record.as_overlap().kind(10), OverlapKind::Internal);
+    }
+}
@@ -1,16 +1,28 @@
-use std::fmt;
-use std::str::FromStr;
+use core::cmp::Ordering;
+use core::fmt;
+use core::str::FromStr;
 
-use std::convert::TryFrom;
+use core::convert::TryFrom;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 use nom::combinator::{cut, all_consuming};
 use crate::errors::Error;
+use crate::tags::{is_tag, Tag, TagValue};
+use crate::cigar::Cigar;
+use crate::cs::Cs;
+use crate::fields::Fields;
 
 /// Represent the strand of the alignment between two loci.
-#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+///
+/// Some PAF-like formats use `*` or `.` when the strand is not known. Both
+/// parse as `Unknown`, which is written back as `*`.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash, Ord, PartialOrd)]
 pub enum Strand {
     Plus,
     Minus,
+    Unknown,
 }
 
 
@@ -22,21 +34,23 @@ impl fmt::Display for Strand {
 }
 
 
-impl Into<char> for &Strand {
-    fn into(self) -> char {
-        match self {
+impl From<&Strand> for char {
+    fn from(strand: &Strand) -> Self {
+        match strand {
             Strand::Plus => '+',
-            Strand::Minus => '-'
+            Strand::Minus => '-',
+            Strand::Unknown => '*',
         }
     }
 }
 
 
-impl Into<u8> for &Strand {
-    fn into(self) -> u8 {
-        match self {
+impl From<&Strand> for u8 {
+    fn from(strand: &Strand) -> Self {
+        match strand {
             Strand::Plus => b'+',
-            Strand::Minus => b'-'
+            Strand::Minus => b'-',
+            Strand::Unknown => b'*',
         }
     }
 }
@@ -49,7 +63,8 @@ impl TryFrom<char> for Strand {
         match value {
             '+' => Ok(Strand::Plus),
             '-' => Ok(Strand::Minus),
-            c   => Err(Error::ParseChar { got: c, expected: "+-".to_string() })
+            '*' | '.' => Ok(Strand::Unknown),
+            c   => Err(Error::ParseChar { got: c, expected: "+-*.".to_string() })
         }
     }
 }
@@ -62,7 +77,8 @@ impl TryFrom<u8> for Strand {
         match value {
             b'+' => Ok(Strand::Plus),
             b'-' => Ok(Strand::Minus),
-            c    => Err(Error::ParseChar { got: c as char, expected: "+-".to_string() })
+            b'*' | b'.' => Ok(Strand::Unknown),
+            c    => Err(Error::ParseChar { got: c as char, expected: "+-*.".to_string() })
         }
     }
 }
@@ -83,6 +99,78 @@ impl Locus {
     pub fn new(name: String, length: u64, start: u64, end: u64) -> Self {
         Locus { name, length, start, end }
     }
+
+    /// Construct a new `Locus`, checking that `start <= end <= length`.
+    pub fn try_new(name: String, length: u64, start: u64, end: u64) -> Result<Self, Error> {
+        let locus = Locus::new(name, length, start, end);
+        locus.validate()?;
+        Ok(locus)
+    }
+
+    /// Check that `start <= end <= length`.
+    ///
+    /// Returns `Error::InvalidInterval` otherwise.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.start <= self.end && self.end <= self.length {
+            Ok(())
+        } else {
+            Err(Error::InvalidInterval {
+                line_num: None,
+                name: self.name.clone(),
+                length: self.length,
+                start: self.start,
+                end: self.end,
+            })
+        }
+    }
+
+    /// Parse a `Locus` and check its invariants, see [`Locus::validate`].
+    ///
+    /// The `FromStr` implementation does not check the invariants.
+    pub fn parse_checked(s: &str) -> Result<Self, Error> {
+        let locus: Locus = s.parse()?;
+        locus.validate()?;
+        Ok(locus)
+    }
+
+    /// The sequence name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The total length of the sequence.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Replace the sequence name.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// The 0-based start of the aligned region.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The 0-based, exclusive end of the aligned region.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// The 1-based, inclusive start, as used by GFF, VCF and SAM.
+    pub fn start_1based(&self) -> u64 {
+        self.start + 1
+    }
+
+    /// The 1-based, inclusive end, as used by GFF, VCF and SAM.
+    ///
+    /// This is numerically the same as the 0-based, exclusive end. For an
+    /// empty region it is one less than [`Locus::start_1based`].
+    pub fn end_inclusive(&self) -> u64 {
+        self.end
+    }
 }
 
 
@@ -97,7 +185,7 @@ impl FromStr for Locus {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (rem, locus) = all_consuming(cut(crate::complete::locus_str))(s)
+        let (_, locus) = all_consuming(cut(crate::complete::locus_str))(s)
             .map_err(|e| {
                 match e {
                     nom::Err::Error(ei) | nom::Err::Failure(ei) => {
@@ -132,7 +220,8 @@ impl FromStr for Locus {
 //}
 
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+/// A single alignment record.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
 pub struct PAF {
     query: Locus,
     strand: Strand,
@@ -140,11 +229,17 @@ pub struct PAF {
     nmatch: u64,
     alnlen: u64,
     mq: u8,
-    fields: Vec<String>,
+    fields: Fields,
 }
 
 
 impl PAF {
+    /// Construct a record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fields` is converted from strings and one of them is not a
+    /// valid field, see [`Fields::try_push`].
     pub fn new(
         query: Locus,
         strand: Strand,
@@ -152,7 +247,7 @@ impl PAF {
         nmatch: u64,
         alnlen: u64,
         mq: u8,
-        fields: Vec<String>
+        fields: impl Into<Fields>
     ) -> Self {
         PAF {
             query,
@@ -161,15 +256,235 @@ impl PAF {
             nmatch,
             alnlen,
             mq,
-            fields,
+            fields: fields.into(),
+        }
+    }
+
+    /// The aligned region of the query sequence.
+    pub fn query(&self) -> &Locus {
+        &self.query
+    }
+
+    /// Mutable access to the query region.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn query_mut(&mut self) -> &mut Locus {
+        &mut self.query
+    }
+
+    /// The relative strand of the query and target.
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    /// The 0-based query start on the original read strand.
+    ///
+    /// PAF always gives query coordinates on the read as sequenced, whatever
+    /// the strand, so this is the same as `self.query().start()`.
+    pub fn query_start_on_read(&self) -> u64 {
+        self.query.start
+    }
+
+    /// The 0-based, exclusive query end on the original read strand.
+    pub fn query_end_on_read(&self) -> u64 {
+        self.query.end
+    }
+
+    /// The 0-based query start on the strand that aligns to the target.
+    ///
+    /// For minus strand records this counts from the end of the read, as
+    /// positions on its reverse complement, which is the sequence that runs
+    /// along the target in the `cg` and `cs` tags. Otherwise it is the same
+    /// as [`PAF::query_start_on_read`].
+    pub fn query_start_on_alignment(&self) -> u64 {
+        if self.strand == Strand::Minus {
+            self.query.length.saturating_sub(self.query.end)
+        } else {
+            self.query.start
         }
     }
+
+    /// The 0-based, exclusive query end on the strand that aligns to the
+    /// target, see [`PAF::query_start_on_alignment`].
+    pub fn query_end_on_alignment(&self) -> u64 {
+        if self.strand == Strand::Minus {
+            self.query.length.saturating_sub(self.query.start)
+        } else {
+            self.query.end
+        }
+    }
+
+    /// Convert a 0-based position on the read to the alignment strand.
+    ///
+    /// Returns `None` if the position is not within the query sequence.
+    pub fn query_pos_on_alignment(&self, read_pos: u64) -> Option<u64> {
+        self.flip_query_pos(read_pos)
+    }
+
+    /// Convert a 0-based position on the alignment strand to the read.
+    ///
+    /// Returns `None` if the position is not within the query sequence.
+    pub fn query_pos_on_read(&self, alignment_pos: u64) -> Option<u64> {
+        self.flip_query_pos(alignment_pos)
+    }
+
+    /// Reverse a query position for minus strand records. Reversal is its
+    /// own inverse, so this converts in either direction.
+    fn flip_query_pos(&self, pos: u64) -> Option<u64> {
+        if pos >= self.query.length {
+            None
+        } else if self.strand == Strand::Minus {
+            Some(self.query.length - 1 - pos)
+        } else {
+            Some(pos)
+        }
+    }
+
+    /// The aligned region of the target sequence.
+    pub fn target(&self) -> &Locus {
+        &self.target
+    }
+
+    /// Mutable access to the target region.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn target_mut(&mut self) -> &mut Locus {
+        &mut self.target
+    }
+
+    /// The number of matching bases in the alignment.
+    pub fn nmatch(&self) -> u64 {
+        self.nmatch
+    }
+
+    /// The number of bases in the alignment, including gaps.
+    pub fn alnlen(&self) -> u64 {
+        self.alnlen
+    }
+
+    /// The fraction of the alignment columns that are matches.
+    ///
+    /// Returns 0 for records with an alignment length of 0.
+    pub fn identity(&self) -> f64 {
+        if self.alnlen == 0 {
+            0.0
+        } else {
+            self.nmatch as f64 / self.alnlen as f64
+        }
+    }
+
+    /// Parse the CIGAR in the `cg` tag.
+    ///
+    /// Returns `None` if the tag is absent or malformed.
+    pub fn cigar(&self) -> Option<Cigar> {
+        self.tag("cg")
+            .and_then(|v| v.as_str().and_then(|s| s.parse().ok()))
+    }
+
+    /// Parse the difference string in the `cs` tag.
+    ///
+    /// Returns `None` if the tag is absent or malformed.
+    pub fn cs(&self) -> Option<Cs> {
+        self.tag("cs")
+            .and_then(|v| v.as_str().and_then(|s| s.parse().ok()))
+    }
+
+    /// A score for ranking alternative alignments.
+    ///
+    /// This is the `AS` tag if present, falling back to the number of
+    /// matching bases.
+    pub fn score(&self) -> i64 {
+        self.tag("AS")
+            .and_then(|v| v.as_int())
+            .unwrap_or(self.nmatch as i64)
+    }
+
+    /// The approximate per-base divergence in the `dv` tag.
+    pub fn dv(&self) -> Option<f64> {
+        self.tag("dv").and_then(|v| v.as_float())
+    }
+
+    /// The number of minimizers on the chain in the `cm` tag.
+    pub fn cm(&self) -> Option<i64> {
+        self.tag("cm").and_then(|v| v.as_int())
+    }
+
+    /// The chaining score in the `s1` tag.
+    pub fn s1(&self) -> Option<i64> {
+        self.tag("s1").and_then(|v| v.as_int())
+    }
+
+    /// The best secondary chaining score in the `s2` tag.
+    pub fn s2(&self) -> Option<i64> {
+        self.tag("s2").and_then(|v| v.as_int())
+    }
+
+    /// The alignment type in the `tp` tag, e.g. `P` for primary.
+    pub fn tp(&self) -> Option<char> {
+        self.tag("tp").and_then(|v| v.as_char())
+    }
+
+    /// Whether the record is a primary alignment.
+    ///
+    /// Records with `tp:A:P`, `tp:A:I` (a primary inversion) or no `tp`
+    /// tag are primary.
+    pub fn is_primary(&self) -> bool {
+        matches!(self.tp(), None | Some('P') | Some('I'))
+    }
+
+    /// The mapping quality (255 for missing).
+    pub fn mapq(&self) -> u8 {
+        self.mq
+    }
+
+    /// Replace the mapping quality.
+    pub fn set_mapq(&mut self, mapq: u8) {
+        self.mq = mapq;
+    }
+
+    /// The optional SAM-style fields following the mandatory columns.
+    ///
+    /// This used to return `&[String]`. [`Fields`] supports the same
+    /// `len`, `get`, `iter` and indexing, but yields `&str`, so use
+    /// [`Fields::to_vec`] where owned strings are needed.
+    pub fn fields(&self) -> &Fields {
+        &self.fields
+    }
+
+    /// Mutable access to the optional fields.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn fields_mut(&mut self) -> &mut Fields {
+        &mut self.fields
+    }
+
+    /// Parse each of the optional fields as a typed tag.
+    pub fn tags(&self) -> impl Iterator<Item = Result<Tag, Error>> + '_ {
+        self.fields.iter().map(|f| f.parse())
+    }
+
+    /// The optional fields that are not SAM-style tags.
+    ///
+    /// These are only present in records read in lenient mode.
+    pub fn extra_columns(&self) -> impl Iterator<Item = &str> + '_ {
+        self.fields
+            .iter()
+            .filter(|f| f.parse::<Tag>().is_err())
+    }
+
+    /// Get the value of the first tag with `name`.
+    ///
+    /// Returns `None` if the tag is absent or its value is malformed.
+    pub fn tag(&self, name: &str) -> Option<TagValue> {
+        self.fields
+            .iter()
+            .find(|f| is_tag(f, name))
+            .and_then(|f| f.parse::<Tag>().ok())
+            .map(Tag::into_value)
+    }
 }
 
 
 impl fmt::Display for PAF {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.fields.len() > 1 {
+        if !self.fields.is_empty() {
             write!(
                 f,
                 "{}\t{}\t{}\t{}\t{}\t{}\t{}",
@@ -179,7 +494,7 @@ impl fmt::Display for PAF {
                 self.nmatch,
                 self.alnlen,
                 self.mq,
-                self.fields.join("\t")
+                self.fields
             )
         } else {
             write!(
@@ -197,11 +512,39 @@ impl fmt::Display for PAF {
 }
 
 
+/// Records are ordered by genome position on the target.
+///
+/// The order compares target name, target start and target end, then query
+/// name and query start, matching `SortBy::Target`. Any remaining ties are
+/// broken by the other columns and then the optional fields, so the order
+/// is total and agrees with `Eq`. Records that compare equal are identical,
+/// which makes the ordering safe for `BTreeSet` and `binary_search`.
+impl Ord for PAF {
+    fn cmp(&self, other: &Self) -> Ordering {
+        crate::sort::SortBy::Target.compare(self, other)
+            .then_with(|| self.query.cmp(&other.query))
+            .then_with(|| self.target.cmp(&other.target))
+            .then_with(|| self.strand.cmp(&other.strand))
+            .then_with(|| self.nmatch.cmp(&other.nmatch))
+            .then_with(|| self.alnlen.cmp(&other.alnlen))
+            .then_with(|| self.mq.cmp(&other.mq))
+            .then_with(|| self.fields.cmp(&other.fields))
+    }
+}
+
+
+impl PartialOrd for PAF {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
 impl FromStr for PAF {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (rem, paf) = all_consuming(cut(crate::complete::paf_str))(s)
+        let (_, paf) = all_consuming(cut(crate::complete::paf_str))(s)
             .map_err(|e| {
                 match e {
                     nom::Err::Error(ei) | nom::Err::Failure(ei) => {
@@ -216,6 +559,38 @@ impl FromStr for PAF {
 }
 
 
+/// Parse every record in a multi-line string.
+///
+/// Blank lines and `#` comment lines are skipped. If any line fails to
+/// parse, all of the errors are returned as [`Error::Parse`], each with the
+/// 1-based line number it occurred on.
+pub fn parse_str(s: &str) -> Result<Vec<PAF>, Vec<Error>> {
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match all_consuming(cut(crate::complete::paf_str))(line) {
+            Ok((_, paf)) => records.push(paf),
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                errors.push(crate::errors::convert_error_str(line, e, Some(i + 1)));
+            },
+            Err(nom::Err::Incomplete(_)) => unreachable!(),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(records)
+    } else {
+        Err(errors)
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -239,5 +614,89 @@ mod tests {
     //        Err(Error::ParseStrand { got: "?".to_string() })
     //    );
     //}
-}
 
+    #[test]
+    fn test_parse_str() {
+        let input = "# header\nq1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\n\n\
+                     q2\t10\t0\tx\t+\tt1\t20\t0\t10\t10\t10\t60\n\
+                     q3\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\n\
+                     q4\t10\n";
+
+        let errors = parse_str(input).unwrap_err();
+        let lines: Vec<Option<usize>> = errors.iter().map(|e| e.line_number()).collect();
+        assert_eq!(lines, vec![Some(4), Some(6)]);
+        assert!(matches!(errors[0], Error::Parse { column: 8, .. }));
+
+        let records = parse_str(&input.replace("\tx\t", "\t10\t").replace("q4\t10\n", "")).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].query().name(), "q3");
+    }
+
+    #[test]
+    fn test_query_on_alignment() {
+        let minus: PAF = "q1\t100\t10\t30\t-\tt1\t200\t0\t20\t20\t20\t60".parse().unwrap();
+        assert_eq!((minus.query_start_on_read(), minus.query_end_on_read()), (10, 30));
+        assert_eq!((minus.query_start_on_alignment(), minus.query_end_on_alignment()), (70, 90));
+        assert_eq!(minus.query_pos_on_alignment(10), Some(89));
+        assert_eq!(minus.query_pos_on_read(89), Some(10));
+        assert_eq!(minus.query_pos_on_read(100), None);
+
+        let plus: PAF = "q1\t100\t10\t30\t+\tt1\t200\t0\t20\t20\t20\t60".parse().unwrap();
+        assert_eq!((plus.query_start_on_alignment(), plus.query_end_on_alignment()), (10, 30));
+        assert_eq!(plus.query_pos_on_alignment(10), Some(10));
+    }
+
+    #[test]
+    fn test_unknown_strand() {
+        let paf: PAF = "q1\t10\t0\t10\t.\tt1\t20\t0\t10\t10\t10\t60".parse().unwrap();
+        assert_eq!(paf.strand(), Strand::Unknown);
+        assert_eq!(paf.to_string(), "q1\t10\t0\t10\t*\tt1\t20\t0\t10\t10\t10\t60");
+        assert_eq!(paf.to_string().parse::<PAF>().unwrap(), paf);
+    }
+
+    #[test]
+    fn test_paf_display_round_trip() {
+        let line = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P";
+        let paf: PAF = line.parse().unwrap();
+        assert_eq!(paf.to_string(), line);
+
+        let line = "q1\t10\t0\t10\t-\tt1\t20\t0\t10\t10\t10\t60";
+        let paf: PAF = line.parse().unwrap();
+        assert_eq!(paf.to_string(), line);
+    }
+
+    #[test]
+    fn test_locus_try_new() {
+        assert!(Locus::try_new("chr1".to_string(), 100, 10, 100).is_ok());
+        assert!(Locus::try_new("chr1".to_string(), 100, 20, 10).is_err());
+        match Locus::try_new("chr1".to_string(), 100, 10, 101) {
+            Err(Error::InvalidInterval { end, .. }) => assert_eq!(end, 101),
+            e => panic!("Expected an invalid interval, got {:?}", e),
+        }
+
+        assert!("chr1\t100\t20\t10".parse::<Locus>().is_ok());
+        assert!(Locus::parse_checked("chr1\t100\t20\t10").is_err());
+    }
+
+    #[test]
+    fn test_locus_1based() {
+        let locus = Locus::new("chr1".to_string(), 100, 0, 10);
+        assert_eq!((locus.start_1based(), locus.end_inclusive()), (1, 10));
+
+        let empty = Locus::new("chr1".to_string(), 100, 5, 5);
+        assert_eq!((empty.start_1based(), empty.end_inclusive()), (6, 5));
+    }
+
+    #[test]
+    fn test_paf_ord() {
+        let a: PAF = "q2\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60".parse().unwrap();
+        let b: PAF = "q1\t10\t0\t10\t+\tt1\t20\t5\t15\t10\t10\t60".parse().unwrap();
+        let c: PAF = "q1\t10\t0\t10\t+\tt1\t20\t5\t15\t10\t10\t30".parse().unwrap();
+        let d: PAF = "q1\t10\t0\t10\t+\tt2\t20\t0\t10\t10\t10\t60".parse().unwrap();
+
+        let mut records = vec![d.clone(), b.clone(), a.clone(), c.clone()];
+        records.sort();
+        assert_eq!(records, vec![a, c.clone(), b, d]);
+        assert_eq!(records.binary_search(&c), Ok(1));
+    }
+}
@@ -0,0 +1,169 @@
+//! Writing records produced by several threads in their original order.
+
+use std::collections::BTreeMap;
+use std::io::{BufWriter, Write};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread::JoinHandle;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::paf::PAF;
+use crate::writer::PafWriter;
+
+
+/// The size of the output buffer, so that writes are flushed in large blocks.
+const BUFFER_SIZE: usize = 1 << 20;
+
+/// The number of batches that may be queued before senders block.
+const CHANNEL_SIZE: usize = 1024;
+
+
+type Batch = (u64, Vec<PAF>);
+
+
+/// A handle for sending batches of records to an [`OrderedWriter`].
+///
+/// Clone it to give one to each worker thread.
+#[derive(Debug, Clone)]
+pub struct OrderedSender {
+    sender: SyncSender<Batch>,
+}
+
+
+impl OrderedSender {
+    /// Send the records produced from input batch number `seq`.
+    ///
+    /// Sequence numbers start at 0 and every number must be sent exactly
+    /// once, with an empty batch if all of its records were filtered out.
+    /// Sending a number twice stops the writer with
+    /// `Error::DuplicateBatch`. Returns an error if the writer has stopped
+    /// because writing failed; the cause is returned by
+    /// [`OrderedWriter::finish`].
+    pub fn send(&self, seq: u64, records: Vec<PAF>) -> Result<(), Error> {
+        self.sender.send((seq, records)).map_err(|_| Error::Io {
+            source: std::io::Error::new(std::io::ErrorKind::BrokenPipe, "The writer thread has stopped."),
+        })
+    }
+}
+
+
+/// Write batches of records from many threads in sequence number order.
+///
+/// Batches arriving early are held until every batch before them has been
+/// written. Writing happens on a dedicated thread through a large buffer.
+///
+/// Only the channel to the writer thread is bounded. Held batches are kept
+/// in memory without limit, so a batch that is slow to arrive, or never
+/// sent, makes every later batch wait in memory. Keep the number of
+/// batches in flight bounded, e.g. with a fixed pool of workers taking
+/// batches in order.
+pub struct OrderedWriter<W> {
+    sender: Option<OrderedSender>,
+    handle: JoinHandle<Result<W, Error>>,
+}
+
+
+impl<W: Write + Send + 'static> OrderedWriter<W> {
+    /// Start a writer thread writing to `inner`.
+    pub fn new(inner: W) -> Self {
+        let (sender, receiver) = sync_channel(CHANNEL_SIZE);
+        let handle = std::thread::spawn(move || write_ordered(inner, receiver));
+        OrderedWriter { sender: Some(OrderedSender { sender }), handle }
+    }
+
+    /// Get a new handle for sending batches.
+    pub fn sender(&self) -> OrderedSender {
+        self.sender.clone().expect("Sender is only taken by finish.")
+    }
+
+    /// Wait for all senders to be dropped and everything to be written.
+    ///
+    /// Returns the underlying sink. If some sequence numbers were never
+    /// sent, the batches after the gap are still written in order.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.sender.take();
+        self.handle.join().expect("The writer thread panicked.")
+    }
+}
+
+
+/// Receive batches and write them in order.
+fn write_ordered<W: Write>(inner: W, receiver: Receiver<Batch>) -> Result<W, Error> {
+    let mut writer = PafWriter::new(BufWriter::with_capacity(BUFFER_SIZE, inner));
+    let mut pending: BTreeMap<u64, Vec<PAF>> = BTreeMap::new();
+    let mut next = 0;
+
+    for (seq, records) in receiver {
+        if seq < next || pending.contains_key(&seq) {
+            return Err(Error::DuplicateBatch { seq });
+        }
+        pending.insert(seq, records);
+        while let Some(records) = pending.remove(&next) {
+            for record in &records {
+                writer.write_record(record)?;
+            }
+            next += 1;
+        }
+    }
+
+    for records in pending.values() {
+        for record in records {
+            writer.write_record(record)?;
+        }
+    }
+
+    writer.into_inner().into_inner().map_err(|e| e.into_error()).context(Io)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_writer() {
+        let lines: Vec<String> = (0..100)
+            .map(|i| format!("q{}\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60", i))
+            .collect();
+
+        let writer = OrderedWriter::new(Vec::new());
+        let threads: Vec<_> = (0..4u64)
+            .map(|t| {
+                let sender = writer.sender();
+                let lines = lines.clone();
+                std::thread::spawn(move || {
+                    // Each thread handles every fourth batch, in reverse.
+                    for seq in (0..25u64).rev().map(|i| i * 4 + t) {
+                        let records = vec![lines[seq as usize].parse().unwrap()];
+                        sender.send(seq, records).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(output, lines.join("\n") + "\n");
+    }
+
+    #[test]
+    fn test_ordered_writer_duplicates() {
+        // Once written, and while still held.
+        for order in &[[0, 0], [1, 1]] {
+            let writer = OrderedWriter::new(Vec::new());
+            let sender = writer.sender();
+            for seq in order {
+                // The second send may fail if the writer has already stopped.
+                let _ = sender.send(*seq, Vec::new());
+            }
+            drop(sender);
+
+            let result = writer.finish();
+            assert!(matches!(result, Err(Error::DuplicateBatch { seq }) if seq == order[0]));
+        }
+    }
+}
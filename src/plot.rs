@@ -0,0 +1,247 @@
+//! Rendering alignments as an SVG dot plot.
+//!
+//! Targets run along the x axis and queries up the y axis, each placed end
+//! to end at cumulative offsets, longest first. Each record is drawn as a
+//! line from its start to its end, with minus strand records running
+//! backwards in a second colour.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::paf::{Strand, PAF};
+
+
+/// The size and colours of a [`DotPlot`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlotOptions {
+    /// The width of the plotting area in pixels, excluding margins.
+    pub width: f64,
+    /// The height of the plotting area in pixels, excluding margins.
+    pub height: f64,
+    /// The space around the plotting area for labels, in pixels.
+    pub margin: f64,
+    /// The colour of plus strand records.
+    pub plus_colour: String,
+    /// The colour of minus strand records.
+    pub minus_colour: String,
+}
+
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        PlotOptions {
+            width: 800.0,
+            height: 800.0,
+            margin: 120.0,
+            plus_colour: "#1f77b4".to_string(),
+            minus_colour: "#d62728".to_string(),
+        }
+    }
+}
+
+
+/// The sequences along one axis, with their offsets.
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Axis {
+    /// Names, lengths and cumulative offsets, longest first.
+    contigs: Vec<(String, u64, u64)>,
+    offsets: HashMap<String, u64>,
+    total: u64,
+}
+
+
+impl Axis {
+    fn new<'a, I: IntoIterator<Item = (&'a str, u64)>>(sequences: I) -> Self {
+        let mut lengths: HashMap<&str, u64> = HashMap::new();
+        for (name, length) in sequences {
+            lengths.insert(name, length);
+        }
+
+        let mut contigs: Vec<(&str, u64)> = lengths.into_iter().collect();
+        contigs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let mut axis = Axis::default();
+        for (name, length) in contigs {
+            axis.contigs.push((name.to_string(), length, axis.total));
+            axis.offsets.insert(name.to_string(), axis.total);
+            axis.total += length;
+        }
+        axis
+    }
+}
+
+
+/// A dot plot of a set of alignments.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DotPlot {
+    targets: Axis,
+    queries: Axis,
+    /// Lines as target and query positions, with the strand.
+    segments: Vec<(u64, u64, u64, u64, Strand)>,
+}
+
+
+impl DotPlot {
+    /// Lay out the records on cumulative target and query axes.
+    pub fn new(records: &[PAF]) -> Self {
+        let targets = Axis::new(records.iter().map(|r| (r.target().name(), r.target().length())));
+        let queries = Axis::new(records.iter().map(|r| (r.query().name(), r.query().length())));
+
+        let segments = records
+            .iter()
+            .map(|r| {
+                let (t, q) = (r.target(), r.query());
+                let t_offset = targets.offsets[t.name()];
+                let q_offset = queries.offsets[q.name()];
+                let (q0, q1) = match r.strand() {
+                    Strand::Minus => (q.end(), q.start()),
+                    _ => (q.start(), q.end()),
+                };
+                (t_offset + t.start(), q_offset + q0, t_offset + t.end(), q_offset + q1, r.strand())
+            })
+            .collect();
+
+        DotPlot { targets, queries, segments }
+    }
+
+    /// Render the plot as an SVG document.
+    pub fn to_svg(&self, options: &PlotOptions) -> String {
+        let (w, h, m) = (options.width, options.height, options.margin);
+        let x_scale = w / self.targets.total.max(1) as f64;
+        let y_scale = h / self.queries.total.max(1) as f64;
+        let x = |pos: u64| m + pos as f64 * x_scale;
+        // The query axis runs upwards from the bottom of the plotting area.
+        let y = |pos: u64| m + h - pos as f64 * y_scale;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"10\">",
+            w + 2.0 * m, h + 2.0 * m, w + 2.0 * m, h + 2.0 * m
+        );
+        let _ = writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>");
+
+        let _ = writeln!(svg, "<g stroke=\"#cccccc\" stroke-width=\"0.5\">");
+        for &(_, _, offset) in self.targets.contigs.iter().skip(1) {
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\"/>",
+                x(offset), m, x(offset), m + h
+            );
+        }
+        for &(_, _, offset) in self.queries.contigs.iter().skip(1) {
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\"/>",
+                m, y(offset), m + w, y(offset)
+            );
+        }
+        let _ = writeln!(svg, "</g>");
+
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>",
+            m, m, w, h
+        );
+
+        for (name, length, offset) in &self.targets.contigs {
+            let mid = x(offset + length / 2);
+            let _ = writeln!(
+                svg,
+                "<text x=\"{:.2}\" y=\"{:.2}\" text-anchor=\"end\" transform=\"rotate(-90 {:.2} {:.2})\">{}</text>",
+                mid, m + h + 6.0, mid, m + h + 6.0, escape(name)
+            );
+        }
+        for (name, length, offset) in &self.queries.contigs {
+            let mid = y(offset + length / 2);
+            let _ = writeln!(
+                svg,
+                "<text x=\"{:.2}\" y=\"{:.2}\" text-anchor=\"end\" dominant-baseline=\"middle\">{}</text>",
+                m - 6.0, mid, escape(name)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.2}\" y=\"{:.2}\" text-anchor=\"middle\">target</text>",
+            m + w / 2.0, h + 2.0 * m - 6.0
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"12\" y=\"{:.2}\" text-anchor=\"middle\" transform=\"rotate(-90 12 {:.2})\">query</text>",
+            m + h / 2.0, m + h / 2.0
+        );
+
+        let _ = writeln!(svg, "<g stroke-width=\"1.5\" stroke-linecap=\"round\">");
+        for &(t0, q0, t1, q1, strand) in &self.segments {
+            let colour = match strand {
+                Strand::Minus => &options.minus_colour,
+                _ => &options.plus_colour,
+            };
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\"/>",
+                x(t0), y(q0), x(t1), y(q1), escape(colour)
+            );
+        }
+        let _ = writeln!(svg, "</g>");
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Write the plot as an SVG document.
+    pub fn write_svg<W: Write>(&self, options: &PlotOptions, mut writer: W) -> Result<(), Error> {
+        writer.write_all(self.to_svg(options).as_bytes()).context(Io)
+    }
+}
+
+
+/// Escape text for use in XML content and attributes.
+fn escape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_dot_plot() {
+        let records = vec![
+            paf("q1\t1000\t0\t500\t+\tchr1\t2000\t0\t500\t500\t500\t60"),
+            paf("q1\t1000\t500\t1000\t-\tchr2\t1000\t0\t500\t500\t500\t60"),
+            paf("q<2>\t500\t0\t500\t+\tchr1\t2000\t1500\t2000\t500\t500\t60"),
+        ];
+        let plot = DotPlot::new(&records);
+        assert_eq!(plot.targets.offsets["chr2"], 2000);
+        assert_eq!(plot.queries.offsets["q<2>"], 1000);
+        assert_eq!(plot.segments[1], (2000, 1000, 2500, 500, Strand::Minus));
+
+        let options = PlotOptions { width: 300.0, height: 150.0, margin: 10.0, ..PlotOptions::default() };
+        let svg = plot.to_svg(&options);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"320\" height=\"170\""));
+        assert!(svg.contains("<line x1=\"210.00\" y1=\"60.00\" x2=\"260.00\" y2=\"110.00\" stroke=\"#d62728\"/>"));
+        assert!(svg.contains(">q&lt;2&gt;</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,12 @@
+//! Re-exports of the commonly used types and traits.
+//!
+//! ```
+//! use paf::prelude::*;
+//! ```
+
+pub use crate::errors::Error;
+pub use crate::paf::{PAF, Locus, Strand};
+pub use crate::record::PafRecord;
+pub use crate::reader::{PafReader, ParseMode};
+pub use crate::writer::PafWriter;
+pub use crate::iter::PafIterExt;
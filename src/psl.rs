@@ -0,0 +1,196 @@
+//! Conversion to BLAT's PSL format.
+
+use std::fmt;
+
+use crate::cigar::CigarKind;
+use crate::paf::{Strand, PAF};
+
+
+/// A single PSL record.
+///
+/// Block query starts are on the strand given by `strand`, so for minus
+/// strand alignments they count from the end of the query, as in BLAT.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PslRecord {
+    /// The number of aligned bases that match.
+    pub matches: u64,
+    /// The number of aligned bases that don't match.
+    pub mismatches: u64,
+    /// The number of matches in repeats, always 0 from PAF.
+    pub rep_matches: u64,
+    /// The number of aligned `N` bases.
+    pub n_count: u64,
+    /// The number of insertions to the target, present in the query.
+    pub q_num_insert: u64,
+    /// The number of bases in insertions to the target.
+    pub q_base_insert: u64,
+    /// The number of deletions and introns, absent in the query.
+    pub t_num_insert: u64,
+    /// The number of bases in deletions and introns.
+    pub t_base_insert: u64,
+    /// The query strand, `+` or `-`.
+    pub strand: char,
+    /// The query sequence name.
+    pub q_name: String,
+    /// The query sequence length.
+    pub q_size: u64,
+    /// The 0-based start of the alignment on the forward query strand.
+    pub q_start: u64,
+    /// The exclusive end of the alignment on the forward query strand.
+    pub q_end: u64,
+    /// The target sequence name.
+    pub t_name: String,
+    /// The target sequence length.
+    pub t_size: u64,
+    /// The 0-based start of the alignment on the target.
+    pub t_start: u64,
+    /// The exclusive end of the alignment on the target.
+    pub t_end: u64,
+    /// The length of each gap-free block.
+    pub block_sizes: Vec<u64>,
+    /// The query start of each block, on the strand given by `strand`.
+    pub q_starts: Vec<u64>,
+    /// The target start of each block.
+    pub t_starts: Vec<u64>,
+}
+
+
+/// Join a list with the trailing comma PSL expects.
+fn psl_list(values: &[u64]) -> String {
+    values.iter().map(|v| format!("{},", v)).collect()
+}
+
+
+impl fmt::Display for PslRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.matches,
+            self.mismatches,
+            self.rep_matches,
+            self.n_count,
+            self.q_num_insert,
+            self.q_base_insert,
+            self.t_num_insert,
+            self.t_base_insert,
+            self.strand,
+            self.q_name,
+            self.q_size,
+            self.q_start,
+            self.q_end,
+            self.t_name,
+            self.t_size,
+            self.t_start,
+            self.t_end,
+            self.block_sizes.len(),
+            psl_list(&self.block_sizes),
+            psl_list(&self.q_starts),
+            psl_list(&self.t_starts),
+        )
+    }
+}
+
+
+impl PAF {
+    /// Convert to a PSL record using the CIGAR in the `cg` tag.
+    ///
+    /// With `=`/`X` operations, matches and mismatches are counted exactly.
+    /// With `M` operations, matches are taken from the number of matches
+    /// column and the remaining aligned bases are counted as mismatches.
+    /// The `nn` tag fills the N count. Unknown strands are written as `+`.
+    /// Returns `None` if there is no valid `cg` tag.
+    pub fn to_psl(&self) -> Option<PslRecord> {
+        let cigar = self.cigar()?;
+        // Checks the CIGAR fits in the coordinates, so the sums below can't
+        // overflow.
+        let blocks = self.blocks()?;
+        let minus = self.strand() == Strand::Minus;
+
+        let mut exact = (0, 0);
+        let mut has_m = false;
+        let (mut q_num_insert, mut q_base_insert) = (0, 0);
+        let (mut t_num_insert, mut t_base_insert) = (0, 0);
+        for op in cigar.ops() {
+            match op.kind() {
+                CigarKind::Equal => exact.0 += op.len(),
+                CigarKind::Diff => exact.1 += op.len(),
+                CigarKind::Match => has_m = true,
+                CigarKind::Insertion => {
+                    q_num_insert += 1;
+                    q_base_insert += op.len();
+                },
+                CigarKind::Deletion | CigarKind::Skip => {
+                    t_num_insert += 1;
+                    t_base_insert += op.len();
+                },
+                _ => {},
+            }
+        }
+
+        let aligned: u64 = blocks.iter().map(|b| b.target_end - b.target_start).sum();
+        let (matches, mismatches) = if has_m {
+            let matches = self.nmatch().min(aligned);
+            (matches, aligned - matches)
+        } else {
+            exact
+        };
+
+        let q_size = self.query().length();
+        let q_starts = blocks
+            .iter()
+            .map(|b| if minus { q_size.saturating_sub(b.query_end) } else { b.query_start })
+            .collect();
+
+        Some(PslRecord {
+            matches,
+            mismatches,
+            rep_matches: 0,
+            n_count: self.tag("nn").and_then(|v| v.as_int()).map_or(0, |n| n.max(0) as u64),
+            q_num_insert,
+            q_base_insert,
+            t_num_insert,
+            t_base_insert,
+            strand: if minus { '-' } else { '+' },
+            q_name: self.query().name().to_string(),
+            q_size,
+            q_start: self.query().start(),
+            q_end: self.query().end(),
+            t_name: self.target().name().to_string(),
+            t_size: self.target().length(),
+            t_start: self.target().start(),
+            t_end: self.target().end(),
+            block_sizes: blocks.iter().map(|b| b.target_end - b.target_start).collect(),
+            q_starts,
+            t_starts: blocks.iter().map(|b| b.target_start).collect(),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_to_psl() {
+        let record = paf("q1\t100\t10\t30\t+\tt1\t1000\t100\t121\t17\t23\t60\tcg:Z:5=1X4=2I5=3D3=");
+        assert_eq!(
+            record.to_psl().unwrap().to_string(),
+            "17\t1\t0\t0\t1\t2\t1\t3\t+\tq1\t100\t10\t30\tt1\t1000\t100\t121\t3\t10,5,3,\t10,22,27,\t100,110,118,"
+        );
+
+        let record = paf("q1\t100\t10\t30\t-\tt1\t1000\t100\t120\t15\t20\t60\tcg:Z:10M5D5M\tnn:i:2");
+        let psl = record.to_psl().unwrap();
+        assert_eq!((psl.matches, psl.mismatches, psl.n_count), (15, 0, 2));
+        assert_eq!(psl.block_sizes, vec![10, 5]);
+        assert_eq!(psl.q_starts, vec![70, 80]);
+        assert_eq!(psl.t_starts, vec![100, 115]);
+
+        assert!(paf("q1\t100\t10\t30\t+\tt1\t1000\t100\t120\t15\t20\t60").to_psl().is_none());
+    }
+}
@@ -0,0 +1,295 @@
+//! Python bindings, built with the `python` feature.
+//!
+//! Build an extension module with maturin, e.g. `maturin develop`, which
+//! reads the features to enable from `pyproject.toml`.
+
+use std::io::BufRead;
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::errors::Error;
+use crate::iter::PafIterExt;
+use crate::paf::PAF;
+use crate::reader::{PafReader, ParseMode};
+use crate::tags::TagValue;
+
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io { .. } | Error::OpenPath { .. } => PyIOError::new_err(error.to_string()),
+            _ => PyValueError::new_err(error.to_string()),
+        }
+    }
+}
+
+
+/// A single PAF record.
+#[pyclass(name = "PafRecord", module = "paf", frozen, from_py_object)]
+#[derive(Clone)]
+pub struct PafRecord {
+    inner: PAF,
+}
+
+
+#[pymethods]
+impl PafRecord {
+    /// Parse a record from a single line.
+    #[staticmethod]
+    fn parse(line: &str) -> PyResult<Self> {
+        Ok(PafRecord { inner: line.parse()? })
+    }
+
+    #[getter]
+    fn query_name(&self) -> &str {
+        self.inner.query().name()
+    }
+
+    #[getter]
+    fn query_length(&self) -> u64 {
+        self.inner.query().length()
+    }
+
+    #[getter]
+    fn query_start(&self) -> u64 {
+        self.inner.query().start()
+    }
+
+    #[getter]
+    fn query_end(&self) -> u64 {
+        self.inner.query().end()
+    }
+
+    #[getter]
+    fn strand(&self) -> char {
+        char::from(u8::from(&self.inner.strand()))
+    }
+
+    #[getter]
+    fn target_name(&self) -> &str {
+        self.inner.target().name()
+    }
+
+    #[getter]
+    fn target_length(&self) -> u64 {
+        self.inner.target().length()
+    }
+
+    #[getter]
+    fn target_start(&self) -> u64 {
+        self.inner.target().start()
+    }
+
+    #[getter]
+    fn target_end(&self) -> u64 {
+        self.inner.target().end()
+    }
+
+    #[getter]
+    fn nmatch(&self) -> u64 {
+        self.inner.nmatch()
+    }
+
+    #[getter]
+    fn alnlen(&self) -> u64 {
+        self.inner.alnlen()
+    }
+
+    #[getter]
+    fn mapq(&self) -> u8 {
+        self.inner.mapq()
+    }
+
+    #[getter]
+    fn identity(&self) -> f64 {
+        self.inner.identity()
+    }
+
+    /// The optional fields, as written in the file.
+    #[getter]
+    fn fields(&self) -> Vec<String> {
+        self.inner.fields().to_vec()
+    }
+
+    /// The value of a SAM-style tag, or `None` if it is absent.
+    ///
+    /// Hex byte arrays are returned as `bytes`, and numeric arrays as lists.
+    fn tag<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let value = match self.inner.tag(name) {
+            None => return Ok(None),
+            Some(TagValue::Char(c)) => c.into_pyobject(py)?.into_any(),
+            Some(TagValue::Int(i)) => i.into_pyobject(py)?.into_any(),
+            Some(TagValue::Float(f)) => f.into_pyobject(py)?.into_any(),
+            Some(TagValue::String(s)) => s.into_pyobject(py)?.into_any(),
+            Some(TagValue::Hex(b)) => PyBytes::new(py, &b).into_any(),
+            Some(TagValue::Array(a)) => match a.to_ints() {
+                Some(ints) => ints.into_pyobject(py)?.into_any(),
+                None => a.to_floats().into_pyobject(py)?.into_any(),
+            },
+        };
+        Ok(Some(value))
+    }
+
+    fn __str__(&self) -> String {
+        self.inner.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PafRecord({}:{}-{} {} {}:{}-{})",
+            self.inner.query().name(),
+            self.inner.query().start(),
+            self.inner.query().end(),
+            self.strand(),
+            self.inner.target().name(),
+            self.inner.target().start(),
+            self.inner.target().end(),
+        )
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+
+/// Iterate over the records of a PAF file, optionally gzip compressed.
+#[pyclass(name = "Reader", module = "paf", unsendable)]
+pub struct Reader {
+    inner: PafReader<Box<dyn BufRead>>,
+}
+
+
+#[pymethods]
+impl Reader {
+    /// Open a file. `lenient` fills missing mandatory columns, and
+    /// `validate` checks that all intervals fit within their sequences.
+    /// Both are off by default, as for `PafReader`.
+    #[new]
+    #[pyo3(signature = (path, lenient = false, validate = false))]
+    fn new(path: &str, lenient: bool, validate: bool) -> PyResult<Self> {
+        let mode = if lenient { ParseMode::Lenient } else { ParseMode::Strict };
+        let inner = PafReader::new(crate::io::open_path(path)?)
+            .with_mode(mode)
+            .with_validation(validate);
+        Ok(Reader { inner })
+    }
+
+    /// The comment lines read so far.
+    fn comments(&self) -> Vec<String> {
+        self.inner.comments().to_vec()
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<PafRecord>> {
+        Ok(self.inner.read_record()?.map(|inner| PafRecord { inner }))
+    }
+}
+
+
+/// Keep the records passing all of the given thresholds.
+///
+/// The secondary ratio is computed from every record of a query, before
+/// low quality or short records are dropped, so that an ambiguous query
+/// is removed even if its secondary alignments would be filtered out.
+#[pyfunction]
+#[pyo3(signature = (records, min_mapq = 0, min_alnlen = 0, max_secondary_ratio = None))]
+fn filter(
+    records: Vec<PafRecord>,
+    min_mapq: u8,
+    min_alnlen: u64,
+    max_secondary_ratio: Option<f64>,
+) -> Vec<PafRecord> {
+    let records = records.into_iter().map(|r| r.inner);
+
+    let filtered: Vec<PAF> = match max_secondary_ratio {
+        Some(ratio) => records
+            .max_secondary_ratio(ratio)
+            .min_mapq(min_mapq)
+            .min_alnlen(min_alnlen)
+            .collect(),
+        None => records.min_mapq(min_mapq).min_alnlen(min_alnlen).collect(),
+    };
+
+    filtered.into_iter().map(|inner| PafRecord { inner }).collect()
+}
+
+
+/// Summary statistics for one target sequence.
+#[pyclass(name = "TargetStats", module = "paf", frozen, get_all)]
+pub struct TargetStats {
+    target: String,
+    records: u64,
+    covered_bases: u64,
+    identity: f64,
+    mapq_counts: [u64; 6],
+}
+
+
+/// Per-target record counts, covered bases, identity, and MAPQ counts.
+#[pyfunction]
+fn target_stats(records: Vec<PafRecord>) -> Vec<TargetStats> {
+    let records: Vec<PAF> = records.into_iter().map(|r| r.inner).collect();
+    crate::stats::target_stats(&records)
+        .into_iter()
+        .map(|s| TargetStats {
+            target: s.target,
+            records: s.records,
+            covered_bases: s.covered_bases,
+            identity: s.identity,
+            mapq_counts: s.mapq_counts,
+        })
+        .collect()
+}
+
+
+#[pymodule]
+fn paf(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PafRecord>()?;
+    m.add_class::<Reader>()?;
+    m.add_class::<TargetStats>()?;
+    m.add_function(wrap_pyfunction!(filter, m)?)?;
+    m.add_function(wrap_pyfunction!(target_stats, m)?)?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(line: &str) -> PafRecord {
+        PafRecord { inner: line.parse().unwrap() }
+    }
+
+    #[test]
+    fn test_filter() {
+        let records = vec![
+            record("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60"),
+            record("q2\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t0"),
+            record("q3\t100\t0\t50\t+\tt1\t1000\t0\t50\t45\t50\t60"),
+        ];
+
+        let kept = filter(records, 10, 80, None);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].query_name(), "q1");
+    }
+
+    #[test]
+    fn test_filter_secondary_before_mapq() {
+        let records = vec![
+            record("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tAS:i:180"),
+            record("q1\t100\t0\t100\t+\tt2\t1000\t0\t100\t89\t100\t0\tAS:i:178"),
+            record("q2\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tAS:i:180"),
+        ];
+
+        let kept = filter(records, 10, 0, Some(0.9));
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].query_name(), "q2");
+    }
+}
@@ -0,0 +1,404 @@
+use std::borrow::Cow;
+use std::io::BufRead;
+
+use nom::combinator::{cut, all_consuming};
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::escape::unescape_name;
+use crate::paf::PAF;
+
+
+/// A snapshot of how far a reader has progressed.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct Progress {
+    /// The number of bytes consumed from the source.
+    pub bytes: u64,
+    /// The number of lines consumed, including blank lines.
+    pub lines: usize,
+    /// The number of records successfully parsed.
+    pub records: u64,
+    /// Whether the end of the input has been reached.
+    pub finished: bool,
+}
+
+
+/// How strictly lines are parsed.
+///
+/// [`PafReader`] defaults to `Strict`. Unlike `str::parse`, which keeps
+/// any extra columns, it rejects extra columns that are not tags unless
+/// `Lenient` is chosen.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash, Default)]
+pub enum ParseMode {
+    /// Require all 12 mandatory columns, and that any further columns are
+    /// SAM-style tags.
+    #[default]
+    Strict,
+    /// Fill missing trailing mandatory columns with defaults (mapq of 255),
+    /// and keep unrecognised extra columns with the optional fields.
+    Lenient,
+}
+
+
+type ProgressCallback = Box<dyn FnMut(Progress) + Send>;
+
+
+/// Read PAF records line-by-line from a buffered source.
+///
+/// Blank lines are skipped. Lines starting with `#` are kept as comments,
+/// see [`PafReader::comments`]. Parse errors report the line number in the
+/// underlying source.
+pub struct PafReader<R> {
+    inner: R,
+    line: String,
+    line_num: usize,
+    bytes: u64,
+    records: u64,
+    finished: bool,
+    mode: ParseMode,
+    trim: bool,
+    validate: bool,
+    unescape: bool,
+    comments: Vec<String>,
+    progress: Option<(u64, ProgressCallback)>,
+}
+
+
+impl<R: BufRead> PafReader<R> {
+    /// Construct a new reader from a buffered source.
+    pub fn new(inner: R) -> Self {
+        PafReader {
+            inner,
+            line: String::new(),
+            line_num: 0,
+            bytes: 0,
+            records: 0,
+            finished: false,
+            mode: ParseMode::default(),
+            trim: false,
+            validate: false,
+            unescape: false,
+            comments: Vec::new(),
+            progress: None,
+        }
+    }
+
+    /// Set how strictly lines are parsed.
+    pub fn with_mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Ignore trailing spaces and tabs at the end of each line.
+    ///
+    /// Both `\n` and `\r\n` line endings are always accepted.
+    pub fn with_trailing_whitespace(mut self, allow: bool) -> Self {
+        self.trim = allow;
+        self
+    }
+
+    /// Check that `start <= end <= length` for the query and target.
+    ///
+    /// Records that break this return `Error::InvalidInterval` with the
+    /// line number.
+    pub fn with_validation(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Decode `%XX` escapes in sequence names, as written by
+    /// [`PafWriter::with_name_escapes`](crate::PafWriter::with_name_escapes).
+    pub fn with_name_escapes(mut self, unescape: bool) -> Self {
+        self.unescape = unescape;
+        self
+    }
+
+    /// Call `callback` after every `every` records, and once at the end.
+    ///
+    /// The callback must be `Send` so that the reader can still be moved
+    /// to another thread.
+    ///
+    /// This suits progress bars, e.g. with `indicatif` the callback can call
+    /// `bar.set_position(p.bytes)` on a bar sized to the file length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `every` is 0.
+    pub fn with_progress<F>(mut self, every: u64, callback: F) -> Self
+    where
+        F: FnMut(Progress) + Send + 'static,
+    {
+        assert!(every > 0, "progress interval must be at least 1");
+        self.progress = Some((every, Box::new(callback)));
+        self
+    }
+
+    /// The progress made so far.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            bytes: self.bytes,
+            lines: self.line_num,
+            records: self.records,
+            finished: self.finished,
+        }
+    }
+
+    /// The number of lines read so far.
+    pub fn line_num(&self) -> usize {
+        self.line_num
+    }
+
+    /// The comment lines read so far, including the leading `#`.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Read the comment lines at the start of the input.
+    ///
+    /// Returns all comments read so far. Comments after the first record
+    /// are collected as records are read.
+    pub fn read_header(&mut self) -> Result<&[String], Error> {
+        while self.inner.fill_buf().context(Io)?.first() == Some(&b'#') {
+            self.line.clear();
+            let nbytes = self.inner.read_line(&mut self.line).context(Io)?;
+            self.bytes += nbytes as u64;
+            self.line_num += 1;
+            let line = self.line.trim_end_matches(&['\r', '\n'][..]).to_string();
+            self.comments.push(line);
+        }
+        Ok(&self.comments)
+    }
+
+    /// Get a reference to the underlying source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consume the reader, returning the underlying source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read the next record, returning `Ok(None)` at the end of input.
+    pub fn read_record(&mut self) -> Result<Option<PAF>, Error> {
+        loop {
+            self.line.clear();
+            let nbytes = self.inner.read_line(&mut self.line).context(Io)?;
+
+            if nbytes == 0 {
+                if !self.finished {
+                    self.finished = true;
+                    self.report();
+                }
+                return Ok(None);
+            }
+
+            self.bytes += nbytes as u64;
+            self.line_num += 1;
+
+            let line = if self.trim {
+                self.line.trim_end()
+            } else {
+                self.line.trim_end_matches(&['\r', '\n'][..])
+            };
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('#') {
+                self.comments.push(line.to_string());
+                continue;
+            }
+
+            let mut record = parse_line(line, self.line_num, self.mode)?;
+            if self.unescape {
+                unescape_names(&mut record);
+            }
+            if self.validate {
+                validate_record(&record, self.line_num)?;
+            }
+            self.records += 1;
+            self.report();
+            return Ok(Some(record));
+        }
+    }
+
+    /// Call the progress callback if one is due.
+    fn report(&mut self) {
+        let progress = self.progress();
+
+        if let Some((every, callback)) = self.progress.as_mut() {
+            if progress.finished || progress.records % *every == 0 {
+                callback(progress);
+            }
+        }
+    }
+}
+
+
+impl<R: BufRead> Iterator for PafReader<R> {
+    type Item = Result<PAF, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+
+/// Parse a single line, attributing any error to `line_num`.
+pub(crate) fn parse_line(line: &str, line_num: usize, mode: ParseMode) -> Result<PAF, Error> {
+    let parser = match mode {
+        ParseMode::Strict => crate::complete::strict_paf_str,
+        ParseMode::Lenient => crate::complete::lenient_paf_str,
+    };
+
+    let (_, paf) = all_consuming(cut(parser))(line)
+        .map_err(|e| {
+            match e {
+                nom::Err::Error(ei) | nom::Err::Failure(ei) => {
+                    crate::errors::convert_error_str(line, ei, Some(line_num))
+                },
+                _ => unreachable!(),
+            }
+        })?;
+
+    Ok(paf)
+}
+
+
+/// Decode escapes in the query and target names in place.
+fn unescape_names(record: &mut PAF) {
+    if let Cow::Owned(name) = unescape_name(record.query().name()) {
+        record.query_mut().set_name(name);
+    }
+    if let Cow::Owned(name) = unescape_name(record.target().name()) {
+        record.target_mut().set_name(name);
+    }
+}
+
+
+/// Check the intervals of both loci, attributing any error to `line_num`.
+fn validate_record(record: &PAF, line_num: usize) -> Result<(), Error> {
+    record.query().validate()
+        .and_then(|_| record.target().validate())
+        .map_err(|e| match e {
+            Error::InvalidInterval { name, length, start, end, .. } => {
+                Error::InvalidInterval { line_num: Some(line_num), name, length, start, end }
+            },
+            e => e,
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_records() {
+        let input = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P\n\
+                     \n\
+                     q2\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t0\n";
+        let records: Vec<PAF> = PafReader::new(input.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].query().name(), "q1");
+        assert_eq!(records[0].fields(), &["tp:A:P".to_string()][..]);
+        assert_eq!(records[1].target().start(), 5);
+    }
+
+    #[test]
+    fn test_validation() {
+        let input = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\n\
+                     q2\t10\t0\t10\t-\tt1\t20\t15\t25\t9\t10\t0\n";
+
+        assert!(PafReader::new(input.as_bytes()).all(|r| r.is_ok()));
+
+        let results: Vec<Result<PAF, Error>> = PafReader::new(input.as_bytes())
+            .with_validation(true)
+            .collect();
+        assert!(results[0].is_ok());
+        assert_eq!(results[1].as_ref().unwrap_err().line_number(), Some(2));
+    }
+
+    #[test]
+    fn test_parse_modes() {
+        let input = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\n\
+                     q2\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t0\textra\ttp:A:P\n";
+
+        let strict: Vec<Result<PAF, Error>> = PafReader::new(input.as_bytes()).collect();
+        assert!(strict.iter().all(|r| r.is_err()));
+
+        let error = strict[1].as_ref().unwrap_err();
+        let column = error.column().unwrap();
+        assert_eq!(&error.line().unwrap()[column..column + 5], "extra");
+
+        let lenient: Vec<PAF> = PafReader::new(input.as_bytes())
+            .with_mode(ParseMode::Lenient)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lenient[0].mapq(), 255);
+        assert_eq!(lenient[1].fields(), &["extra".to_string(), "tp:A:P".to_string()][..]);
+        assert_eq!(lenient[1].extra_columns().collect::<Vec<_>>(), vec!["extra"]);
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let input = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\r\n\
+                     q2\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t0\ttp:A:P \t\r\n";
+
+        let records: Vec<Result<PAF, Error>> = PafReader::new(input.as_bytes()).collect();
+        assert!(records[0].is_ok());
+        assert!(records[1].is_err());
+
+        let records: Vec<PAF> = PafReader::new(input.as_bytes())
+            .with_trailing_whitespace(true)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records[1].fields(), &["tp:A:P".to_string()][..]);
+    }
+
+    #[test]
+    fn test_progress() {
+        use std::sync::{Arc, Mutex};
+
+        let line = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\n";
+        let input = line.repeat(5);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let mut reader = PafReader::new(input.as_bytes())
+            .with_progress(2, move |p| sink.lock().unwrap().push(p));
+
+        // The reader can be moved to a worker thread.
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&reader);
+
+        assert_eq!(reader.by_ref().count(), 5);
+        assert!(reader.next().is_none());
+        assert!(reader.progress().finished);
+
+        let seen = seen.lock().unwrap();
+        let records: Vec<u64> = seen.iter().map(|p| p.records).collect();
+        assert_eq!(records, vec![2, 4, 5]);
+        assert_eq!(seen[0].bytes, 2 * line.len() as u64);
+        assert!(seen[2].finished);
+        assert_eq!(seen[2].bytes, input.len() as u64);
+    }
+
+    #[test]
+    fn test_read_error_line_num() {
+        let input = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\n\
+                     q2\t10\t0\t10\t?\tt1\t20\t5\t15\t9\t10\t0\n";
+        let mut reader = PafReader::new(input.as_bytes());
+
+        assert!(reader.next().unwrap().is_ok());
+        match reader.next().unwrap() {
+            Err(Error::Parse { line_num, .. }) => assert_eq!(line_num, 2),
+            e => panic!("Expected a parse error, got {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Flag candidate inversions and translocations between two assemblies.
+//!
+//! This is a simple heuristic in the spirit of SyRI. For each query sequence
+//! the target carrying most of its aligned bases is taken as its homologue,
+//! and the dominant strand on that target as its orientation. Alignments to
+//! other targets are reported as translocations, alignments on the other
+//! strand as inversions, and alignments breaking the collinear order along
+//! the homologue as transpositions. Alignments with an unknown strand are
+//! never flagged as inversions.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::paf::{Locus, Strand, PAF};
+
+
+/// The kind of rearrangement suggested by an alignment.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub enum RearrangementKind {
+    /// Aligned on the opposite strand to the rest of the query.
+    Inversion,
+    /// Aligned to a different target sequence than the rest of the query.
+    Translocation,
+    /// Aligned out of order along the same target sequence.
+    Transposition,
+}
+
+
+/// A candidate rearrangement with its coordinates on both genomes.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Rearrangement {
+    /// How the alignment differs from the rest of its query.
+    pub kind: RearrangementKind,
+    /// The aligned interval of the rearranged alignment on the query.
+    pub query: Locus,
+    /// The aligned interval of the rearranged alignment on the target.
+    pub target: Locus,
+    /// The strand of the rearranged alignment.
+    pub strand: Strand,
+}
+
+
+/// Find candidate rearrangements between the query and target assemblies.
+///
+/// Alignments shorter than `min_alnlen` are ignored entirely. Results are
+/// ordered by query name, then by query start.
+pub fn find_rearrangements(records: &[PAF], min_alnlen: u64) -> Vec<Rearrangement> {
+    let mut by_query: BTreeMap<&str, Vec<&PAF>> = BTreeMap::new();
+    for record in records.iter().filter(|r| r.alnlen() >= min_alnlen) {
+        by_query.entry(record.query().name()).or_default().push(record);
+    }
+
+    let mut output = Vec::new();
+    for (_, mut group) in by_query {
+        group.sort_by_key(|r| (r.query().start(), r.query().end()));
+        flag_query(&group, &mut output);
+    }
+
+    output
+}
+
+
+/// Flag the alignments of a single query sequence, sorted by query start.
+fn flag_query(group: &[&PAF], output: &mut Vec<Rearrangement>) {
+    let mut target_bases: HashMap<&str, u64> = HashMap::new();
+    for record in group {
+        *target_bases.entry(record.target().name()).or_insert(0) += record.alnlen();
+    }
+
+    // Ties are broken by name so that the result is deterministic.
+    let homologue = match target_bases
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+    {
+        Some((name, _)) => name,
+        None => return,
+    };
+
+    let (plus, minus) = group
+        .iter()
+        .filter(|r| r.target().name() == homologue)
+        .fold((0, 0), |(p, m), r| match r.strand() {
+            Strand::Plus => (p + r.alnlen(), m),
+            Strand::Minus => (p, m + r.alnlen()),
+            Strand::Unknown => (p, m),
+        });
+    let orientation = if plus >= minus { Strand::Plus } else { Strand::Minus };
+
+    // Along the collinear blocks, target starts should increase with the
+    // query for plus orientation and decrease for minus orientation.
+    let collinear: Vec<&PAF> = group
+        .iter()
+        .filter(|r| r.target().name() == homologue && r.strand() == orientation)
+        .copied()
+        .collect();
+    let keys: Vec<i128> = collinear
+        .iter()
+        .map(|r| match orientation {
+            Strand::Minus => -i128::from(r.target().end()),
+            _ => i128::from(r.target().start()),
+        })
+        .collect();
+    let chain = longest_increasing(&keys);
+
+    let mut in_chain = vec![false; collinear.len()];
+    for i in chain {
+        in_chain[i] = true;
+    }
+
+    let mut collinear_idx = 0;
+    for record in group {
+        let kind = if record.target().name() != homologue {
+            Some(RearrangementKind::Translocation)
+        } else if record.strand() == Strand::Unknown {
+            None
+        } else if record.strand() != orientation {
+            Some(RearrangementKind::Inversion)
+        } else {
+            let keep = in_chain[collinear_idx];
+            collinear_idx += 1;
+            if keep { None } else { Some(RearrangementKind::Transposition) }
+        };
+
+        if let Some(kind) = kind {
+            output.push(Rearrangement {
+                kind,
+                query: record.query().clone(),
+                target: record.target().clone(),
+                strand: record.strand(),
+            });
+        }
+    }
+}
+
+
+/// Indices of a longest non-decreasing subsequence of `keys`.
+fn longest_increasing(keys: &[i128]) -> Vec<usize> {
+    // tails[k] holds the index of the smallest tail of a subsequence of
+    // length k + 1.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = vec![None; keys.len()];
+
+    for (i, key) in keys.iter().enumerate() {
+        let pos = tails.partition_point(|&t| keys[t] <= *key);
+        if pos > 0 {
+            prev[i] = Some(tails[pos - 1]);
+        }
+
+        if pos == tails.len() {
+            tails.push(i);
+        } else {
+            tails[pos] = i;
+        }
+    }
+
+    let mut output = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        output.push(i);
+        current = prev[i];
+    }
+
+    output.reverse();
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        assert_eq!(longest_increasing(&[1, 5, 2, 3, 0, 4]), vec![0, 2, 3, 5]);
+        assert_eq!(longest_increasing(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_find_rearrangements() {
+        let records = vec![
+            paf("q1\t1000\t0\t100\t+\tt1\t1000\t0\t100\t100\t100\t60"),
+            paf("q1\t1000\t100\t200\t-\tt1\t1000\t100\t200\t100\t100\t60"),
+            paf("q1\t1000\t200\t300\t+\tt1\t1000\t600\t700\t100\t100\t60"),
+            paf("q1\t1000\t300\t400\t+\tt1\t1000\t300\t400\t100\t100\t60"),
+            paf("q1\t1000\t400\t500\t+\tt1\t1000\t400\t500\t100\t100\t60"),
+            paf("q1\t1000\t500\t550\t+\tt2\t1000\t0\t50\t50\t50\t60"),
+            paf("q1\t1000\t550\t560\t+\tt3\t1000\t0\t10\t10\t10\t60"),
+        ];
+
+        let found: Vec<(RearrangementKind, u64)> = find_rearrangements(&records, 20)
+            .into_iter()
+            .map(|r| (r.kind, r.query.start()))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (RearrangementKind::Inversion, 100),
+                (RearrangementKind::Transposition, 200),
+                (RearrangementKind::Translocation, 500),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_minus_orientation() {
+        let records = vec![
+            paf("q1\t1000\t0\t100\t-\tt1\t1000\t900\t1000\t100\t100\t60"),
+            paf("q1\t1000\t100\t200\t-\tt1\t1000\t800\t900\t100\t100\t60"),
+            paf("q1\t1000\t200\t300\t-\tt1\t1000\t700\t800\t100\t100\t60"),
+        ];
+
+        assert!(find_rearrangements(&records, 0).is_empty());
+    }
+}
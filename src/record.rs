@@ -0,0 +1,239 @@
+//! A trait over the different representations of a record.
+
+use crate::paf::{Strand, PAF};
+use crate::tags::{is_tag, Tag, TagValue};
+
+
+/// The accessors shared by every representation of a PAF record.
+///
+/// Code written against this trait works with [`PAF`], with
+/// [`MinimalPaf`](crate::minimal::MinimalPaf), and with references to
+/// either. Types without optional fields use the default
+/// [`PafRecord::optional_fields`], so they have no tags.
+pub trait PafRecord {
+    /// The query sequence name.
+    fn query_name(&self) -> &str;
+
+    /// The query sequence length.
+    fn query_length(&self) -> u64;
+
+    /// The 0-based start of the aligned query region.
+    fn query_start(&self) -> u64;
+
+    /// The 0-based, exclusive end of the aligned query region.
+    fn query_end(&self) -> u64;
+
+    /// The relative strand of the query and target.
+    fn strand(&self) -> Strand;
+
+    /// The target sequence name.
+    fn target_name(&self) -> &str;
+
+    /// The target sequence length.
+    fn target_length(&self) -> u64;
+
+    /// The 0-based start of the aligned target region.
+    fn target_start(&self) -> u64;
+
+    /// The 0-based, exclusive end of the aligned target region.
+    fn target_end(&self) -> u64;
+
+    /// The number of matching bases in the alignment.
+    fn nmatch(&self) -> u64;
+
+    /// The number of bases in the alignment, including gaps.
+    fn alnlen(&self) -> u64;
+
+    /// The mapping quality (255 for missing).
+    fn mapq(&self) -> u8;
+
+    /// The optional fields joined by tabs, as written in a PAF line.
+    fn optional_fields(&self) -> &str {
+        ""
+    }
+
+    /// Get the value of the first tag with `name`.
+    ///
+    /// Returns `None` if the tag is absent or its value is malformed.
+    fn tag(&self, name: &str) -> Option<TagValue> {
+        self.optional_fields()
+            .split('\t')
+            .find(|f| is_tag(f, name))
+            .and_then(|f| f.parse::<Tag>().ok())
+            .map(Tag::into_value)
+    }
+
+    /// The fraction of alignment columns that are matches.
+    ///
+    /// Returns 0 if the alignment length is 0.
+    fn identity(&self) -> f64 {
+        if self.alnlen() == 0 {
+            0.0
+        } else {
+            self.nmatch() as f64 / self.alnlen() as f64
+        }
+    }
+
+    /// Whether the record is a primary alignment, see [`PAF::is_primary`].
+    fn is_primary(&self) -> bool {
+        matches!(self.tag("tp").and_then(|v| v.as_char()), None | Some('P') | Some('I'))
+    }
+}
+
+
+impl PafRecord for PAF {
+    fn query_name(&self) -> &str {
+        self.query().name()
+    }
+
+    fn query_length(&self) -> u64 {
+        self.query().length()
+    }
+
+    fn query_start(&self) -> u64 {
+        self.query().start()
+    }
+
+    fn query_end(&self) -> u64 {
+        self.query().end()
+    }
+
+    fn strand(&self) -> Strand {
+        PAF::strand(self)
+    }
+
+    fn target_name(&self) -> &str {
+        self.target().name()
+    }
+
+    fn target_length(&self) -> u64 {
+        self.target().length()
+    }
+
+    fn target_start(&self) -> u64 {
+        self.target().start()
+    }
+
+    fn target_end(&self) -> u64 {
+        self.target().end()
+    }
+
+    fn nmatch(&self) -> u64 {
+        PAF::nmatch(self)
+    }
+
+    fn alnlen(&self) -> u64 {
+        PAF::alnlen(self)
+    }
+
+    fn mapq(&self) -> u8 {
+        PAF::mapq(self)
+    }
+
+    fn optional_fields(&self) -> &str {
+        self.fields().as_str()
+    }
+
+    fn tag(&self, name: &str) -> Option<TagValue> {
+        PAF::tag(self, name)
+    }
+
+    fn identity(&self) -> f64 {
+        PAF::identity(self)
+    }
+
+    fn is_primary(&self) -> bool {
+        PAF::is_primary(self)
+    }
+}
+
+
+impl<R: PafRecord + ?Sized> PafRecord for &R {
+    fn query_name(&self) -> &str {
+        (**self).query_name()
+    }
+
+    fn query_length(&self) -> u64 {
+        (**self).query_length()
+    }
+
+    fn query_start(&self) -> u64 {
+        (**self).query_start()
+    }
+
+    fn query_end(&self) -> u64 {
+        (**self).query_end()
+    }
+
+    fn strand(&self) -> Strand {
+        (**self).strand()
+    }
+
+    fn target_name(&self) -> &str {
+        (**self).target_name()
+    }
+
+    fn target_length(&self) -> u64 {
+        (**self).target_length()
+    }
+
+    fn target_start(&self) -> u64 {
+        (**self).target_start()
+    }
+
+    fn target_end(&self) -> u64 {
+        (**self).target_end()
+    }
+
+    fn nmatch(&self) -> u64 {
+        (**self).nmatch()
+    }
+
+    fn alnlen(&self) -> u64 {
+        (**self).alnlen()
+    }
+
+    fn mapq(&self) -> u8 {
+        (**self).mapq()
+    }
+
+    fn optional_fields(&self) -> &str {
+        (**self).optional_fields()
+    }
+
+    fn tag(&self, name: &str) -> Option<TagValue> {
+        (**self).tag(name)
+    }
+}
+
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::minimal::Interner;
+    use crate::writer::PafWriter;
+
+    fn summary<R: PafRecord>(record: &R) -> (String, u64, bool, Option<i64>) {
+        (
+            record.target_name().to_string(),
+            record.target_end(),
+            record.is_primary(),
+            record.tag("NM").and_then(|v| v.as_int()),
+        )
+    }
+
+    #[test]
+    fn test_paf_record() {
+        let record: PAF = "q1\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t60\ttp:A:S\tNM:i:1".parse().unwrap();
+        let minimal = record.minimize(&mut Interner::new());
+
+        assert_eq!(summary(&record), ("t1".to_string(), 15, false, Some(1)));
+        assert_eq!(summary(&&record), summary(&record));
+        assert_eq!(summary(&minimal), ("t1".to_string(), 15, true, None));
+        assert_eq!(PafRecord::identity(&minimal), 0.9);
+
+        let mut writer = PafWriter::new(Vec::new());
+        writer.write_record(&minimal).unwrap();
+        assert_eq!(writer.into_inner(), b"q1\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t60\n");
+    }
+}
@@ -0,0 +1,152 @@
+//! Rewriting of sequence names through an alias mapping.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::paf::{Locus, PAF};
+
+
+/// What to do with names that are not in the mapping.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum MissingName {
+    /// Keep the original name.
+    #[default]
+    PassThrough,
+    /// Return `Error::MissingName`.
+    Fail,
+}
+
+
+/// Which columns to rename.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum RenameColumns {
+    /// Rename only query names.
+    Query,
+    /// Rename only target names.
+    Target,
+    /// Rename both query and target names.
+    #[default]
+    Both,
+}
+
+
+/// Rename query and target sequences, e.g. from `chr1` to `1`.
+#[derive(Debug, Clone)]
+pub struct Renamer {
+    map: HashMap<String, String>,
+    missing: MissingName,
+    columns: RenameColumns,
+}
+
+
+impl Renamer {
+    /// Construct a renamer from a map of old names to new names.
+    ///
+    /// By default both columns are renamed and missing names are kept.
+    pub fn new(map: HashMap<String, String>) -> Self {
+        Renamer { map, missing: MissingName::default(), columns: RenameColumns::default() }
+    }
+
+    /// Read the mapping from two tab separated columns, old then new.
+    ///
+    /// Blank lines and lines starting with `#` are skipped. Any columns
+    /// after the second are ignored.
+    pub fn from_tsv<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut map = HashMap::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.context(Io)?;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut columns = line.split('\t');
+            match (columns.next(), columns.next()) {
+                (Some(old), Some(new)) if !old.is_empty() && !new.is_empty() => {
+                    map.insert(old.to_string(), new.to_string());
+                },
+                _ => return Err(Error::ParseNameMap {
+                    line_num: i + 1,
+                    message: "expected two tab separated names".to_string(),
+                }),
+            }
+        }
+        Ok(Renamer::new(map))
+    }
+
+    /// Set what to do with names that are not in the mapping.
+    pub fn with_missing(mut self, missing: MissingName) -> Self {
+        self.missing = missing;
+        self
+    }
+
+    /// Set which columns are renamed.
+    pub fn with_columns(mut self, columns: RenameColumns) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Get the new name for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(name).map(|s| s.as_str())
+    }
+
+    /// Rename the query and/or target of a record.
+    pub fn rename(&self, mut record: PAF) -> Result<PAF, Error> {
+        if self.columns != RenameColumns::Target {
+            self.rename_locus(record.query_mut())?;
+        }
+        if self.columns != RenameColumns::Query {
+            self.rename_locus(record.target_mut())?;
+        }
+        Ok(record)
+    }
+
+    fn rename_locus(&self, locus: &mut Locus) -> Result<(), Error> {
+        match (self.map.get(locus.name()), self.missing) {
+            (Some(new), _) => locus.set_name(new.clone()),
+            (None, MissingName::PassThrough) => {},
+            (None, MissingName::Fail) => {
+                return Err(Error::MissingName { name: locus.name().to_string() });
+            },
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rename() {
+        let renamer = Renamer::from_tsv("# old\tnew\nchr1\t1\nctg1\tcontig_1\textra\n".as_bytes()).unwrap();
+        let record = paf("ctg1\t10\t0\t10\t+\tchr1\t20\t0\t10\t10\t10\t60");
+
+        let renamed = renamer.rename(record.clone()).unwrap();
+        assert_eq!(renamed.query().name(), "contig_1");
+        assert_eq!(renamed.target().name(), "1");
+
+        let renamer = renamer.with_columns(RenameColumns::Target);
+        assert_eq!(renamer.rename(record.clone()).unwrap().query().name(), "ctg1");
+
+        let renamer = Renamer::new(HashMap::new()).with_missing(MissingName::Fail);
+        match renamer.rename(record) {
+            Err(Error::MissingName { name }) => assert_eq!(name, "ctg1"),
+            e => panic!("Expected a missing name error, got {:?}", e),
+        }
+
+        match Renamer::from_tsv("chr1\n".as_bytes()) {
+            Err(e) => assert_eq!(e.line_number(), Some(1)),
+            Ok(_) => panic!("Expected a parse error."),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Fixing common inconsistencies in records written by buggy tools.
+
+use std::fmt;
+
+use crate::cigar::CigarKind;
+use crate::paf::{Locus, PAF};
+
+
+/// A change made by [`PAF::repair`].
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Repair {
+    /// The query start and end were swapped, as the end was before the start.
+    SwappedQuery,
+    /// The target start and end were swapped, as the end was before the start.
+    SwappedTarget,
+    /// The alignment length was replaced with the length spanned by the CIGAR.
+    AlnLen { from: u64, to: u64 },
+    /// The number of matches was capped at the alignment length.
+    NMatch { from: u64, to: u64 },
+}
+
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Repair::SwappedQuery => write!(f, "Swapped the query start and end."),
+            Repair::SwappedTarget => write!(f, "Swapped the target start and end."),
+            Repair::AlnLen { from, to } => {
+                write!(f, "Changed the alignment length from {} to {} to match the CIGAR.", from, to)
+            },
+            Repair::NMatch { from, to } => {
+                write!(f, "Changed the number of matches from {} to {}, the alignment length.", from, to)
+            },
+        }
+    }
+}
+
+
+impl PAF {
+    /// Fix common inconsistencies in the mandatory columns.
+    ///
+    /// The rules are applied in order:
+    ///
+    /// 1. A query or target end before its start is swapped with the start.
+    /// 2. If there is a valid `cg` tag, the alignment length is set to the
+    ///    number of `M`, `=`, `X`, `I` and `D` bases it spans.
+    /// 3. A number of matches greater than the alignment length is lowered to
+    ///    the alignment length.
+    ///
+    /// Returns the changes made, in order. An empty result means the record
+    /// was left as it was. Ends past the sequence length are not repaired,
+    /// as there is no way to tell whether the end or the length is wrong.
+    pub fn repair(&mut self) -> Vec<Repair> {
+        let mut repairs = Vec::new();
+
+        let query = swap_if_reversed(self.query(), Repair::SwappedQuery, &mut repairs);
+        let target = swap_if_reversed(self.target(), Repair::SwappedTarget, &mut repairs);
+
+        let mut alnlen = self.alnlen();
+        if let Some(cigar) = self.cigar() {
+            let spanned = cigar.ops()
+                .iter()
+                .filter(|o| matches!(
+                    o.kind(),
+                    CigarKind::Match | CigarKind::Equal | CigarKind::Diff
+                        | CigarKind::Insertion | CigarKind::Deletion
+                ))
+                .map(|o| o.len())
+                .sum();
+            if spanned != alnlen {
+                repairs.push(Repair::AlnLen { from: alnlen, to: spanned });
+                alnlen = spanned;
+            }
+        }
+
+        let mut nmatch = self.nmatch();
+        if nmatch > alnlen {
+            repairs.push(Repair::NMatch { from: nmatch, to: alnlen });
+            nmatch = alnlen;
+        }
+
+        if !repairs.is_empty() {
+            *self = PAF::new(
+                query.unwrap_or_else(|| self.query().clone()),
+                self.strand(),
+                target.unwrap_or_else(|| self.target().clone()),
+                nmatch,
+                alnlen,
+                self.mapq(),
+                self.fields().clone(),
+            );
+        }
+        repairs
+    }
+}
+
+
+/// A copy of the locus with start and end swapped, if they are reversed.
+fn swap_if_reversed(locus: &Locus, repair: Repair, repairs: &mut Vec<Repair>) -> Option<Locus> {
+    if locus.end() >= locus.start() {
+        return None;
+    }
+
+    repairs.push(repair);
+    Some(Locus::new(locus.name().to_string(), locus.length(), locus.end(), locus.start()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_repair() {
+        let mut record = paf("q1\t100\t60\t10\t+\tt1\t200\t0\t50\t90\t40\t60\tcg:Z:30M5I5M10D10M");
+        let repairs = record.repair();
+
+        assert_eq!(repairs, vec![
+            Repair::SwappedQuery,
+            Repair::AlnLen { from: 40, to: 60 },
+            Repair::NMatch { from: 90, to: 60 },
+        ]);
+        assert_eq!(record.to_string(), "q1\t100\t10\t60\t+\tt1\t200\t0\t50\t60\t60\t60\tcg:Z:30M5I5M10D10M");
+        assert!(record.repair().is_empty());
+    }
+}
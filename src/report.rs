@@ -0,0 +1,273 @@
+//! A summary report comparing two assemblies from their alignments.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::export::json_string;
+use crate::paf::{Strand, PAF};
+use crate::rearrange::{find_rearrangements, RearrangementKind};
+use crate::set::covered_intervals;
+use crate::stats::{QueryStats, QuerySummary};
+use crate::sv::{find_svs, SvKind};
+
+
+/// Thresholds used when building a [`Report`].
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct ReportOptions {
+    /// The smallest insertion or deletion counted, in bases.
+    pub min_sv_size: u64,
+    /// Indels closer than this on the target are counted once.
+    pub sv_max_distance: u64,
+    /// Alignments shorter than this are ignored when looking for
+    /// translocations and inversions.
+    pub min_rearrangement_len: u64,
+}
+
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        ReportOptions {
+            min_sv_size: 50,
+            sv_max_distance: 100,
+            min_rearrangement_len: 10_000,
+        }
+    }
+}
+
+
+/// One alignment as a line on a dot plot of a target sequence.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DotSegment {
+    /// The query sequence name.
+    pub query: String,
+    /// The 0-based start of the alignment on the query, in bases.
+    pub query_start: u64,
+    /// The exclusive end of the alignment on the query, in bases.
+    pub query_end: u64,
+    /// The 0-based start of the alignment on the target, in bases.
+    pub target_start: u64,
+    /// The exclusive end of the alignment on the target, in bases.
+    pub target_end: u64,
+    /// The strand of the alignment.
+    pub strand: Strand,
+}
+
+
+/// The summary returned by [`compare_assemblies`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Report {
+    /// Query sequences, bases and primary aligned bases.
+    pub queries: QuerySummary,
+    /// The total length of the target sequences with alignments.
+    pub target_bases: u64,
+    /// Target bases covered by primary alignments.
+    pub covered_target_bases: u64,
+    /// The number of insertions of at least `min_sv_size` bases, 50 by
+    /// default, read from the `cg` or `cs` tags. Insertions within
+    /// `sv_max_distance` target bases of each other, 100 by default, count
+    /// once.
+    pub large_insertions: u64,
+    /// The number of deletions of at least `min_sv_size` bases, counted
+    /// the same way.
+    pub large_deletions: u64,
+    /// The number of primary alignments of at least
+    /// `min_rearrangement_len` bases, 10 kb by default, to a different
+    /// target than the rest of their query.
+    pub translocations: u64,
+    /// The number of primary alignments of at least
+    /// `min_rearrangement_len` bases on the opposite strand to the rest of
+    /// their query.
+    pub inversions: u64,
+    /// Primary alignments by target name, for dot plots.
+    pub segments: BTreeMap<String, Vec<DotSegment>>,
+}
+
+
+impl Report {
+    /// The fraction of query bases covered by primary alignments.
+    pub fn query_aligned_fraction(&self) -> f64 {
+        ratio(self.queries.aligned_bases, self.queries.bases)
+    }
+
+    /// The fraction of target bases covered by primary alignments.
+    pub fn target_aligned_fraction(&self) -> f64 {
+        ratio(self.covered_target_bases, self.target_bases)
+    }
+
+    /// The report as a single JSON object.
+    pub fn to_json(&self) -> String {
+        let segments: Vec<String> = self.segments
+            .iter()
+            .map(|(target, segments)| {
+                let rows: Vec<String> = segments
+                    .iter()
+                    .map(|s| format!(
+                        "{{\"query\":{},\"query_start\":{},\"query_end\":{},\
+                         \"target_start\":{},\"target_end\":{},\"strand\":\"{}\"}}",
+                        json_string(&s.query), s.query_start, s.query_end,
+                        s.target_start, s.target_end, s.strand
+                    ))
+                    .collect();
+                format!("{}:[{}]", json_string(target), rows.join(","))
+            })
+            .collect();
+
+        format!(
+            "{{\"queries\":{},\"split_queries\":{},\"query_bases\":{},\"aligned_query_bases\":{},\
+             \"query_aligned_fraction\":{},\"target_bases\":{},\"covered_target_bases\":{},\
+             \"target_aligned_fraction\":{},\"large_insertions\":{},\"large_deletions\":{},\
+             \"translocations\":{},\"inversions\":{},\"segments\":{{{}}}}}",
+            self.queries.queries,
+            self.queries.split_queries,
+            self.queries.bases,
+            self.queries.aligned_bases,
+            self.query_aligned_fraction(),
+            self.target_bases,
+            self.covered_target_bases,
+            self.target_aligned_fraction(),
+            self.large_insertions,
+            self.large_deletions,
+            self.translocations,
+            self.inversions,
+            segments.join(",")
+        )
+    }
+}
+
+
+/// Human readable text, without the dot plot segments.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Query sequences:        {}", self.queries.queries)?;
+        writeln!(f, "Split query sequences:  {}", self.queries.split_queries)?;
+        writeln!(
+            f,
+            "Query bases aligned:    {} of {} ({:.2}%)",
+            self.queries.aligned_bases,
+            self.queries.bases,
+            100.0 * self.query_aligned_fraction()
+        )?;
+        writeln!(
+            f,
+            "Target bases covered:   {} of {} ({:.2}%)",
+            self.covered_target_bases,
+            self.target_bases,
+            100.0 * self.target_aligned_fraction()
+        )?;
+        writeln!(f, "Large insertions:       {}", self.large_insertions)?;
+        writeln!(f, "Large deletions:        {}", self.large_deletions)?;
+        writeln!(f, "Translocations:         {}", self.translocations)?;
+        writeln!(f, "Inversions:             {}", self.inversions)
+    }
+}
+
+
+/// Summarise the alignments of one assembly, the query, to another.
+pub fn compare_assemblies(records: &[PAF]) -> Report {
+    compare_assemblies_with(records, &ReportOptions::default())
+}
+
+
+/// Summarise the alignments of one assembly to another, with custom
+/// thresholds.
+pub fn compare_assemblies_with(records: &[PAF], options: &ReportOptions) -> Report {
+    let primary: Vec<PAF> = records.iter().filter(|r| r.is_primary()).cloned().collect();
+
+    let mut by_query: BTreeMap<&str, Vec<&PAF>> = BTreeMap::new();
+    for record in records {
+        by_query.entry(record.query().name()).or_default().push(record);
+    }
+    let mut queries = QuerySummary::new();
+    for group in by_query.values() {
+        if let Some(stats) = QueryStats::from_group(group) {
+            queries.add(&stats);
+        }
+    }
+
+    let mut target_lengths: BTreeMap<&str, u64> = BTreeMap::new();
+    for record in records {
+        target_lengths.insert(record.target().name(), record.target().length());
+    }
+
+    let covered_target_bases = covered_intervals(&primary)
+        .values()
+        .flat_map(|spans| spans.iter().map(|(s, e)| e - s))
+        .sum();
+
+    let svs = find_svs(records, options.min_sv_size, options.sv_max_distance);
+    let rearrangements = find_rearrangements(&primary, options.min_rearrangement_len);
+
+    let mut segments: BTreeMap<String, Vec<DotSegment>> = BTreeMap::new();
+    for record in &primary {
+        segments.entry(record.target().name().to_string()).or_default().push(DotSegment {
+            query: record.query().name().to_string(),
+            query_start: record.query().start(),
+            query_end: record.query().end(),
+            target_start: record.target().start(),
+            target_end: record.target().end(),
+            strand: record.strand(),
+        });
+    }
+    for rows in segments.values_mut() {
+        rows.sort_by_key(|s| (s.target_start, s.target_end));
+    }
+
+    Report {
+        queries,
+        target_bases: target_lengths.values().sum(),
+        covered_target_bases,
+        large_insertions: svs.iter().filter(|s| s.kind == SvKind::Insertion).count() as u64,
+        large_deletions: svs.iter().filter(|s| s.kind == SvKind::Deletion).count() as u64,
+        translocations: rearrangements
+            .iter()
+            .filter(|r| r.kind == RearrangementKind::Translocation)
+            .count() as u64,
+        inversions: rearrangements
+            .iter()
+            .filter(|r| r.kind == RearrangementKind::Inversion)
+            .count() as u64,
+        segments,
+    }
+}
+
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_compare_assemblies() {
+        let records = vec![
+            paf("chr1\t30000\t0\t20000\t+\tchr1\t40000\t0\t20100\t19000\t20100\t60\tcg:Z:10000M100D10000M"),
+            paf("chr1\t30000\t20000\t30000\t+\tchr2\t10000\t0\t10000\t9000\t10000\t60"),
+            paf("chr1\t30000\t0\t5000\t+\tchr2\t10000\t0\t5000\t4000\t5000\t0\ttp:A:S"),
+        ];
+
+        let report = compare_assemblies(&records);
+        assert_eq!(report.queries.queries, 1);
+        assert_eq!(report.queries.aligned_bases, 30000);
+        assert_eq!(report.target_bases, 50000);
+        assert_eq!(report.covered_target_bases, 30100);
+        assert_eq!(report.large_deletions, 1);
+        assert_eq!(report.translocations, 1);
+        assert_eq!(report.segments["chr2"].len(), 1);
+
+        let json = report.to_json();
+        assert!(json.starts_with("{\"queries\":1,\"split_queries\":1,"));
+        assert!(json.contains("\"chr2\":[{\"query\":\"chr1\",\"query_start\":20000,"));
+        assert!(report.to_string().contains("Translocations:         1\n"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Recomputing alignment scores with a chosen scoring scheme, so records
+//! from different aligners or parameters can be compared.
+
+use crate::cigar::{Cigar, CigarKind};
+use crate::cs::{reverse_complement, Cs};
+use crate::errors::Error;
+use crate::fasta::Sequences;
+use crate::fields::Fields;
+use crate::paf::{Strand, PAF};
+use crate::tags::is_tag;
+
+
+/// Scores for an affine gap alignment.
+///
+/// As in minimap2, a gap of length `k` costs `gap_open + k * gap_extend`.
+/// Penalties are given as positive numbers.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub struct Scoring {
+    pub match_score: i64,
+    pub mismatch: i64,
+    pub gap_open: i64,
+    pub gap_extend: i64,
+}
+
+
+impl Scoring {
+    /// Construct a new scoring scheme.
+    pub fn new(match_score: i64, mismatch: i64, gap_open: i64, gap_extend: i64) -> Self {
+        Scoring { match_score, mismatch, gap_open, gap_extend }
+    }
+
+    /// Score a CIGAR that distinguishes matches (`=`) from mismatches (`X`).
+    ///
+    /// Returns `None` if the CIGAR contains `M` operations. Introns,
+    /// clipping and padding do not contribute.
+    pub fn score_cigar(&self, cigar: &Cigar) -> Option<i64> {
+        let mut score = 0;
+        for op in cigar.ops() {
+            let len = op.len() as i64;
+            score += match op.kind() {
+                CigarKind::Match => return None,
+                CigarKind::Equal => len * self.match_score,
+                CigarKind::Diff => -len * self.mismatch,
+                CigarKind::Insertion | CigarKind::Deletion => -(self.gap_open + len * self.gap_extend),
+                CigarKind::Skip | CigarKind::SoftClip | CigarKind::HardClip | CigarKind::Padding => 0,
+            };
+        }
+        Some(score)
+    }
+
+    /// Score a `cs` difference string.
+    pub fn score_cs(&self, cs: &Cs) -> i64 {
+        self.score_cigar(&cs.to_cigar(true))
+            .expect("An extended CIGAR has no M operations.")
+    }
+}
+
+
+/// The minimap2 defaults, `-A2 -B4 -O4 -E2`.
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring::new(2, 4, 4, 2)
+    }
+}
+
+
+impl PAF {
+    /// Recompute the alignment score and store it in the `AS` tag.
+    ///
+    /// The score comes from the `cs` tag if present, then from the `cg` tag.
+    /// A CIGAR using `M` cannot tell matches from mismatches, so the aligned
+    /// bases are compared using `sequences`. Any existing `AS` tag is
+    /// replaced. Returns the new score, or `Error::Rescore` if there is no
+    /// alignment or a needed sequence is missing.
+    pub fn rescore(&mut self, scoring: &Scoring, sequences: Option<&dyn Sequences>) -> Result<i64, Error> {
+        let score = self.alignment_score(scoring, sequences)?;
+
+        let mut fields: Fields = self.fields()
+            .iter()
+            .filter(|f| !is_tag(f, "AS"))
+            .collect();
+        fields.push(&format!("AS:i:{}", score));
+        *self.fields_mut() = fields;
+
+        Ok(score)
+    }
+
+    fn alignment_score(&self, scoring: &Scoring, sequences: Option<&dyn Sequences>) -> Result<i64, Error> {
+        let err = |message: &str| Error::Rescore {
+            query: self.query().name().to_string(),
+            message: message.to_string(),
+        };
+
+        if let Some(cs) = self.cs() {
+            return Ok(scoring.score_cs(&cs));
+        }
+
+        let cigar = self.cigar().ok_or_else(|| err("no cg or cs tag"))?;
+        if let Some(score) = scoring.score_cigar(&cigar) {
+            return Ok(score);
+        }
+
+        let sequences = sequences.ok_or_else(|| err("the CIGAR uses M and no sequences were given"))?;
+        let region = |name: &str, start: u64, end: u64| -> Result<&str, Error> {
+            sequences
+                .sequence(name)
+                .ok_or_else(|| err(&format!("no sequence for '{}'", name)))?
+                .get(start as usize..end as usize)
+                .ok_or_else(|| err(&format!("the sequence for '{}' is shorter than the record", name)))
+        };
+
+        let (query, target) = (self.query(), self.target());
+        let mut query_seq = region(query.name(), query.start(), query.end())?.to_string();
+        if self.strand() == Strand::Minus {
+            query_seq = reverse_complement(&query_seq);
+        }
+        let target_seq = region(target.name(), target.start(), target.end())?;
+
+        let cs = Cs::from_cigar(&cigar, &query_seq, target_seq, false)?;
+        Ok(scoring.score_cs(&cs))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rescore() {
+        let scoring = Scoring::new(1, 3, 5, 2);
+
+        // 8 matches, 1 mismatch and a 2 base deletion.
+        let mut record = paf("q1\t9\t0\t9\t+\tt1\t11\t0\t11\t8\t11\t60\tAS:i:100\tcs:Z::4*ag-tt:4");
+        assert_eq!(record.rescore(&scoring, None).unwrap(), 8 - 3 - 9);
+        assert_eq!(record.score(), -4);
+        assert_eq!(record.fields().iter().filter(|f| f.starts_with("AS:")).count(), 1);
+
+        let mut seqs = HashMap::new();
+        seqs.insert("q1".to_string(), "TTACGTACGT".to_string());
+        seqs.insert("t1".to_string(), "ACGATACC".to_string());
+
+        // The query aligns on the minus strand, as ACGTACG.
+        let mut record = paf("q1\t10\t3\t10\t-\tt1\t8\t0\t8\t6\t8\t60\tcg:Z:3M1D4M");
+        assert_eq!(record.rescore(&scoring, Some(&seqs)).unwrap(), 6 - 3 - 7);
+
+        assert!(record.clone().rescore(&scoring, None).is_err());
+        seqs.remove("t1");
+        assert!(record.rescore(&scoring, Some(&seqs)).is_err());
+    }
+}
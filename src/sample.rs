@@ -0,0 +1,118 @@
+//! Reproducible random subsampling of records.
+
+use crate::paf::PAF;
+
+
+/// How many records to keep.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SampleSize {
+    /// Keep each record independently with this probability.
+    Fraction(f64),
+    /// Keep exactly this many records, or all if there are fewer.
+    Count(usize),
+}
+
+
+/// A small, fast pseudo-random generator (SplitMix64).
+///
+/// This is not suitable for cryptography, but it is stable across platforms
+/// and releases, so samples are reproducible from their seed.
+#[derive(Debug, Clone)]
+struct SplitMix64 {
+    state: u64,
+}
+
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform float in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A uniform integer in `[0, n)`.
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+
+/// Randomly subsample records, reproducibly for a given `seed`.
+///
+/// A fixed count is drawn with reservoir sampling, so the whole input is
+/// streamed but only `n` records are held at once. Sampled records are
+/// returned in their input order.
+pub fn sample<I>(records: I, size: SampleSize, seed: u64) -> Vec<PAF>
+where
+    I: IntoIterator<Item = PAF>,
+{
+    let mut rng = SplitMix64::new(seed);
+
+    match size {
+        SampleSize::Fraction(fraction) => records
+            .into_iter()
+            .filter(|_| rng.next_f64() < fraction)
+            .collect(),
+        SampleSize::Count(n) => {
+            // `n` may be far more than the number of records.
+            let mut reservoir: Vec<(usize, PAF)> = Vec::with_capacity(n.min(1 << 16));
+            for (i, record) in records.into_iter().enumerate() {
+                if reservoir.len() < n {
+                    reservoir.push((i, record));
+                } else {
+                    let j = rng.below(i as u64 + 1) as usize;
+                    if j < n {
+                        reservoir[j] = (i, record);
+                    }
+                }
+            }
+            reservoir.sort_unstable_by_key(|(i, _)| *i);
+            reservoir.into_iter().map(|(_, r)| r).collect()
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(n: usize) -> Vec<PAF> {
+        (0..n)
+            .map(|i| format!("q{}\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60", i).parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_count() {
+        let sampled = sample(records(1000), SampleSize::Count(10), 42);
+        assert_eq!(sampled.len(), 10);
+        assert_eq!(sampled, sample(records(1000), SampleSize::Count(10), 42));
+        assert_ne!(sampled, sample(records(1000), SampleSize::Count(10), 43));
+
+        let indices: Vec<usize> = sampled.iter().map(|r| r.query().name()[1..].parse().unwrap()).collect();
+        assert!(indices.windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!(sample(records(5), SampleSize::Count(10), 42).len(), 5);
+        assert_eq!(sample(records(5), SampleSize::Count(usize::MAX), 42).len(), 5);
+    }
+
+    #[test]
+    fn test_sample_fraction() {
+        let sampled = sample(records(10000), SampleSize::Fraction(0.1), 7);
+        assert!(sampled.len() > 800 && sampled.len() < 1200);
+        assert_eq!(sampled, sample(records(10000), SampleSize::Fraction(0.1), 7));
+        assert!(sample(records(100), SampleSize::Fraction(0.0), 7).is_empty());
+    }
+}
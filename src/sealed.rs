@@ -0,0 +1,6 @@
+//! Private supertrait preventing downstream implementations of the crate's
+//! extension traits, so that methods can be added without breaking changes.
+
+pub trait Sealed {}
+
+impl<I> Sealed for I where I: Iterator<Item = crate::paf::PAF> {}
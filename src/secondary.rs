@@ -0,0 +1,109 @@
+//! Marking primary and secondary alignments for records without `tp` tags.
+//!
+//! minimap2 records whether an alignment is primary in the `tp` tag, but
+//! many other aligners write no such tag, so every record looks primary to
+//! [`PAF::is_primary`]. The pass here assigns a synthetic `tp:A:P` or
+//! `tp:A:S` following minimap2's rules, so primary-only filters behave the
+//! same for every aligner.
+
+use std::collections::HashMap;
+
+use crate::paf::PAF;
+
+
+/// minimap2's default `--mask-level`.
+pub const DEFAULT_MASK_LEVEL: f64 = 0.5;
+
+
+/// Add a `tp` tag marking each record as primary or secondary.
+///
+/// Records are ranked within each query by [`PAF::score`], then alignment
+/// length. The best record is primary. Each following record is secondary
+/// if at least `mask_level` of the shorter query span overlaps a record
+/// already marked primary, and is otherwise primary too, as for the parts
+/// of a split alignment. Records that already have a `tp` tag are left as
+/// they are and take no part in the ranking.
+///
+/// `mask_level` must be in `0..=1`. At 0 any overlap makes a record
+/// secondary, and above 1 no record is ever secondary.
+///
+/// Records need not be grouped by query, and their order is not changed.
+/// Records with an end before their start are treated as empty spans.
+pub fn mark_secondary(records: &mut [PAF], mask_level: f64) {
+    let mut by_query: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        if record.tp().is_none() {
+            by_query.entry(record.query().name()).or_default().push(i);
+        }
+    }
+
+    let mut primary = vec![false; records.len()];
+    let mut marked: Vec<usize> = Vec::new();
+
+    for (_, mut group) in by_query {
+        group.sort_by(|&a, &b| {
+            let (ra, rb) = (&records[a], &records[b]);
+            rb.score().cmp(&ra.score())
+                .then(rb.alnlen().cmp(&ra.alnlen()))
+                .then(a.cmp(&b))
+        });
+
+        let mut kept: Vec<(u64, u64)> = Vec::new();
+        for i in group {
+            let query = records[i].query();
+            let (start, end) = (query.start(), query.end());
+
+            let masked = kept.iter().any(|&(s, e)| {
+                let overlap = end.min(e).saturating_sub(start.max(s));
+                let shorter = end.saturating_sub(start).min(e.saturating_sub(s));
+                overlap > 0 && overlap as f64 >= mask_level * shorter as f64
+            });
+
+            if !masked {
+                primary[i] = true;
+                kept.push((start, end));
+            }
+            marked.push(i);
+        }
+    }
+
+    for i in marked {
+        let tp = if primary[i] { "tp:A:P" } else { "tp:A:S" };
+        records[i].fields_mut().push(tp);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_mark_secondary() {
+        let mut records = vec![
+            paf("q1\t1000\t0\t800\t+\tt2\t5000\t0\t800\t700\t800\t0"),
+            paf("q1\t1000\t0\t900\t+\tt1\t5000\t0\t900\t850\t900\t60"),
+            paf("q1\t1000\t850\t1000\t+\tt3\t5000\t0\t150\t150\t150\t60"),
+            paf("q2\t1000\t0\t500\t+\tt1\t5000\t0\t500\t100\t500\t60\tAS:i:900"),
+            paf("q2\t1000\t0\t500\t+\tt1\t5000\t1000\t1500\t490\t500\t60\tAS:i:800"),
+            paf("q3\t1000\t0\t500\t+\tt1\t5000\t0\t500\t500\t500\t60\ttp:A:S"),
+        ];
+        mark_secondary(&mut records, DEFAULT_MASK_LEVEL);
+
+        let tp: Vec<Option<char>> = records.iter().map(|r| r.tp()).collect();
+        assert_eq!(tp, vec![Some('S'), Some('P'), Some('P'), Some('P'), Some('S'), Some('S')]);
+        assert_eq!(records[5].fields().len(), 1);
+
+        // Unvalidated records with the ends swapped don't underflow.
+        let mut records = vec![
+            paf("q1\t1000\t0\t800\t+\tt1\t5000\t0\t800\t700\t800\t60"),
+            paf("q1\t1000\t500\t100\t+\tt2\t5000\t0\t400\t300\t400\t60"),
+        ];
+        mark_secondary(&mut records, DEFAULT_MASK_LEVEL);
+        assert_eq!(records[0].tp(), Some('P'));
+    }
+}
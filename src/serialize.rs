@@ -0,0 +1,173 @@
+//! Allocation-free serialisation of records.
+
+use std::io::{self, Write};
+
+use crate::paf::PAF;
+use crate::record::PafRecord;
+
+
+/// Format an unsigned integer into the end of `buf`, returning the digits.
+fn format_u64(mut value: u64, buf: &mut [u8; 20]) -> &[u8] {
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    &buf[i..]
+}
+
+
+/// Write an unsigned integer without going through `fmt`.
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    let mut buf = [0; 20];
+    writer.write_all(format_u64(value, &mut buf))
+}
+
+
+fn write_locus<W: Write>(writer: &mut W, name: &str, length: u64, start: u64, end: u64) -> io::Result<()> {
+    writer.write_all(name.as_bytes())?;
+    writer.write_all(b"\t")?;
+    write_u64(writer, length)?;
+    writer.write_all(b"\t")?;
+    write_u64(writer, start)?;
+    writer.write_all(b"\t")?;
+    write_u64(writer, end)
+}
+
+
+/// Write any record as a tab separated line, without a line ending.
+pub(crate) fn write_record<W: Write, R: PafRecord + ?Sized>(writer: W, record: &R) -> io::Result<()> {
+    write_record_with_fields(writer, record, record.optional_fields())
+}
+
+
+/// Write the mandatory columns of a record followed by `fields`, which
+/// replace its own optional fields.
+pub(crate) fn write_record_with_fields<W, R>(writer: W, record: &R, fields: &str) -> io::Result<()>
+where
+    W: Write,
+    R: PafRecord + ?Sized,
+{
+    write_record_with_names(writer, record, record.query_name(), record.target_name(), fields)
+}
+
+
+/// Write a record as [`write_record_with_fields`] does, but with the query
+/// and target names replaced, e.g. by escaped names.
+pub(crate) fn write_record_with_names<W, R>(
+    mut writer: W,
+    record: &R,
+    query_name: &str,
+    target_name: &str,
+    fields: &str,
+) -> io::Result<()>
+where
+    W: Write,
+    R: PafRecord + ?Sized,
+{
+    let mut strand = [0; 1];
+    strand[0] = u8::from(&record.strand());
+
+    write_locus(
+        &mut writer,
+        query_name,
+        record.query_length(),
+        record.query_start(),
+        record.query_end(),
+    )?;
+    writer.write_all(b"\t")?;
+    writer.write_all(&strand)?;
+    writer.write_all(b"\t")?;
+    write_locus(
+        &mut writer,
+        target_name,
+        record.target_length(),
+        record.target_start(),
+        record.target_end(),
+    )?;
+    writer.write_all(b"\t")?;
+    write_u64(&mut writer, record.nmatch())?;
+    writer.write_all(b"\t")?;
+    write_u64(&mut writer, record.alnlen())?;
+    writer.write_all(b"\t")?;
+    write_u64(&mut writer, record.mapq() as u64)?;
+
+    if !fields.is_empty() {
+        writer.write_all(b"\t")?;
+        writer.write_all(fields.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+
+impl PAF {
+    /// Write the record as a tab separated line, without a line ending.
+    ///
+    /// The output is identical to `Display`, but no intermediate `String` is
+    /// allocated. Wrap unbuffered sinks in a `BufWriter`, as each column is
+    /// written separately.
+    pub fn write_tsv<W: Write>(&self, writer: W) -> io::Result<()> {
+        write_record(writer, self)
+    }
+
+    /// Append the record to a byte buffer, without a line ending.
+    ///
+    /// The bytes are the same as `Display` produces and can be parsed back
+    /// with [`crate::complete::paf_u8`].
+    pub fn to_bytes_into(&self, buf: &mut Vec<u8>) {
+        self.write_tsv(buf).expect("Writing to a Vec cannot fail.");
+    }
+
+    /// The record as bytes, without a line ending.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.to_bytes_into(&mut buf);
+        buf
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_u64() {
+        let mut buf = [0; 20];
+        assert_eq!(format_u64(0, &mut buf), b"0");
+        assert_eq!(format_u64(1234567, &mut buf), b"1234567");
+        assert_eq!(format_u64(u64::MAX, &mut buf), u64::MAX.to_string().as_bytes());
+    }
+
+    #[test]
+    fn test_write_tsv() {
+        for line in &[
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P\tcg:Z:10M",
+            "q1\t10\t0\t10\t*\tt1\t20\t0\t10\t10\t10\t255",
+        ] {
+            let record: PAF = line.parse().unwrap();
+            let mut output = Vec::new();
+            record.write_tsv(&mut output).unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), record.to_string());
+        }
+    }
+
+    #[test]
+    fn test_to_bytes_into() {
+        let record: PAF = "q1\t10\t0\t10\t-\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P".parse().unwrap();
+
+        let mut buf = b"prefix\n".to_vec();
+        record.to_bytes_into(&mut buf);
+        assert_eq!(&buf[..7], b"prefix\n");
+
+        let (rest, parsed) = crate::complete::paf_u8::<nom::error::VerboseError<&[u8]>>(&buf[7..]).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(parsed, record);
+        assert_eq!(record.to_bytes(), &buf[7..]);
+    }
+}
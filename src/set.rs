@@ -0,0 +1,323 @@
+//! An owned collection of records with summary methods.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::BufRead;
+use std::iter::FromIterator;
+use std::ops::Deref;
+
+use crate::depth::{depth_runs, DepthRun};
+use crate::errors::Error;
+use crate::paf::{Locus, PAF};
+use crate::reader::PafReader;
+use crate::sort::SortBy;
+
+
+/// A collection of alignment records for interactive exploration.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct PafSet {
+    records: Vec<PAF>,
+}
+
+
+impl PafSet {
+    /// Construct an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read all records from a reader.
+    pub fn from_reader<R: BufRead>(reader: PafReader<R>) -> Result<Self, Error> {
+        reader.collect::<Result<Vec<_>, _>>().map(PafSet::from)
+    }
+
+    /// The number of records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether the set has no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Add a record.
+    pub fn push(&mut self, record: PAF) {
+        self.records.push(record);
+    }
+
+    /// Iterate over the records.
+    pub fn iter(&self) -> std::slice::Iter<'_, PAF> {
+        self.records.iter()
+    }
+
+    /// The records as a slice.
+    pub fn as_slice(&self) -> &[PAF] {
+        &self.records
+    }
+
+    /// Consume the set, returning the records.
+    pub fn into_vec(self) -> Vec<PAF> {
+        self.records
+    }
+
+    /// The total alignment length over all records.
+    pub fn total_aligned_bases(&self) -> u64 {
+        self.records.iter().map(|r| r.alnlen()).sum()
+    }
+
+    /// The distinct target names, in order of first appearance.
+    pub fn targets(&self) -> Vec<&str> {
+        distinct(self.records.iter().map(|r| r.target().name()))
+    }
+
+    /// The distinct query names, in order of first appearance.
+    pub fn queries(&self) -> Vec<&str> {
+        distinct(self.records.iter().map(|r| r.query().name()))
+    }
+
+    /// Keep the records matching a predicate.
+    pub fn filter<F>(&self, mut predicate: F) -> PafSet
+    where
+        F: FnMut(&PAF) -> bool,
+    {
+        self.records.iter().filter(|r| predicate(r)).cloned().collect()
+    }
+
+    /// A copy of the set in target genome order.
+    pub fn sorted(&self) -> PafSet {
+        let mut records = self.records.clone();
+        records.sort();
+        PafSet::from(records)
+    }
+
+    /// Whether the records are in target genome order, see [`SortBy::Target`].
+    pub fn is_sorted(&self) -> bool {
+        self.first_unsorted().is_none()
+    }
+
+    /// Mark the set as sorted, or return `Error::UnsortedRecords` with the
+    /// index of the first record out of order.
+    pub fn check_sorted(self) -> Result<Sorted<PafSet>, Error> {
+        match self.first_unsorted() {
+            None => Ok(Sorted(self)),
+            Some(index) => Err(Error::UnsortedRecords { index, by: SortBy::Target.to_string() }),
+        }
+    }
+
+    /// Sort the set into target genome order, unless it already is.
+    pub fn ensure_sorted(mut self) -> Sorted<PafSet> {
+        if !self.is_sorted() {
+            self.records.sort();
+        }
+        Sorted(self)
+    }
+
+    /// The index of the first record that sorts before the one preceding it.
+    fn first_unsorted(&self) -> Option<usize> {
+        self.records
+            .windows(2)
+            .position(|w| SortBy::Target.compare(&w[0], &w[1]).is_gt())
+            .map(|i| i + 1)
+    }
+
+    /// The number of bases of each target covered by at least one record.
+    pub fn coverage(&self) -> BTreeMap<&str, u64> {
+        covered_intervals(&self.records)
+            .into_iter()
+            .map(|(name, spans)| (name, spans.iter().map(|(s, e)| e - s).sum()))
+            .collect()
+    }
+
+    /// The run-length encoded depth of primary alignments on the targets.
+    pub fn depth(&self) -> Vec<DepthRun<'_>> {
+        depth_runs(&self.records)
+    }
+}
+
+
+/// A collection known to be in target genome order.
+///
+/// Operations that rely on the order are only available on this wrapper,
+/// so they can't be called on unsorted records by mistake. Get one from
+/// [`PafSet::ensure_sorted`] or [`PafSet::check_sorted`]. The wrapped
+/// collection can be read through `Deref`, but not modified.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct Sorted<T>(T);
+
+
+impl<T> Sorted<T> {
+    /// Consume the wrapper, returning the collection.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+
+impl<T> Deref for Sorted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+
+impl Sorted<PafSet> {
+    /// The records overlapping `start..end` on a target, in order.
+    ///
+    /// Records for the target are found by binary search, then scanned up to
+    /// the first record starting at or after `end`.
+    pub fn region(&self, target: &str, start: u64, end: u64) -> Vec<&PAF> {
+        let records = self.as_slice();
+        let first = records.partition_point(|r| r.target().name() < target);
+        let records = &records[first..];
+        let last = records.partition_point(|r| r.target().name() == target && r.target().start() < end);
+
+        records[..last].iter().filter(|r| r.target().end() > start).collect()
+    }
+}
+
+
+/// The merged target intervals covered by the records, by target name.
+///
+/// Intervals are sorted, non-overlapping and non-empty.
+pub(crate) fn covered_intervals(records: &[PAF]) -> BTreeMap<&str, Vec<(u64, u64)>> {
+    merged_intervals(records, PAF::target)
+}
+
+
+/// The merged intervals of the loci chosen by `locus`, by sequence name.
+pub(crate) fn merged_intervals<F>(records: &[PAF], locus: F) -> BTreeMap<&str, Vec<(u64, u64)>>
+where
+    F: Fn(&PAF) -> &Locus,
+{
+    let mut intervals: BTreeMap<&str, Vec<(u64, u64)>> = BTreeMap::new();
+    for record in records {
+        let locus = locus(record);
+        if locus.end() > locus.start() {
+            intervals.entry(locus.name()).or_default().push((locus.start(), locus.end()));
+        }
+    }
+
+    for spans in intervals.values_mut() {
+        merge_spans(spans);
+    }
+
+    intervals
+}
+
+
+/// Sort and merge overlapping or touching half-open intervals in place.
+pub(crate) fn merge_spans(spans: &mut Vec<(u64, u64)>) {
+    spans.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(spans.len());
+    for &(start, end) in spans.iter() {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    *spans = merged;
+}
+
+
+/// Collect distinct names, keeping the first occurrence.
+fn distinct<'a, I: Iterator<Item = &'a str>>(names: I) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    names.filter(|n| seen.insert(*n)).collect()
+}
+
+
+impl From<Vec<PAF>> for PafSet {
+    fn from(records: Vec<PAF>) -> Self {
+        PafSet { records }
+    }
+}
+
+
+impl FromIterator<PAF> for PafSet {
+    fn from_iter<I: IntoIterator<Item = PAF>>(iter: I) -> Self {
+        PafSet { records: iter.into_iter().collect() }
+    }
+}
+
+
+impl Extend<PAF> for PafSet {
+    fn extend<I: IntoIterator<Item = PAF>>(&mut self, iter: I) {
+        self.records.extend(iter);
+    }
+}
+
+
+impl IntoIterator for PafSet {
+    type Item = PAF;
+    type IntoIter = std::vec::IntoIter<PAF>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.into_iter()
+    }
+}
+
+
+impl<'a> IntoIterator for &'a PafSet {
+    type Item = &'a PAF;
+    type IntoIter = std::slice::Iter<'a, PAF>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.iter()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paf_set() {
+        let input = "q1\t100\t0\t100\t+\tt2\t1000\t100\t200\t90\t100\t60\n\
+                     q2\t100\t0\t100\t+\tt1\t1000\t0\t100\t100\t100\t0\n\
+                     q1\t100\t0\t100\t-\tt1\t1000\t50\t150\t95\t100\t60\n";
+        let set = PafSet::from_reader(PafReader::new(input.as_bytes())).unwrap();
+
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.total_aligned_bases(), 300);
+        assert_eq!(set.targets(), vec!["t2", "t1"]);
+        assert_eq!(set.queries(), vec!["q1", "q2"]);
+        assert_eq!(set.filter(|r| r.mapq() >= 30).len(), 2);
+
+        let starts: Vec<u64> = set.sorted().iter().map(|r| r.target().start()).collect();
+        assert_eq!(starts, vec![0, 50, 100]);
+
+        let coverage = set.coverage();
+        assert_eq!(coverage["t1"], 150);
+        assert_eq!(coverage["t2"], 100);
+    }
+
+    #[test]
+    fn test_sorted() {
+        let input = "q1\t100\t0\t100\t+\tt2\t1000\t100\t200\t90\t100\t60\n\
+                     q2\t100\t0\t100\t+\tt1\t1000\t0\t100\t100\t100\t0\n\
+                     q1\t100\t0\t100\t-\tt1\t1000\t50\t150\t95\t100\t60\n\
+                     q3\t900\t0\t900\t+\tt1\t1000\t60\t960\t900\t900\t60\n";
+        let set = PafSet::from_reader(PafReader::new(input.as_bytes())).unwrap();
+        assert!(!set.is_sorted());
+
+        match set.clone().check_sorted() {
+            Err(e @ Error::UnsortedRecords { index: 1, .. }) => assert_eq!(e.line_number(), None),
+            e => panic!("Expected an unsorted error, got {:?}", e),
+        }
+
+        let sorted = set.ensure_sorted();
+        assert!(sorted.is_sorted());
+        assert_eq!(sorted.clone().into_inner().check_sorted().unwrap(), sorted);
+
+        let names = |records: Vec<&PAF>| -> Vec<String> {
+            records.iter().map(|r| r.query().name().to_string()).collect()
+        };
+        assert_eq!(names(sorted.region("t1", 120, 130)), vec!["q1", "q3"]);
+        assert_eq!(names(sorted.region("t1", 0, 50)), vec!["q2"]);
+        assert_eq!(names(sorted.region("t2", 0, 100)), Vec::<String>::new());
+        assert_eq!(names(sorted.region("t3", 0, 1000)), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,239 @@
+//! Trimming records, and their CIGARs, to a sub-region.
+
+use crate::cigar::{Cigar, CigarKind, CigarOp};
+use crate::fields::Fields;
+use crate::paf::{Locus, Strand, PAF};
+use crate::tags::is_tag;
+
+
+/// Tags describing the alignment content, which are invalid once the
+/// alignment is trimmed.
+const STALE_TAGS: &[&str] = &["cg", "cs", "NM", "AS", "ms", "de", "nn"];
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Query,
+    Target,
+}
+
+
+impl PAF {
+    /// Trim the record to the part aligned within `[start, end)` on the target.
+    ///
+    /// The CIGAR in the `cg` tag is trimmed to match, and both coordinate sets,
+    /// `nmatch` and `alnlen` are recomputed. Matches in `M` operations are
+    /// estimated from the record's overall match rate, whereas `=` and `X`
+    /// operations are counted exactly. Tags that describe the alignment
+    /// content (`cs`, `NM`, `AS`, `ms`, `de`, `nn`) are dropped.
+    ///
+    /// Returns `None` if the record has no CIGAR, the CIGAR's query or target
+    /// length differs from the record's spans, or no aligned bases fall
+    /// within the interval.
+    pub fn slice_target(&self, start: u64, end: u64) -> Option<PAF> {
+        let offset_start = start.saturating_sub(self.target().start());
+        let offset_end = end.saturating_sub(self.target().start());
+        self.slice(Axis::Target, offset_start, offset_end)
+    }
+
+    /// Trim the record to the part aligned within `[start, end)` on the query.
+    ///
+    /// Coordinates are on the forward strand of the query, as in the record.
+    /// See [`PAF::slice_target`] for how the record is updated.
+    pub fn slice_query(&self, start: u64, end: u64) -> Option<PAF> {
+        let (offset_start, offset_end) = match self.strand() {
+            Strand::Minus => (
+                self.query().end().saturating_sub(end),
+                self.query().end().saturating_sub(start),
+            ),
+            _ => (
+                start.saturating_sub(self.query().start()),
+                end.saturating_sub(self.query().start()),
+            ),
+        };
+        self.slice(Axis::Query, offset_start, offset_end)
+    }
+
+    fn slice(&self, axis: Axis, start: u64, end: u64) -> Option<PAF> {
+        let cigar = self.cigar()?;
+        if cigar.query_len().ok()? != self.query().end() - self.query().start()
+            || cigar.target_len().ok()? != self.target().end() - self.target().start()
+        {
+            return None;
+        }
+
+        let (ops, (q0, q1), (t0, t1)) = slice_cigar(&cigar, axis, start, end)?;
+
+        let (query_start, query_end) = match self.strand() {
+            Strand::Minus => (self.query().end() - q1, self.query().end() - q0),
+            _ => (self.query().start() + q0, self.query().start() + q1),
+        };
+        let query = Locus::new(
+            self.query().name().to_string(),
+            self.query().length(),
+            query_start,
+            query_end,
+        );
+        let target = Locus::new(
+            self.target().name().to_string(),
+            self.target().length(),
+            self.target().start() + t0,
+            self.target().start() + t1,
+        );
+
+        let nmatch = estimate_nmatch(self, &cigar, &ops);
+        let alnlen = ops
+            .iter()
+            .filter(|o| o.kind() != CigarKind::Skip)
+            .filter(|o| o.kind().consumes_query() || o.kind().consumes_target())
+            .map(|o| o.len())
+            .sum();
+
+        let mut fields: Fields = self.fields()
+            .iter()
+            .filter(|f| !STALE_TAGS.iter().any(|t| is_tag(f, t)))
+            .collect();
+        fields.push(&format!("cg:Z:{}", Cigar::new(ops)));
+
+        Some(PAF::new(query, self.strand(), target, nmatch, alnlen, self.mapq(), fields))
+    }
+}
+
+
+/// The query and target offset range of a trimmed CIGAR.
+type Trimmed = (Vec<CigarOp>, (u64, u64), (u64, u64));
+
+
+/// Trim a CIGAR to the offsets `[start, end)` along one axis.
+///
+/// Offsets are relative to the start of the alignment in CIGAR order.
+/// Leading and trailing gaps are dropped from the result.
+fn slice_cigar(cigar: &Cigar, axis: Axis, start: u64, end: u64) -> Option<Trimmed> {
+    // Each kept op with the query and target offsets where it starts.
+    let mut kept: Vec<(CigarOp, u64, u64)> = Vec::new();
+    let (mut q, mut t) = (0, 0);
+
+    for op in cigar.ops() {
+        let kind = op.kind();
+        let (on_axis, pos) = match axis {
+            Axis::Query => (kind.consumes_query(), q),
+            Axis::Target => (kind.consumes_target(), t),
+        };
+
+        if on_axis {
+            let ov_start = start.max(pos);
+            let ov_end = end.min(pos + op.len());
+            if ov_start < ov_end {
+                let skip = ov_start - pos;
+                let qs = if kind.consumes_query() { q + skip } else { q };
+                let ts = if kind.consumes_target() { t + skip } else { t };
+                kept.push((CigarOp::new(kind, ov_end - ov_start), qs, ts));
+            }
+        } else if pos > start && pos < end {
+            kept.push((*op, q, t));
+        }
+
+        if kind.consumes_query() {
+            q += op.len();
+        }
+        if kind.consumes_target() {
+            t += op.len();
+        }
+    }
+
+    let aligned = |op: &CigarOp| op.kind().consumes_query() && op.kind().consumes_target();
+    let first = kept.iter().position(|(op, _, _)| aligned(op))?;
+    let last = kept.iter().rposition(|(op, _, _)| aligned(op))?;
+    let kept = &kept[first..=last];
+
+    let (_, q0, t0) = kept[0];
+    let (last_op, q_last, t_last) = kept[kept.len() - 1];
+
+    let mut ops: Vec<CigarOp> = Vec::with_capacity(kept.len());
+    for (op, _, _) in kept {
+        match ops.last_mut() {
+            Some(prev) if prev.kind() == op.kind() => {
+                *prev = CigarOp::new(op.kind(), prev.len() + op.len());
+            },
+            _ => ops.push(*op),
+        }
+    }
+
+    Some((ops, (q0, q_last + last_op.len()), (t0, t_last + last_op.len())))
+}
+
+
+/// Estimate the matches in the trimmed ops from the original record.
+fn estimate_nmatch(record: &PAF, original: &Cigar, ops: &[CigarOp]) -> u64 {
+    let count = |ops: &[CigarOp], kind: CigarKind| -> u64 {
+        ops.iter().filter(|o| o.kind() == kind).map(|o| o.len()).sum()
+    };
+
+    let orig_equal = count(original.ops(), CigarKind::Equal);
+    let orig_m = count(original.ops(), CigarKind::Match);
+    let new_equal = count(ops, CigarKind::Equal);
+    let new_m = count(ops, CigarKind::Match);
+
+    if orig_m == 0 {
+        return new_equal;
+    }
+
+    let m_matches = record.nmatch().saturating_sub(orig_equal).min(orig_m);
+    let estimate = (new_m as f64 * m_matches as f64 / orig_m as f64).round() as u64;
+    new_equal + estimate
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_slice_target() {
+        let record = paf("q\t100\t10\t30\t+\tt\t100\t50\t73\t18\t25\t60\tNM:i:7\tcg:Z:5=2I3=5D10=");
+
+        let sliced = record.slice_target(52, 70).unwrap();
+        assert_eq!(
+            sliced.to_string(),
+            "q\t100\t12\t27\t+\tt\t100\t52\t70\t13\t20\t60\tcg:Z:3=2I3=5D7="
+        );
+
+        // Gaps at the slice edges are removed.
+        let sliced = record.slice_target(56, 65).unwrap();
+        assert_eq!(sliced.cigar().unwrap().to_string(), "2=5D2=");
+        assert_eq!(sliced.query().start(), 18);
+        assert_eq!(sliced.query().end(), 22);
+
+        assert!(record.slice_target(58, 63).is_none());
+        assert!(record.slice_target(0, 10).is_none());
+    }
+
+    #[test]
+    fn test_slice_query_minus() {
+        let record = paf("q\t100\t10\t30\t-\tt\t100\t50\t73\t18\t25\t60\tcg:Z:5=2I3=5D10=");
+
+        let sliced = record.slice_query(10, 20).unwrap();
+        assert_eq!(sliced.cigar().unwrap().to_string(), "10=");
+        assert_eq!(sliced.target().start(), 63);
+        assert_eq!(sliced.target().end(), 73);
+        assert_eq!(sliced.query().start(), 10);
+        assert_eq!(sliced.query().end(), 20);
+
+        // A CIGAR longer than the record's query span can't be sliced.
+        let record = paf("q\t100\t0\t5\t-\tt\t100\t0\t20\t5\t20\t60\tcg:Z:20M");
+        assert!(record.slice_query(0, 5).is_none());
+        assert!(record.slice_target(0, 20).is_none());
+    }
+
+    #[test]
+    fn test_slice_estimates_m() {
+        let record = paf("q\t100\t0\t20\t+\tt\t100\t0\t20\t10\t20\t60\tcg:Z:20M");
+        let sliced = record.slice_target(0, 10).unwrap();
+        assert_eq!(sliced.nmatch(), 5);
+        assert_eq!(sliced.alnlen(), 10);
+    }
+}
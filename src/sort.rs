@@ -0,0 +1,372 @@
+//! Sorting of record sets, including files larger than memory.
+
+use core::cmp::Ordering;
+use core::fmt;
+#[cfg(feature = "fs")]
+use std::collections::BinaryHeap;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+#[cfg(feature = "fs")]
+use snafu::ResultExt;
+#[cfg(feature = "fs")]
+use tempfile::{NamedTempFile, TempPath};
+
+#[cfg(feature = "fs")]
+use crate::errors::{Error, Io};
+use crate::paf::PAF;
+#[cfg(feature = "fs")]
+use crate::reader::PafReader;
+#[cfg(feature = "fs")]
+use crate::writer::PafWriter;
+
+
+/// The coordinate system to sort records by.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub enum SortBy {
+    /// By target name, start, end, then query name and start.
+    Target,
+    /// By query name, start, end, then target name and start.
+    Query,
+}
+
+
+impl SortBy {
+    /// Compare two records under this ordering.
+    pub fn compare(self, a: &PAF, b: &PAF) -> Ordering {
+        let (a1, a2, b1, b2) = match self {
+            SortBy::Target => (a.target(), a.query(), b.target(), b.query()),
+            SortBy::Query => (a.query(), a.target(), b.query(), b.target()),
+        };
+
+        a1.name().cmp(b1.name())
+            .then(a1.start().cmp(&b1.start()))
+            .then(a1.end().cmp(&b1.end()))
+            .then(a2.name().cmp(b2.name()))
+            .then(a2.start().cmp(&b2.start()))
+    }
+}
+
+
+impl fmt::Display for SortBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortBy::Target => write!(f, "target"),
+            SortBy::Query => write!(f, "query"),
+        }
+    }
+}
+
+
+/// Sort records in memory. The sort is stable.
+pub fn sort_records(records: &mut [PAF], by: SortBy) {
+    records.sort_by(|a, b| by.compare(a, b));
+}
+
+
+/// The most sorted runs merged at once, bounding the open file handles.
+#[cfg(feature = "fs")]
+const MAX_MERGE_RUNS: usize = 64;
+
+
+/// Approximate the heap and inline memory used by a record.
+#[cfg(feature = "fs")]
+pub(crate) fn approx_size(record: &PAF) -> usize {
+    core::mem::size_of::<PAF>()
+        + record.query().name().len()
+        + record.target().name().len()
+        + record.fields().as_str().len()
+        + record.fields().len() * core::mem::size_of::<u32>()
+}
+
+
+/// Sort a PAF stream that may not fit in memory.
+///
+/// Records are buffered until their approximate size exceeds `mem_limit`
+/// bytes, then sorted and spilled to a temporary file, which is closed until
+/// the merge. The sorted runs are merged into `output`, at most 64 at a
+/// time, with intermediate passes for larger inputs. Input order is
+/// preserved between records that compare equal.
+#[cfg(feature = "fs")]
+pub fn external_sort<R, W>(
+    input: R,
+    output: W,
+    by: SortBy,
+    mem_limit: usize,
+) -> Result<(), Error>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut runs: Vec<TempPath> = Vec::new();
+    let mut buffer: Vec<PAF> = Vec::new();
+    let mut buffer_size = 0;
+
+    for record in PafReader::new(input) {
+        let record = record?;
+        buffer_size += approx_size(&record);
+        buffer.push(record);
+
+        if buffer_size >= mem_limit {
+            runs.push(spill(&mut buffer, by)?);
+            buffer_size = 0;
+        }
+    }
+
+    let mut writer = PafWriter::new(output);
+
+    if runs.is_empty() {
+        sort_records(&mut buffer, by);
+        for record in &buffer {
+            writer.write_record(record)?;
+        }
+        return writer.flush();
+    }
+
+    if !buffer.is_empty() {
+        runs.push(spill(&mut buffer, by)?);
+    }
+
+    merge_runs(runs, &mut writer, by, MAX_MERGE_RUNS)?;
+    writer.flush()
+}
+
+
+/// Sort the buffer and write it to a temporary file, emptying the buffer.
+#[cfg(feature = "fs")]
+fn spill(buffer: &mut Vec<PAF>, by: SortBy) -> Result<TempPath, Error> {
+    sort_records(buffer, by);
+
+    let mut writer = run_writer()?;
+    for record in buffer.drain(..) {
+        writer.write_record(&record)?;
+    }
+    close_run(writer)
+}
+
+
+/// Open a writer for a new sorted run.
+#[cfg(feature = "fs")]
+fn run_writer() -> Result<PafWriter<BufWriter<NamedTempFile>>, Error> {
+    let file = NamedTempFile::new().context(Io)?;
+    Ok(PafWriter::new(BufWriter::new(file)))
+}
+
+
+/// Flush and close a run, keeping its path until it is dropped.
+#[cfg(feature = "fs")]
+fn close_run(mut writer: PafWriter<BufWriter<NamedTempFile>>) -> Result<TempPath, Error> {
+    writer.flush()?;
+
+    let file = writer
+        .into_inner()
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(Io)?;
+    Ok(file.into_temp_path())
+}
+
+
+/// A record at the head of a sorted run, ordered for a min-heap.
+#[cfg(feature = "fs")]
+struct Head {
+    record: PAF,
+    run: usize,
+    by: SortBy,
+}
+
+
+#[cfg(feature = "fs")]
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so that the max-heap yields the smallest record, with
+        // earlier runs first to keep the sort stable.
+        self.by.compare(&other.record, &self.record)
+            .then(other.run.cmp(&self.run))
+    }
+}
+
+
+#[cfg(feature = "fs")]
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+#[cfg(feature = "fs")]
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+
+#[cfg(feature = "fs")]
+impl Eq for Head {}
+
+
+/// K-way merge sorted runs into the writer.
+///
+/// While there are more than `fan_in` runs, consecutive groups are merged
+/// into intermediate runs, so ties still come from earlier runs first.
+#[cfg(feature = "fs")]
+fn merge_runs<W: Write>(
+    mut runs: Vec<TempPath>,
+    writer: &mut PafWriter<W>,
+    by: SortBy,
+    fan_in: usize,
+) -> Result<(), Error> {
+    let fan_in = fan_in.max(2);
+
+    while runs.len() > fan_in {
+        let mut merged = Vec::with_capacity(runs.len() / fan_in + 1);
+        for group in runs.chunks(fan_in) {
+            let mut run = run_writer()?;
+            merge_group(group, &mut run, by)?;
+            merged.push(close_run(run)?);
+        }
+        // Dropping the merged runs removes their files.
+        runs = merged;
+    }
+
+    merge_group(&runs, writer, by)
+}
+
+
+/// Open a group of runs together and merge them into the writer.
+#[cfg(feature = "fs")]
+fn merge_group<W: Write>(
+    runs: &[TempPath],
+    writer: &mut PafWriter<W>,
+    by: SortBy,
+) -> Result<(), Error> {
+    let mut readers = Vec::with_capacity(runs.len());
+    for path in runs {
+        let file = File::open(path).context(Io)?;
+        readers.push(PafReader::new(BufReader::new(file)));
+    }
+
+    merge_sorted(
+        &mut readers,
+        by,
+        |_, record| writer.write_record(&record),
+        |_, line_num| Error::Unsorted { line_num, by: by.to_string() },
+    )
+}
+
+
+/// K-way merge sorted readers, passing each record and the index of its
+/// reader to `emit` in order. Ties are taken from earlier readers first.
+///
+/// If a reader's records are out of order, returns the error built by
+/// `unsorted` from the reader's index and line number.
+#[cfg(feature = "fs")]
+pub(crate) fn merge_sorted<R, F, U>(
+    readers: &mut [PafReader<R>],
+    by: SortBy,
+    mut emit: F,
+    unsorted: U,
+) -> Result<(), Error>
+where
+    R: BufRead,
+    F: FnMut(usize, PAF) -> Result<(), Error>,
+    U: Fn(usize, usize) -> Error,
+{
+    let mut heap = BinaryHeap::with_capacity(readers.len());
+    for (run, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = reader.read_record()? {
+            heap.push(Head { record, run, by });
+        }
+    }
+
+    while let Some(Head { record, run, .. }) = heap.pop() {
+        let reader = &mut readers[run];
+        let next = reader.read_record()?;
+
+        if let Some(next) = next {
+            if by.compare(&record, &next) == Ordering::Greater {
+                return Err(unsorted(run, reader.line_num()));
+            }
+            heap.push(Head { record: next, run, by });
+        }
+
+        emit(run, record)?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "\
+q3\t100\t0\t100\t+\tt2\t1000\t50\t150\t90\t100\t60
+q1\t100\t0\t100\t+\tt1\t1000\t500\t600\t90\t100\t60
+q2\t100\t0\t100\t-\tt2\t1000\t10\t110\t90\t100\t60
+q4\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60
+q0\t100\t0\t100\t+\tt1\t1000\t500\t600\t90\t100\t60
+";
+
+    fn query_order(output: &[u8]) -> Vec<String> {
+        PafReader::new(output)
+            .map(|r| r.unwrap().query().name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_external_sort() {
+        let expected = vec!["q4", "q0", "q1", "q2", "q3"];
+
+        // In memory, and with a spill after every record.
+        for mem_limit in &[usize::MAX, 1] {
+            let mut output = Vec::new();
+            external_sort(INPUT.as_bytes(), &mut output, SortBy::Target, *mem_limit).unwrap();
+            assert_eq!(query_order(&output), expected);
+        }
+    }
+
+    #[test]
+    fn test_sort_by_query() {
+        let mut output = Vec::new();
+        external_sort(INPUT.as_bytes(), &mut output, SortBy::Query, 300).unwrap();
+        assert_eq!(query_order(&output), vec!["q0", "q1", "q2", "q3", "q4"]);
+    }
+
+    #[test]
+    fn test_multi_pass_merge() {
+        // One run per record, merged two at a time over several passes.
+        let mut runs = Vec::new();
+        for record in PafReader::new(INPUT.as_bytes()) {
+            runs.push(spill(&mut vec![record.unwrap()], SortBy::Target).unwrap());
+        }
+
+        let mut output = Vec::new();
+        let mut writer = PafWriter::new(&mut output);
+        merge_runs(runs, &mut writer, SortBy::Target, 2).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert_eq!(query_order(&output), vec!["q4", "q0", "q1", "q2", "q3"]);
+    }
+
+    #[test]
+    fn test_many_runs() {
+        // More runs than the fan-in limit.
+        let mut input = String::new();
+        for i in (0..150).rev() {
+            input.push_str(&format!("q{}\t100\t0\t100\t+\tt1\t1000\t{}\t{}\t90\t100\t60\n", i, i, i + 100));
+        }
+
+        let mut output = Vec::new();
+        external_sort(input.as_bytes(), &mut output, SortBy::Target, 1).unwrap();
+
+        let expected: Vec<String> = (0..150).map(|i| format!("q{}", i)).collect();
+        assert_eq!(query_order(&output), expected);
+    }
+}
@@ -0,0 +1,214 @@
+//! Demultiplex records into one file per sequence.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, OpenPath};
+use crate::paf::PAF;
+use crate::writer::PafWriter;
+
+
+/// Which sequence name to route records by.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub enum SplitBy {
+    /// One file per target sequence.
+    Target,
+    /// One file per query sequence.
+    Query,
+}
+
+
+/// Route records to per-sequence files in a directory.
+///
+/// Files are created lazily as names are first seen. At most `max_open`
+/// handles are kept open at once, and the least recently used handle is
+/// closed when another is needed. Files that are reopened are appended to.
+pub struct SplitWriter {
+    dir: PathBuf,
+    by: SplitBy,
+    extension: String,
+    max_open: usize,
+    tick: u64,
+    open: HashMap<String, (u64, PafWriter<BufWriter<File>>)>,
+    created: HashSet<PathBuf>,
+    paths: Vec<PathBuf>,
+}
+
+
+impl SplitWriter {
+    /// Construct a writer creating `<dir>/<name>.paf` files.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_open` is 0.
+    pub fn new<P: AsRef<Path>>(dir: P, by: SplitBy, max_open: usize) -> Self {
+        assert!(max_open > 0, "max_open must be at least 1");
+        SplitWriter {
+            dir: dir.as_ref().to_path_buf(),
+            by,
+            extension: "paf".to_string(),
+            max_open,
+            tick: 0,
+            open: HashMap::new(),
+            created: HashSet::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Use a different file extension, e.g. `"paf.txt"`.
+    pub fn with_extension(mut self, extension: &str) -> Self {
+        self.extension = extension.to_string();
+        self
+    }
+
+    /// The output path used for a sequence name.
+    ///
+    /// Path separators and NUL in names are written as `%` followed by two
+    /// hex digits, e.g. `%2F` for `/`, and `%` itself as `%25`, so distinct
+    /// names always get distinct files.
+    pub fn path_for(&self, name: &str) -> PathBuf {
+        let mut safe = String::with_capacity(name.len());
+        for c in name.chars() {
+            match c {
+                '%' | '/' | '\\' | '\0' => safe.push_str(&format!("%{:02X}", c as u32)),
+                c => safe.push(c),
+            }
+        }
+        self.dir.join(format!("{}.{}", safe, self.extension))
+    }
+
+    /// Write a record to the file for its query or target.
+    pub fn write_record(&mut self, record: &PAF) -> Result<(), Error> {
+        let name = match self.by {
+            SplitBy::Target => record.target().name(),
+            SplitBy::Query => record.query().name(),
+        };
+
+        self.tick += 1;
+        let tick = self.tick;
+
+        if !self.open.contains_key(name) {
+            if self.open.len() >= self.max_open {
+                self.close_oldest()?;
+            }
+
+            let writer = self.open_file(name)?;
+            self.open.insert(name.to_string(), (tick, writer));
+        }
+
+        let entry = self.open.get_mut(name).expect("handle was just opened");
+        entry.0 = tick;
+        entry.1.write_record(record)
+    }
+
+    /// Flush and close all files, returning the paths written in order of
+    /// creation.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, Error> {
+        for (_, (_, mut writer)) in self.open.drain() {
+            writer.flush()?;
+        }
+        Ok(self.paths)
+    }
+
+    fn open_file(&mut self, name: &str) -> Result<PafWriter<BufWriter<File>>, Error> {
+        let path = self.path_for(name);
+
+        let file = if self.created.contains(&path) {
+            OpenOptions::new().append(true).open(&path).context(OpenPath { path: &path })?
+        } else {
+            let file = File::create(&path).context(OpenPath { path: &path })?;
+            self.created.insert(path.clone());
+            self.paths.push(path);
+            file
+        };
+
+        Ok(PafWriter::new(BufWriter::new(file)))
+    }
+
+    fn close_oldest(&mut self) -> Result<(), Error> {
+        let oldest = self.open
+            .iter()
+            .min_by_key(|(_, (tick, _))| *tick)
+            .map(|(name, _)| name.clone());
+
+        if let Some(name) = oldest {
+            if let Some((_, mut writer)) = self.open.remove(&name) {
+                writer.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_to_string;
+
+    #[test]
+    fn test_split_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let lines = [
+            "q1\t100\t0\t100\t+\tchr1\t1000\t0\t100\t90\t100\t60",
+            "q2\t100\t0\t100\t+\tchr2\t1000\t0\t100\t90\t100\t60",
+            "q3\t100\t0\t100\t+\tchr3\t1000\t0\t100\t90\t100\t60",
+            "q4\t100\t0\t100\t+\tchr1\t1000\t0\t100\t90\t100\t60",
+        ];
+
+        let mut writer = SplitWriter::new(dir.path(), SplitBy::Target, 2);
+        for line in &lines {
+            writer.write_record(&line.parse().unwrap()).unwrap();
+        }
+        let paths = writer.finish().unwrap();
+
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], dir.path().join("chr1.paf"));
+        assert_eq!(
+            read_to_string(&paths[0]).unwrap(),
+            format!("{}\n{}\n", lines[0], lines[3])
+        );
+        assert_eq!(read_to_string(&paths[2]).unwrap(), format!("{}\n", lines[2]));
+    }
+
+    #[test]
+    fn test_split_writer_similar_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let lines = [
+            "q1\t100\t0\t100\t+\ta/b\t1000\t0\t100\t90\t100\t60",
+            "q2\t100\t0\t100\t+\ta_b\t1000\t0\t100\t90\t100\t60",
+            "q3\t100\t0\t100\t+\ta%2Fb\t1000\t0\t100\t90\t100\t60",
+        ];
+
+        let mut writer = SplitWriter::new(dir.path(), SplitBy::Target, 1);
+        for line in &lines {
+            writer.write_record(&line.parse().unwrap()).unwrap();
+        }
+        let paths = writer.finish().unwrap();
+
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], dir.path().join("a%2Fb.paf"));
+        assert_eq!(paths[2], dir.path().join("a%252Fb.paf"));
+        for (path, line) in paths.iter().zip(&lines) {
+            assert_eq!(read_to_string(path).unwrap(), format!("{}\n", line));
+        }
+    }
+
+    #[test]
+    fn test_split_writer_missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let record: PAF = "q1\t100\t0\t100\t+\tchr1\t1000\t0\t100\t90\t100\t60".parse().unwrap();
+
+        let mut writer = SplitWriter::new(&missing, SplitBy::Target, 1);
+        match writer.write_record(&record) {
+            Err(Error::OpenPath { path, .. }) => assert_eq!(path, missing.join("chr1.paf")),
+            r => panic!("expected an open error, got {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,786 @@
+//! Summary statistics over alignment records.
+
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::export::json_string;
+use crate::paf::PAF;
+use crate::record::PafRecord;
+use crate::set::covered_intervals;
+use crate::trim::trim_overlaps;
+
+
+/// The per-record quantity to summarise.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Metric {
+    /// The fraction of alignment columns that are matches.
+    Identity,
+    /// The `de` tag if present, otherwise one minus the identity.
+    Divergence,
+}
+
+
+impl Metric {
+    /// Compute the metric for a record.
+    pub fn value<R: PafRecord + ?Sized>(self, record: &R) -> f64 {
+        match self {
+            Metric::Identity => record.identity(),
+            Metric::Divergence => divergence(record),
+        }
+    }
+}
+
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Identity => write!(f, "identity"),
+            Metric::Divergence => write!(f, "divergence"),
+        }
+    }
+}
+
+
+/// The sequence divergence of a record.
+///
+/// Uses minimap2's gap-compressed divergence in the `de` tag when present,
+/// falling back to one minus [`PAF::identity`].
+pub fn divergence<R: PafRecord + ?Sized>(record: &R) -> f64 {
+    record.tag("de")
+        .and_then(|v| v.as_float())
+        .unwrap_or_else(|| 1.0 - record.identity())
+}
+
+
+/// A single bin of a [`Histogram`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Bin {
+    /// The inclusive lower bound of the bin.
+    pub start: f64,
+    /// The exclusive upper bound of the bin (inclusive for the last bin).
+    pub end: f64,
+    /// The number of records in the bin.
+    pub count: u64,
+    /// The total alignment length of records in the bin.
+    pub bases: u64,
+}
+
+
+/// A fixed-width histogram of identity or divergence over `[0, 1]`.
+///
+/// Displays as a tab separated table with a header row.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Histogram {
+    metric: Metric,
+    width: f64,
+    counts: Vec<u64>,
+    bases: Vec<u64>,
+}
+
+
+impl Histogram {
+    /// Construct an empty histogram with bins of `width`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is not in `(0, 1]`.
+    pub fn new(metric: Metric, width: f64) -> Self {
+        assert!(width > 0.0 && width <= 1.0, "Histogram bin width must be in (0, 1].");
+        let nbins = (1.0 / width).ceil() as usize;
+        Histogram { metric, width, counts: vec![0; nbins], bases: vec![0; nbins] }
+    }
+
+    /// The metric being summarised.
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Add a record to the histogram.
+    ///
+    /// Values outside `[0, 1]` are clamped into the first or last bin.
+    pub fn add<R: PafRecord + ?Sized>(&mut self, record: &R) {
+        let value = self.metric.value(record).clamp(0.0, 1.0);
+        let i = ((value / self.width) as usize).min(self.counts.len() - 1);
+        self.counts[i] += 1;
+        self.bases[i] += record.alnlen();
+    }
+
+    /// The bins in increasing order.
+    pub fn bins(&self) -> impl Iterator<Item = Bin> + '_ {
+        self.counts
+            .iter()
+            .zip(&self.bases)
+            .enumerate()
+            .map(move |(i, (count, bases))| Bin {
+                start: i as f64 * self.width,
+                end: ((i + 1) as f64 * self.width).min(1.0),
+                count: *count,
+                bases: *bases,
+            })
+    }
+}
+
+
+impl<'a, R: PafRecord + 'a> Extend<&'a R> for Histogram {
+    fn extend<I: IntoIterator<Item = &'a R>>(&mut self, iter: I) {
+        for record in iter {
+            self.add(record);
+        }
+    }
+}
+
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "metric\tbin_start\tbin_end\tcount\tbases")?;
+        for bin in self.bins() {
+            writeln!(f, "{}\t{}\t{}\t{}\t{}", self.metric, bin.start, bin.end, bin.count, bin.bases)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Aggregated divergence of the records aligned to one target.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TargetDivergence {
+    /// The number of records.
+    pub records: u64,
+    /// The total alignment length.
+    pub alnlen: u64,
+    /// The mean divergence weighted by alignment length.
+    pub divergence: f64,
+    weighted: f64,
+}
+
+
+/// Per-target divergence, ordered by target name.
+///
+/// Displays as a tab separated table with a header row.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DivergenceProfile {
+    targets: BTreeMap<String, TargetDivergence>,
+}
+
+
+impl DivergenceProfile {
+    /// Construct an empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a record to the profile.
+    pub fn add(&mut self, record: &PAF) {
+        let name = record.target().name();
+        if !self.targets.contains_key(name) {
+            self.targets.insert(name.to_string(), TargetDivergence::default());
+        }
+
+        let target = self.targets.get_mut(name).unwrap();
+        target.records += 1;
+        target.alnlen += record.alnlen();
+        target.weighted += divergence(record) * record.alnlen() as f64;
+        target.divergence = if target.alnlen == 0 { 0.0 } else { target.weighted / target.alnlen as f64 };
+    }
+
+    /// Get the aggregate for a single target.
+    pub fn get(&self, target: &str) -> Option<&TargetDivergence> {
+        self.targets.get(target)
+    }
+
+    /// Iterate over targets and their aggregates in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TargetDivergence)> {
+        self.targets.iter().map(|(k, v)| (k.as_str(), v))
+    }
+}
+
+
+impl<'a> Extend<&'a PAF> for DivergenceProfile {
+    fn extend<I: IntoIterator<Item = &'a PAF>>(&mut self, iter: I) {
+        for record in iter {
+            self.add(record);
+        }
+    }
+}
+
+
+impl fmt::Display for DivergenceProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "target\trecords\talnlen\tdivergence")?;
+        for (name, target) in self.iter() {
+            writeln!(f, "{}\t{}\t{}\t{}", name, target.records, target.alnlen, target.divergence)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// The MAPQ ranges reported by [`TargetStats`], inclusive.
+///
+/// A MAPQ of 255 means the value is missing, so it has its own bin.
+pub const MAPQ_BINS: [(u8, u8); 6] = [(0, 0), (1, 9), (10, 29), (30, 59), (60, 254), (255, 255)];
+
+
+/// Summary of the records aligned to one target.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TargetStats {
+    /// The target name.
+    pub target: String,
+    /// The number of records.
+    pub records: u64,
+    /// The number of target bases covered by at least one record.
+    pub covered_bases: u64,
+    /// Identity over all records, total matches over total alignment length.
+    pub identity: f64,
+    /// The number of records in each of [`MAPQ_BINS`].
+    pub mapq_counts: [u64; 6],
+}
+
+
+/// Summarise records by target, ordered by target name.
+pub fn target_stats(records: &[PAF]) -> Vec<TargetStats> {
+    let covered = covered_intervals(records);
+    let mut totals: BTreeMap<&str, (u64, u64, u64, [u64; 6])> = BTreeMap::new();
+
+    for record in records {
+        let entry = totals.entry(record.target().name()).or_default();
+        entry.0 += 1;
+        entry.1 += record.nmatch();
+        entry.2 += record.alnlen();
+
+        let bin = MAPQ_BINS
+            .iter()
+            .position(|(lo, hi)| (*lo..=*hi).contains(&record.mapq()))
+            .expect("MAPQ bins cover every u8.");
+        entry.3[bin] += 1;
+    }
+
+    totals
+        .into_iter()
+        .map(|(name, (count, nmatch, alnlen, mapq_counts))| TargetStats {
+            target: name.to_string(),
+            records: count,
+            covered_bases: covered
+                .get(name)
+                .map_or(0, |spans| spans.iter().map(|(s, e)| e - s).sum()),
+            identity: if alnlen == 0 { 0.0 } else { nmatch as f64 / alnlen as f64 },
+            mapq_counts,
+        })
+        .collect()
+}
+
+
+/// The output format for summary tables.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum TableFormat {
+    /// Tab separated values with a header row.
+    Tsv,
+    /// JSON Lines, one object per row.
+    Json,
+}
+
+
+/// Names for the MAPQ bins used as column headers.
+fn mapq_bin_name(bin: (u8, u8)) -> String {
+    match bin {
+        (255, 255) => "mapq_missing".to_string(),
+        (lo, hi) if lo == hi => format!("mapq_{}", lo),
+        (lo, 254) => format!("mapq_{}_plus", lo),
+        (lo, hi) => format!("mapq_{}_{}", lo, hi),
+    }
+}
+
+
+/// Write a per-target summary table.
+pub fn write_target_stats<W: Write>(
+    stats: &[TargetStats],
+    format: TableFormat,
+    mut writer: W,
+) -> Result<(), Error> {
+    let mapq_names: Vec<String> = MAPQ_BINS.iter().map(|b| mapq_bin_name(*b)).collect();
+
+    if format == TableFormat::Tsv {
+        writeln!(writer, "target\trecords\tcovered_bases\tidentity\t{}", mapq_names.join("\t")).context(Io)?;
+    }
+
+    for row in stats {
+        let line = match format {
+            TableFormat::Tsv => {
+                let mapqs: Vec<String> = row.mapq_counts.iter().map(|c| c.to_string()).collect();
+                format!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    row.target, row.records, row.covered_bases, row.identity, mapqs.join("\t")
+                )
+            },
+            TableFormat::Json => {
+                let mapqs: Vec<String> = mapq_names
+                    .iter()
+                    .zip(&row.mapq_counts)
+                    .map(|(name, count)| format!("{}:{}", json_string(name), count))
+                    .collect();
+                format!(
+                    "{{\"target\":{},\"records\":{},\"covered_bases\":{},\"identity\":{},{}}}",
+                    json_string(&row.target),
+                    row.records,
+                    row.covered_bases,
+                    row.identity,
+                    mapqs.join(",")
+                )
+            },
+        };
+        writeln!(writer, "{}", line).context(Io)?;
+    }
+
+    Ok(())
+}
+
+
+/// Read-level alignment summary for one query, from its primary records.
+#[derive(Debug, PartialEq, Clone)]
+pub struct QueryStats {
+    /// The query name.
+    pub query: String,
+    /// The query length.
+    pub length: u64,
+    /// The number of query bases covered by at least one primary record.
+    pub aligned_bases: u64,
+    /// The number of primary records, more than one for split alignments.
+    pub pieces: u64,
+    /// The longer of the unaligned prefix and suffix of the query.
+    pub max_terminal_unaligned: u64,
+}
+
+
+impl QueryStats {
+    /// Summarise all records of one query, e.g. a group from
+    /// [`crate::iter::PafIterExt::group_by_query`].
+    ///
+    /// Returns `None` for an empty group. A query with no primary records
+    /// has no aligned bases and is entirely unaligned.
+    pub fn from_group<R: Borrow<PAF>>(group: &[R]) -> Option<QueryStats> {
+        let first = group.first()?.borrow().query();
+
+        let mut spans: Vec<(u64, u64)> = group
+            .iter()
+            .map(|r| r.borrow())
+            .filter(|r| r.is_primary())
+            .map(|r| (r.query().start(), r.query().end()))
+            .collect();
+        spans.sort_unstable();
+
+        let pieces = spans.len() as u64;
+        let (mut aligned_bases, mut reach) = (0, 0);
+        for &(start, end) in &spans {
+            let start = start.max(reach);
+            if end > start {
+                aligned_bases += end - start;
+                reach = end;
+            }
+        }
+
+        let max_terminal_unaligned = match (spans.first(), spans.iter().map(|s| s.1).max()) {
+            (Some(&(first_start, _)), Some(last_end)) => {
+                first_start.max(first.length().saturating_sub(last_end))
+            },
+            _ => first.length(),
+        };
+
+        Some(QueryStats {
+            query: first.name().to_string(),
+            length: first.length(),
+            aligned_bases,
+            pieces,
+            max_terminal_unaligned,
+        })
+    }
+
+    /// The fraction of the query covered by primary records.
+    pub fn fraction_aligned(&self) -> f64 {
+        if self.length == 0 {
+            0.0
+        } else {
+            self.aligned_bases as f64 / self.length as f64
+        }
+    }
+}
+
+
+/// Totals of [`QueryStats`] across a file.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct QuerySummary {
+    /// The number of queries.
+    pub queries: u64,
+    /// The number of queries with more than one primary record.
+    pub split_queries: u64,
+    /// The total length of the queries.
+    pub bases: u64,
+    /// The total number of query bases covered by primary records.
+    pub aligned_bases: u64,
+    /// The sum of the per-query aligned fractions.
+    fraction_sum: f64,
+    /// The largest unaligned end of any query.
+    pub max_terminal_unaligned: u64,
+}
+
+
+impl QuerySummary {
+    /// Construct an empty summary.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a query.
+    pub fn add(&mut self, stats: &QueryStats) {
+        self.queries += 1;
+        if stats.pieces > 1 {
+            self.split_queries += 1;
+        }
+        self.bases += stats.length;
+        self.aligned_bases += stats.aligned_bases;
+        self.fraction_sum += stats.fraction_aligned();
+        self.max_terminal_unaligned = self.max_terminal_unaligned.max(stats.max_terminal_unaligned);
+    }
+
+    /// The mean of the per-query aligned fractions.
+    pub fn mean_fraction_aligned(&self) -> f64 {
+        if self.queries == 0 {
+            0.0
+        } else {
+            self.fraction_sum / self.queries as f64
+        }
+    }
+}
+
+
+impl<'a> Extend<&'a QueryStats> for QuerySummary {
+    fn extend<I: IntoIterator<Item = &'a QueryStats>>(&mut self, iter: I) {
+        for stats in iter {
+            self.add(stats);
+        }
+    }
+}
+
+
+impl fmt::Display for QuerySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "queries\t{}", self.queries)?;
+        writeln!(f, "split_queries\t{}", self.split_queries)?;
+        writeln!(f, "bases\t{}", self.bases)?;
+        writeln!(f, "aligned_bases\t{}", self.aligned_bases)?;
+        writeln!(f, "mean_fraction_aligned\t{}", self.mean_fraction_aligned())?;
+        writeln!(f, "max_terminal_unaligned\t{}", self.max_terminal_unaligned)
+    }
+}
+
+
+/// The distribution of a set of lengths, with assembly-style N50 metrics.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct Stats {
+    /// Sorted longest first.
+    lengths: Vec<u64>,
+    total: u64,
+}
+
+
+impl Stats {
+    /// Summarise a set of lengths. Zero lengths are ignored.
+    pub fn new<I: IntoIterator<Item = u64>>(lengths: I) -> Self {
+        let mut lengths: Vec<u64> = lengths.into_iter().filter(|l| *l > 0).collect();
+        lengths.sort_unstable_by(|a, b| b.cmp(a));
+        let total = lengths.iter().sum();
+        Stats { lengths, total }
+    }
+
+    /// The alignment block lengths of the records.
+    pub fn alignment_lengths<R: PafRecord>(records: &[R]) -> Self {
+        Self::new(records.iter().map(|r| r.alnlen()))
+    }
+
+    /// The lengths of the merged target intervals covered by the records.
+    pub fn covered_regions(records: &[PAF]) -> Self {
+        Self::new(
+            covered_intervals(records)
+                .values()
+                .flat_map(|spans| spans.iter().map(|(s, e)| e - s))
+        )
+    }
+
+    /// The number of lengths.
+    pub fn count(&self) -> usize {
+        self.lengths.len()
+    }
+
+    /// The sum of the lengths.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The largest length.
+    pub fn max(&self) -> Option<u64> {
+        self.lengths.first().copied()
+    }
+
+    /// The length `L` such that lengths of at least `L` make up `x` percent
+    /// of the total. Returns `None` if there are no lengths or `x` is
+    /// outside `0..=100`.
+    pub fn nx(&self, x: f64) -> Option<u64> {
+        if !(0.0..=100.0).contains(&x) {
+            return None;
+        }
+
+        let target = self.total as f64 * x / 100.0;
+        let mut sum = 0;
+        for &length in &self.lengths {
+            sum += length;
+            if sum as f64 >= target {
+                return Some(length);
+            }
+        }
+        None
+    }
+
+    /// The N50.
+    pub fn n50(&self) -> Option<u64> {
+        self.nx(50.0)
+    }
+
+    /// The area under the Nx curve, the sum of squared lengths over the
+    /// total. Unlike the N50 this changes smoothly as lengths change.
+    pub fn aun(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let squares: f64 = self.lengths.iter().map(|&l| l as f64 * l as f64).sum();
+        squares / self.total as f64
+    }
+
+    /// The Nx curve at each whole percentage from 0 to 100.
+    pub fn nx_curve(&self) -> Vec<(u8, u64)> {
+        (0..=100u8)
+            .filter_map(|x| self.nx(x as f64).map(|l| (x, l)))
+            .collect()
+    }
+}
+
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "count\t{}", self.count())?;
+        writeln!(f, "total\t{}", self.total)?;
+        writeln!(f, "max\t{}", self.max().unwrap_or(0))?;
+        writeln!(f, "n50\t{}", self.n50().unwrap_or(0))?;
+        writeln!(f, "aun\t{}", self.aun())
+    }
+}
+
+
+/// An average nucleotide identity estimate, see [`ani`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Ani {
+    /// The identity of all blocks together, weighting each by its length.
+    pub ani: f64,
+    /// The unweighted mean identity of the blocks.
+    pub mean_identity: f64,
+    /// The number of one-to-one blocks.
+    pub blocks: usize,
+    /// The total alignment length of the blocks.
+    pub aligned_bases: u64,
+}
+
+
+/// Estimate the average nucleotide identity of two genomes from their
+/// whole-genome alignments.
+///
+/// Only primary alignments are used, trimmed with [`trim_overlaps`] on the
+/// target and then on the query, so each base of either genome is counted
+/// in at most one block. Records without a `cg` tag cannot be trimmed and
+/// are kept whole. Returns `None` if there are no blocks with a non-zero
+/// alignment length.
+pub fn ani(records: &[PAF]) -> Option<Ani> {
+    ani_of_blocks(&one_to_one_blocks(records))
+}
+
+
+/// The primary records trimmed so each base of either genome is in at most
+/// one block, see [`ani`].
+///
+/// Blocks are returned with query and target swapped.
+pub(crate) fn one_to_one_blocks(records: &[PAF]) -> Vec<PAF> {
+    let primary: Vec<PAF> = records.iter().filter(|r| r.is_primary()).cloned().collect();
+    let inverted: Vec<PAF> = trim_overlaps(&primary).iter().map(|r| r.invert()).collect();
+    trim_overlaps(&inverted)
+        .into_iter()
+        .filter(|r| r.alnlen() > 0)
+        .collect()
+}
+
+
+/// Summarise blocks from [`one_to_one_blocks`].
+pub(crate) fn ani_of_blocks(blocks: &[PAF]) -> Option<Ani> {
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let nmatch: u64 = blocks.iter().map(|r| r.nmatch()).sum();
+    let aligned_bases: u64 = blocks.iter().map(|r| r.alnlen()).sum();
+    let identity_sum: f64 = blocks.iter().map(|r| r.identity()).sum();
+
+    Some(Ani {
+        ani: nmatch as f64 / aligned_bases as f64,
+        mean_identity: identity_sum / blocks.len() as f64,
+        blocks: blocks.len(),
+        aligned_bases,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_histogram() {
+        let records = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t95\t100\t60"),
+            paf("q2\t100\t0\t100\t+\tt1\t1000\t0\t100\t100\t100\t60"),
+            paf("q3\t100\t0\t100\t+\tt2\t1000\t0\t100\t50\t100\t60\tde:f:0.2"),
+        ];
+
+        let mut hist = Histogram::new(Metric::Identity, 0.1);
+        hist.extend(&records);
+        let counts: Vec<u64> = hist.bins().map(|b| b.count).collect();
+        assert_eq!(counts, vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 2]);
+
+        let mut hist = Histogram::new(Metric::Divergence, 0.5);
+        hist.extend(&records);
+        assert_eq!(hist.to_string(), "metric\tbin_start\tbin_end\tcount\tbases\n\
+            divergence\t0\t0.5\t3\t300\n\
+            divergence\t0.5\t1\t0\t0\n");
+    }
+
+    #[test]
+    fn test_divergence_profile() {
+        let records = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60"),
+            paf("q2\t300\t0\t300\t+\tt1\t1000\t0\t300\t300\t300\t60"),
+            paf("q3\t100\t0\t100\t+\tt2\t1000\t0\t100\t50\t100\t60\tde:f:0.2"),
+        ];
+
+        let mut profile = DivergenceProfile::new();
+        profile.extend(&records);
+
+        let t1 = profile.get("t1").unwrap();
+        assert_eq!(t1.records, 2);
+        assert_eq!(t1.alnlen, 400);
+        assert!((t1.divergence - 0.025).abs() < 1e-9);
+        assert!((profile.get("t2").unwrap().divergence - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_target_stats() {
+        let records = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60"),
+            paf("q2\t100\t0\t100\t+\tt1\t1000\t50\t150\t100\t100\t0"),
+            paf("q3\t100\t0\t100\t+\tt2\t1000\t0\t100\t50\t100\t255"),
+        ];
+
+        let stats = target_stats(&records);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].covered_bases, 150);
+        assert!((stats[0].identity - 0.95).abs() < 1e-9);
+        assert_eq!(stats[0].mapq_counts, [1, 0, 0, 0, 1, 0]);
+
+        let mut tsv = Vec::new();
+        write_target_stats(&stats[1..], TableFormat::Tsv, &mut tsv).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "target\trecords\tcovered_bases\tidentity\tmapq_0\tmapq_1_9\tmapq_10_29\tmapq_30_59\tmapq_60_plus\tmapq_missing\n\
+             t2\t1\t100\t0.5\t0\t0\t0\t0\t0\t1\n"
+        );
+
+        let mut json = Vec::new();
+        write_target_stats(&stats[1..], TableFormat::Json, &mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\"target\":\"t2\",\"records\":1,\"covered_bases\":100,\"identity\":0.5,\
+             \"mapq_0\":0,\"mapq_1_9\":0,\"mapq_10_29\":0,\"mapq_30_59\":0,\"mapq_60_plus\":0,\"mapq_missing\":1}\n"
+        );
+    }
+
+    #[test]
+    fn test_query_stats() {
+        use crate::iter::PafIterExt;
+
+        let records = vec![
+            paf("q1\t1000\t100\t600\t+\tt1\t5000\t0\t500\t450\t500\t60\ttp:A:P"),
+            paf("q1\t1000\t550\t900\t+\tt2\t5000\t0\t350\t300\t350\t60\ttp:A:P"),
+            paf("q1\t1000\t0\t1000\t+\tt3\t5000\t0\t1000\t500\t1000\t0\ttp:A:S"),
+            paf("q2\t500\t0\t500\t-\tt1\t5000\t0\t500\t500\t500\t60"),
+        ];
+
+        let stats: Vec<QueryStats> = records
+            .into_iter()
+            .group_by_query()
+            .filter_map(|g| QueryStats::from_group(&g))
+            .collect();
+
+        assert_eq!(stats[0].aligned_bases, 800);
+        assert_eq!(stats[0].pieces, 2);
+        assert_eq!(stats[0].max_terminal_unaligned, 100);
+        assert_eq!(stats[1].fraction_aligned(), 1.0);
+
+        let mut summary = QuerySummary::new();
+        summary.extend(&stats);
+        assert_eq!(summary.split_queries, 1);
+        assert_eq!(summary.aligned_bases, 1300);
+        assert!((summary.mean_fraction_aligned() - 0.9).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_stats_n50() {
+        let stats = Stats::new(vec![2, 3, 0, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(stats.count(), 9);
+        assert_eq!(stats.total(), 54);
+        assert_eq!(stats.n50(), Some(8));
+        assert_eq!(stats.nx(0.0), Some(10));
+        assert_eq!(stats.nx(100.0), Some(2));
+        assert_eq!(stats.nx(101.0), None);
+        assert!((stats.aun() - 384.0 / 54.0).abs() < 1e-12);
+        assert_eq!(stats.nx_curve().len(), 101);
+
+        let records = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60"),
+            paf("q2\t100\t0\t100\t+\tt1\t1000\t50\t150\t90\t120\t60"),
+        ];
+        assert_eq!(Stats::alignment_lengths(&records).max(), Some(120));
+        assert_eq!(Stats::covered_regions(&records).n50(), Some(150));
+        assert_eq!(Stats::new(vec![]).n50(), None);
+    }
+
+    #[test]
+    fn test_ani() {
+        let records = vec![
+            paf("q1\t1000\t0\t1000\t+\tt1\t1000\t0\t1000\t990\t1000\t60\tcg:Z:1000M"),
+            // Covered on the query by the better first record, so dropped.
+            paf("q1\t1000\t900\t1000\t+\tt2\t500\t0\t100\t80\t100\t60\tcg:Z:100M"),
+            paf("q2\t500\t0\t100\t+\tt2\t500\t300\t400\t80\t100\t60\tcg:Z:100M"),
+            paf("q2\t500\t0\t100\t+\tt1\t1000\t0\t100\t100\t100\t0\ttp:A:S"),
+        ];
+
+        let ani = ani(&records).unwrap();
+        assert_eq!(ani.blocks, 2);
+        assert_eq!(ani.aligned_bases, 1100);
+        assert!((ani.ani - 1070.0 / 1100.0).abs() < 1e-12);
+        assert!((ani.mean_identity - (0.99 + 0.8) / 2.0).abs() < 1e-12);
+        assert_eq!(super::ani(&records[3..]), None);
+    }
+}
@@ -0,0 +1,263 @@
+//! Incremental parsers for PAF arriving in arbitrary chunks.
+//!
+//! Unlike the parsers in [`crate::complete`], these return
+//! `nom::Err::Incomplete` when the input ends part way through a record,
+//! rather than an error. A record is only complete once its line ending
+//! has been seen. [`PafStream`] wraps them to parse records from chunks
+//! read from a socket or pipe.
+
+use nom::{IResult, Needed};
+use nom::character::complete::{line_ending, tab};
+use nom::combinator::{all_consuming, opt};
+use nom::error::{ParseError, VerboseError};
+use nom::sequence::{pair, terminated};
+
+use crate::complete::Recovered;
+use crate::errors::{convert_error_u8, Error};
+use crate::paf::PAF;
+
+
+/// Parse a byte string as a paf line, including its line ending.
+///
+/// The columns are parsed as by [`crate::complete::paf_u8`], once the whole
+/// line has arrived. A tab before the line ending is allowed. Returns
+/// `nom::Err::Incomplete` if the input ends before the line ending.
+pub fn paf_u8<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], PAF, E>
+where
+    E: ParseError<&'a [u8]>
+{
+    let end = match i.iter().position(|b| *b == b'\n') {
+        Some(p) => p + 1,
+        None => return Err(nom::Err::Incomplete(Needed::Size(1))),
+    };
+
+    let (_, record) = all_consuming(terminated(
+        crate::complete::paf_u8,
+        pair(opt(tab), opt(line_ending))
+    ))(&i[..end])?;
+    Ok((&i[end..], record))
+}
+
+
+/// Skip the rest of a line and its line ending, returning the number of
+/// bytes skipped.
+///
+/// Returns `nom::Err::Incomplete` if the line ending has not arrived yet.
+pub fn skip_line_u8<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], usize, E>
+where
+    E: ParseError<&'a [u8]>
+{
+    match i.iter().position(|b| *b == b'\n') {
+        Some(p) => Ok((&i[p + 1..], p + 1)),
+        None => Err(nom::Err::Incomplete(Needed::Size(1))),
+    }
+}
+
+
+/// Apply `parser`, or on an error skip to the start of the next line.
+///
+/// The error is returned with the number of bytes skipped, counted from
+/// the start of the failed line. Like [`skip_line_u8`], this returns
+/// `nom::Err::Incomplete` until the whole failed line has arrived.
+pub fn recover_line_u8<'a, O, E, F>(parser: F)
+    -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Recovered<O, E>, E>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], O, E>,
+    E: ParseError<&'a [u8]>
+{
+    move |i: &'a [u8]| match parser(i) {
+        Ok((rest, value)) => Ok((rest, Ok(value))),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            let (rest, n) = skip_line_u8(i)?;
+            Ok((rest, Err((e, n))))
+        },
+        Err(nom::Err::Incomplete(needed)) => Err(nom::Err::Incomplete(needed)),
+    }
+}
+
+
+/// Parse records from input that arrives in chunks.
+///
+/// Only the unparsed tail of the input is retained between calls, and each
+/// byte is scanned for a line ending once. Blank lines are skipped, and
+/// lines starting with `#` are kept as comments, as by
+/// [`PafReader`](crate::PafReader). Optional fields are not validated as
+/// tags.
+#[derive(Debug, Clone, Default)]
+pub struct PafStream {
+    buffer: Vec<u8>,
+    /// The start of the unparsed input in `buffer`.
+    pos: usize,
+    /// Where to continue looking for the end of the current line.
+    scanned: usize,
+    line_num: usize,
+    skipped: u64,
+    comments: Vec<String>,
+}
+
+
+impl PafStream {
+    /// Construct an empty stream.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a chunk of input.
+    pub fn push(&mut self, chunk: &[u8]) {
+        // Parsed input is only dropped once it is most of the buffer, so
+        // the cost of moving the tail is paid for by the bytes parsed.
+        if self.pos > self.buffer.len() / 2 {
+            self.buffer.drain(..self.pos);
+            self.scanned -= self.pos;
+            self.pos = 0;
+        }
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// The number of lines consumed so far.
+    pub fn line_num(&self) -> usize {
+        self.line_num
+    }
+
+    /// The number of bytes discarded with malformed lines so far.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// The number of bytes received but not yet parsed.
+    pub fn pending(&self) -> usize {
+        self.buffer.len() - self.pos
+    }
+
+    /// The comment lines read so far, including the leading `#`.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Parse the next complete record.
+    ///
+    /// Returns `None` if more input is needed. On an error the offending
+    /// line is discarded so that parsing can continue, see [`skipped`].
+    ///
+    /// [`skipped`]: PafStream::skipped
+    pub fn next_record(&mut self) -> Option<Result<PAF, Error>> {
+        loop {
+            let end = match self.buffer[self.scanned..].iter().position(|b| *b == b'\n') {
+                Some(p) => self.scanned + p + 1,
+                None => {
+                    self.scanned = self.buffer.len();
+                    return None;
+                },
+            };
+
+            let line = &self.buffer[self.pos..end];
+            self.pos = end;
+            self.scanned = end;
+            self.line_num += 1;
+
+            let content = line.strip_suffix(b"\n").unwrap_or(line);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            if content.is_empty() {
+                continue;
+            }
+            if content.starts_with(b"#") {
+                self.comments.push(String::from_utf8_lossy(content).into_owned());
+                continue;
+            }
+
+            return match paf_u8::<VerboseError<&[u8]>>(line) {
+                Ok((_, record)) => Some(Ok(record)),
+                Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                    self.skipped += line.len() as u64;
+                    Some(Err(convert_error_u8(line, e, Some(self.line_num))))
+                },
+                Err(nom::Err::Incomplete(_)) => unreachable!("the line ending has been found"),
+            };
+        }
+    }
+
+    /// Parse the final record once the input has ended.
+    ///
+    /// This accepts a last line without a line ending. Returns `None` if
+    /// nothing remains.
+    pub fn finish(&mut self) -> Option<Result<PAF, Error>> {
+        if self.pending() > 0 && !self.buffer.ends_with(b"\n") {
+            self.buffer.push(b'\n');
+        }
+        self.next_record()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incomplete() {
+        let line = b"q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P\n";
+        for i in 0..line.len() {
+            match paf_u8::<VerboseError<&[u8]>>(&line[..i]) {
+                Err(nom::Err::Incomplete(_)) => {},
+                r => panic!("Expected incomplete at {}, got {:?}", i, r),
+            }
+        }
+        assert!(paf_u8::<VerboseError<&[u8]>>(line).is_ok());
+    }
+
+    #[test]
+    fn test_stream_chunks() {
+        let input = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P\r\n\
+                     \n\
+                     q2\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t0\n\
+                     q3\t10\t0\tx\t-\tt1\t20\t5\t15\t9\t10\t0\n\
+                     q4\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t0";
+
+        let mut stream = PafStream::new();
+        let mut results = Vec::new();
+        for chunk in input.as_bytes().chunks(5) {
+            stream.push(chunk);
+            while let Some(result) = stream.next_record() {
+                results.push(result);
+            }
+        }
+        while let Some(result) = stream.finish() {
+            results.push(result);
+        }
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().query().name(), "q1");
+        assert_eq!(results[1].as_ref().unwrap().query().name(), "q2");
+        let error = results[2].as_ref().unwrap_err();
+        assert_eq!(error.line_number(), Some(4));
+        assert_eq!(error.line(), Some("q3\t10\t0\tx\t-\tt1\t20\t5\t15\t9\t10\t0"));
+        assert_eq!(error.column(), Some(8));
+        assert_eq!(results[3].as_ref().unwrap().query().name(), "q4");
+        assert_eq!(stream.pending(), 0);
+        assert_eq!(stream.skipped(), 30);
+    }
+
+    #[test]
+    fn test_stream_comments_and_long_lines() {
+        let cigar = "10M1I".repeat(1000) + "10M";
+        let input = format!(
+            "#PG\tminimap2\nq1\t11001\t0\t11001\t+\tt1\t20000\t0\t10010\t10010\t11001\t60\tcg:Z:{}\t\n",
+            cigar
+        );
+
+        let mut stream = PafStream::new();
+        let mut records = Vec::new();
+        for byte in input.as_bytes() {
+            stream.push(&[*byte]);
+            while let Some(result) = stream.next_record() {
+                records.push(result.unwrap());
+            }
+        }
+
+        assert_eq!(stream.comments(), &["#PG\tminimap2".to_string()][..]);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].cigar().unwrap().to_string(), cigar);
+        assert_eq!(stream.line_num(), 2);
+        assert_eq!(stream.pending(), 0);
+    }
+}
@@ -0,0 +1,228 @@
+//! Candidate structural variants from long indels and split alignments.
+//!
+//! Insertions and deletions come from single alignments, as long `I` and
+//! `D` operations in the CIGAR, and from consecutive alignments of a query
+//! that are collinear on the target but with a gap of different length on
+//! each sequence. Consecutive alignments on a different target or strand
+//! are reported as breakpoints. Candidates of the same kind close together
+//! on a target are clustered, with the support counting the observations.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cigar::CigarKind;
+use crate::paf::{Strand, PAF};
+
+
+/// The kind of a candidate structural variant.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash, Ord, PartialOrd)]
+pub enum SvKind {
+    /// Sequence present in the query but not the target.
+    Insertion,
+    /// Sequence present in the target but not the query.
+    Deletion,
+    /// Adjacent parts of a query aligned to a different target or strand.
+    Breakpoint,
+}
+
+
+impl fmt::Display for SvKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvKind::Insertion => write!(f, "INS"),
+            SvKind::Deletion => write!(f, "DEL"),
+            SvKind::Breakpoint => write!(f, "BND"),
+        }
+    }
+}
+
+
+/// A candidate structural variant on a target sequence.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SvCandidate {
+    /// The kind of variant.
+    pub kind: SvKind,
+    /// The name of the target sequence the variant is on.
+    pub target: String,
+    /// The 0-based target position, the start of a deletion.
+    pub position: u64,
+    /// The length of the inserted or deleted sequence, 0 for breakpoints.
+    pub size: u64,
+    /// The number of observations clustered into this candidate.
+    pub support: u64,
+}
+
+
+/// Writes the candidate as a tab separated line, without a line ending.
+impl fmt::Display for SvCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}\t{}\t{}", self.target, self.position, self.kind, self.size, self.support)
+    }
+}
+
+
+/// Find candidate structural variants of at least `min_size` bases.
+///
+/// Indels are read from the `cg` tag, or the `cs` tag if there is no
+/// CIGAR. Split alignments use primary records only. Observations of the
+/// same kind within `max_distance` of each other on a target are merged,
+/// taking the median size. Results are sorted by target and position.
+pub fn find_svs(records: &[PAF], min_size: u64, max_distance: u64) -> Vec<SvCandidate> {
+    let mut observations: BTreeMap<(&str, SvKind), Vec<(u64, u64)>> = BTreeMap::new();
+
+    for record in records {
+        indels(record, min_size, |kind, position, size| {
+            observations.entry((record.target().name(), kind)).or_default().push((position, size));
+        });
+    }
+
+    let mut by_query: BTreeMap<&str, Vec<&PAF>> = BTreeMap::new();
+    for record in records.iter().filter(|r| r.is_primary()) {
+        by_query.entry(record.query().name()).or_default().push(record);
+    }
+    for (_, mut group) in by_query {
+        group.sort_by_key(|r| (r.query().start(), r.query().end()));
+        for pair in group.windows(2) {
+            if let Some((kind, position, size)) = junction(pair[0], pair[1], min_size) {
+                observations.entry((pair[0].target().name(), kind)).or_default().push((position, size));
+            }
+        }
+    }
+
+    let mut output = Vec::new();
+    for ((target, kind), mut points) in observations {
+        points.sort_unstable();
+        let mut cluster: Vec<(u64, u64)> = Vec::new();
+        for point in points {
+            if let Some(last) = cluster.last() {
+                if point.0 - last.0 > max_distance {
+                    output.push(summarise(target, kind, &mut cluster));
+                }
+            }
+            cluster.push(point);
+        }
+        if !cluster.is_empty() {
+            output.push(summarise(target, kind, &mut cluster));
+        }
+    }
+
+    output.sort_by(|a, b| {
+        a.target.cmp(&b.target)
+            .then(a.position.cmp(&b.position))
+            .then(a.kind.cmp(&b.kind))
+    });
+    output
+}
+
+
+/// Call `emit` with the kind, target position and size of each long indel.
+fn indels<F: FnMut(SvKind, u64, u64)>(record: &PAF, min_size: u64, mut emit: F) {
+    let cigar = match record.cigar().or_else(|| record.cs().map(|cs| cs.to_cigar(false))) {
+        Some(c) => c,
+        None => return,
+    };
+
+    let mut pos = record.target().start();
+    for op in cigar.ops() {
+        if op.len() >= min_size {
+            match op.kind() {
+                CigarKind::Insertion => emit(SvKind::Insertion, pos, op.len()),
+                CigarKind::Deletion => emit(SvKind::Deletion, pos, op.len()),
+                _ => {},
+            }
+        }
+        if op.kind().consumes_target() {
+            pos += op.len();
+        }
+    }
+}
+
+
+/// Classify the junction between alignments adjacent along the query.
+fn junction(a: &PAF, b: &PAF, min_size: u64) -> Option<(SvKind, u64, u64)> {
+    // The end of `a` on the target, in the direction of the query.
+    let a_end = match a.strand() {
+        Strand::Minus => a.target().start(),
+        _ => a.target().end(),
+    };
+
+    if a.target().name() != b.target().name() || a.strand() != b.strand() {
+        return Some((SvKind::Breakpoint, a_end, 0));
+    }
+
+    let query_gap = b.query().start() as i64 - a.query().end() as i64;
+    let target_gap = match a.strand() {
+        Strand::Minus => a.target().start() as i64 - b.target().end() as i64,
+        _ => b.target().start() as i64 - a.target().end() as i64,
+    };
+
+    // Out of order on the target, e.g. a duplication or transposition.
+    if target_gap < -(min_size as i64) {
+        return Some((SvKind::Breakpoint, a_end, 0));
+    }
+
+    let diff = target_gap - query_gap;
+    let position = match a.strand() {
+        Strand::Minus => b.target().end(),
+        _ => a.target().end(),
+    };
+
+    match diff.cmp(&0) {
+        Ordering::Greater if diff as u64 >= min_size => Some((SvKind::Deletion, position, diff as u64)),
+        Ordering::Less if diff.unsigned_abs() >= min_size => {
+            Some((SvKind::Insertion, position, diff.unsigned_abs()))
+        },
+        _ => None,
+    }
+}
+
+
+/// Merge a cluster of observations into one candidate, emptying it.
+fn summarise(target: &str, kind: SvKind, cluster: &mut Vec<(u64, u64)>) -> SvCandidate {
+    let mut sizes: Vec<u64> = cluster.iter().map(|p| p.1).collect();
+    sizes.sort_unstable();
+
+    let candidate = SvCandidate {
+        kind,
+        target: target.to_string(),
+        position: cluster[0].0,
+        size: sizes[sizes.len() / 2],
+        support: cluster.len() as u64,
+    };
+    cluster.clear();
+    candidate
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_find_svs() {
+        let records = vec![
+            // A 100 base deletion within one alignment, seen twice.
+            paf("q1\t1000\t0\t300\t+\tt1\t5000\t1000\t1400\t300\t400\t60\tcg:Z:150M100D150M"),
+            paf("q2\t1000\t0\t300\t+\tt1\t5000\t1010\t1410\t300\t400\t60\tcg:Z:140M102D160M"),
+            // A 500 base deletion between split alignments.
+            paf("q3\t1000\t0\t400\t+\tt1\t5000\t2000\t2400\t400\t400\t60"),
+            paf("q3\t1000\t400\t800\t+\tt1\t5000\t2900\t3300\t400\t400\t60"),
+            // An inversion breakpoint.
+            paf("q4\t1000\t0\t400\t+\tt2\t5000\t0\t400\t400\t400\t60"),
+            paf("q4\t1000\t400\t800\t-\tt2\t5000\t400\t800\t400\t400\t60"),
+        ];
+
+        let svs = find_svs(&records, 50, 20);
+        let lines: Vec<String> = svs.iter().map(|s| s.to_string()).collect();
+        assert_eq!(lines, vec![
+            "t1\t1150\tDEL\t102\t2",
+            "t1\t2400\tDEL\t500\t1",
+            "t2\t400\tBND\t0\t1",
+        ]);
+    }
+}
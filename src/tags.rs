@@ -0,0 +1,330 @@
+use core::fmt;
+use core::str::FromStr;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use nom::combinator::{cut, all_consuming};
+
+use crate::errors::Error;
+
+
+/// The typed value of an optional SAM-style field.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TagValue {
+    /// A single printable character (`A`).
+    Char(char),
+    /// A signed integer (`i`).
+    Int(i64),
+    /// A floating point number (`f`).
+    Float(f64),
+    /// A printable string (`Z`).
+    String(String),
+    /// A byte array (`H`), written as pairs of hexadecimal digits.
+    Hex(Vec<u8>),
+    /// A numeric array (`B`).
+    Array(TagArray),
+}
+
+
+/// The elements of a numeric array tag, by SAM element type.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TagArray {
+    /// Signed 8 bit integers (`c`).
+    Int8(Vec<i8>),
+    /// Unsigned 8 bit integers (`C`).
+    UInt8(Vec<u8>),
+    /// Signed 16 bit integers (`s`).
+    Int16(Vec<i16>),
+    /// Unsigned 16 bit integers (`S`).
+    UInt16(Vec<u16>),
+    /// Signed 32 bit integers (`i`).
+    Int32(Vec<i32>),
+    /// Unsigned 32 bit integers (`I`).
+    UInt32(Vec<u32>),
+    /// Single precision floats (`f`).
+    Float(Vec<f32>),
+}
+
+
+impl TagArray {
+    /// The SAM element type character for this array.
+    pub fn subtype(&self) -> char {
+        match self {
+            TagArray::Int8(_) => 'c',
+            TagArray::UInt8(_) => 'C',
+            TagArray::Int16(_) => 's',
+            TagArray::UInt16(_) => 'S',
+            TagArray::Int32(_) => 'i',
+            TagArray::UInt32(_) => 'I',
+            TagArray::Float(_) => 'f',
+        }
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> usize {
+        match self {
+            TagArray::Int8(v) => v.len(),
+            TagArray::UInt8(v) => v.len(),
+            TagArray::Int16(v) => v.len(),
+            TagArray::UInt16(v) => v.len(),
+            TagArray::Int32(v) => v.len(),
+            TagArray::UInt32(v) => v.len(),
+            TagArray::Float(v) => v.len(),
+        }
+    }
+
+    /// Whether the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The elements as integers, or `None` for a float array.
+    pub fn to_ints(&self) -> Option<Vec<i64>> {
+        fn widen<T: Copy + Into<i64>>(v: &[T]) -> Vec<i64> {
+            v.iter().map(|&x| x.into()).collect()
+        }
+
+        let ints = match self {
+            TagArray::Int8(v) => widen(v),
+            TagArray::UInt8(v) => widen(v),
+            TagArray::Int16(v) => widen(v),
+            TagArray::UInt16(v) => widen(v),
+            TagArray::Int32(v) => widen(v),
+            TagArray::UInt32(v) => widen(v),
+            TagArray::Float(_) => return None,
+        };
+        Some(ints)
+    }
+
+    /// The elements as floats, converting integers.
+    pub fn to_floats(&self) -> Vec<f64> {
+        match self {
+            TagArray::Float(v) => v.iter().map(|&x| x.into()).collect(),
+            _ => self.to_ints().unwrap_or_default().into_iter().map(|x| x as f64).collect(),
+        }
+    }
+}
+
+
+impl fmt::Display for TagArray {
+    /// Formats as the element type followed by comma separated values, e.g. `c,1,-2`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn values<T: fmt::Display>(f: &mut fmt::Formatter<'_>, v: &[T]) -> fmt::Result {
+            v.iter().try_for_each(|x| write!(f, ",{}", x))
+        }
+
+        write!(f, "{}", self.subtype())?;
+        match self {
+            TagArray::Int8(v) => values(f, v),
+            TagArray::UInt8(v) => values(f, v),
+            TagArray::Int16(v) => values(f, v),
+            TagArray::UInt16(v) => values(f, v),
+            TagArray::Int32(v) => values(f, v),
+            TagArray::UInt32(v) => values(f, v),
+            TagArray::Float(v) => values(f, v),
+        }
+    }
+}
+
+
+impl TagValue {
+    /// The SAM type character for this value.
+    pub fn type_char(&self) -> char {
+        match self {
+            TagValue::Char(_) => 'A',
+            TagValue::Int(_) => 'i',
+            TagValue::Float(_) => 'f',
+            TagValue::String(_) => 'Z',
+            TagValue::Hex(_) => 'H',
+            TagValue::Array(_) => 'B',
+        }
+    }
+
+    /// Get the value if it is a character.
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            TagValue::Char(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it is an integer.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            TagValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a float, converting integers.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            TagValue::Float(f) => Some(*f),
+            TagValue::Int(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TagValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it is a byte array.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            TagValue::Hex(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it is a numeric array.
+    pub fn as_array(&self) -> Option<&TagArray> {
+        match self {
+            TagValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+
+impl fmt::Display for TagValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagValue::Char(c) => write!(f, "{}", c),
+            TagValue::Int(i) => write!(f, "{}", i),
+            TagValue::Float(v) => write!(f, "{}", v),
+            TagValue::String(s) => write!(f, "{}", s),
+            TagValue::Hex(b) => b.iter().try_for_each(|x| write!(f, "{:02X}", x)),
+            TagValue::Array(a) => write!(f, "{}", a),
+        }
+    }
+}
+
+
+/// Whether a raw optional field is a tag called `name`.
+pub(crate) fn is_tag(field: &str, name: &str) -> bool {
+    field.len() > name.len()
+        && field.starts_with(name)
+        && field.as_bytes()[name.len()] == b':'
+}
+
+
+/// An optional SAM-style field, e.g. `NM:i:12`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tag {
+    name: String,
+    value: TagValue,
+}
+
+
+impl Tag {
+    /// Construct a new `Tag` object.
+    pub fn new(name: String, value: TagValue) -> Self {
+        Tag { name, value }
+    }
+
+    /// The two character tag name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The typed value.
+    pub fn value(&self) -> &TagValue {
+        &self.value
+    }
+
+    /// Consume the tag, returning the typed value.
+    pub fn into_value(self) -> TagValue {
+        self.value
+    }
+}
+
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.name, self.value.type_char(), self.value)
+    }
+}
+
+
+impl FromStr for Tag {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, tag) = all_consuming(cut(crate::complete::tag_str))(s)
+            .map_err(|e| {
+                match e {
+                    nom::Err::Error(ei) | nom::Err::Failure(ei) => {
+                        crate::errors::convert_error_str(s, ei, None)
+                    },
+                    _ => unreachable!(),
+                }
+            })?;
+
+        Ok(tag)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_parse() {
+        assert_eq!(
+            "tp:A:P".parse::<Tag>().unwrap(),
+            Tag::new("tp".to_string(), TagValue::Char('P'))
+        );
+        assert_eq!(
+            "NM:i:-12".parse::<Tag>().unwrap(),
+            Tag::new("NM".to_string(), TagValue::Int(-12))
+        );
+        assert_eq!(
+            "de:f:0.0125".parse::<Tag>().unwrap(),
+            Tag::new("de".to_string(), TagValue::Float(0.0125))
+        );
+        assert_eq!(
+            "cg:Z:10M2I5M".parse::<Tag>().unwrap(),
+            Tag::new("cg".to_string(), TagValue::String("10M2I5M".to_string()))
+        );
+
+        assert!("NM:i:twelve".parse::<Tag>().is_err());
+        assert!("NM:q:12".parse::<Tag>().is_err());
+        assert!("tp:A:PP".parse::<Tag>().is_err());
+
+        assert_eq!(
+            "XH:H:1ae3".parse::<Tag>().unwrap().value().as_bytes(),
+            Some(&[0x1a, 0xe3][..])
+        );
+        assert_eq!(
+            "XB:B:s,-3,200".parse::<Tag>().unwrap().into_value(),
+            TagValue::Array(TagArray::Int16(vec![-3, 200]))
+        );
+        let floats = "XB:B:f,0.5,-1e3".parse::<Tag>().unwrap().into_value();
+        assert_eq!(floats.as_array().unwrap().to_floats(), vec![0.5, -1000.0]);
+        assert_eq!(floats.as_array().unwrap().to_ints(), None);
+
+        assert!("XH:H:1AE".parse::<Tag>().is_err());
+        assert!("XH:H:1G".parse::<Tag>().is_err());
+        assert!("XB:B:c,128".parse::<Tag>().is_err());
+        assert!("XB:B:C,-1".parse::<Tag>().is_err());
+        assert!("XB:B:q,1".parse::<Tag>().is_err());
+        assert!("XB:B:i,1,".parse::<Tag>().is_err());
+    }
+
+    #[test]
+    fn test_tag_round_trip() {
+        for s in &[
+            "tp:A:P", "NM:i:12", "de:f:0.0125", "cs:Z::10*ag:5", "XH:H:1AE3", "XH:H:",
+            "XB:B:c,-128,127", "XB:B:C", "XB:B:I,4294967295", "XB:B:f,0.1,2,-3.5",
+        ] {
+            assert_eq!(&s.parse::<Tag>().unwrap().to_string(), s);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Generators of valid random records, for property testing and fuzzing.
+//!
+//! With the `testing` feature, [`random_record`] draws records from any
+//! `rand` generator. With the `arbitrary` feature, `PAF` implements
+//! `arbitrary::Arbitrary` using the same generator.
+//!
+//! Generated records have coordinates within their sequence lengths and an
+//! extended `cg` tag consistent with the aligned spans, matching count, and
+//! alignment length, alongside the usual minimap2 tags.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::cigar::{Cigar, CigarKind, CigarOp};
+use crate::fields::Fields;
+use crate::paf::{Locus, Strand, PAF};
+
+
+/// A source of uniformly distributed integers.
+trait Source {
+    /// An integer in `0..n`. `n` must be at least 1.
+    fn below(&mut self, n: u64) -> u64;
+}
+
+
+/// Build a random CIGAR of `=` blocks separated by mismatches and indels.
+fn random_cigar<S: Source>(source: &mut S) -> Cigar {
+    let nblocks = 1 + source.below(8);
+    let mut ops = Vec::new();
+
+    for i in 0..nblocks {
+        ops.push(CigarOp::new(CigarKind::Equal, 1 + source.below(200)));
+        if i + 1 < nblocks {
+            let kind = match source.below(3) {
+                0 => CigarKind::Diff,
+                1 => CigarKind::Insertion,
+                _ => CigarKind::Deletion,
+            };
+            ops.push(CigarOp::new(kind, 1 + source.below(10)));
+        }
+    }
+
+    Cigar::new(ops)
+}
+
+
+/// Place an aligned span of `span` bases on a sequence at least that long.
+fn random_locus<S: Source>(source: &mut S, prefix: char, span: u64) -> Locus {
+    let name = format!("{}{}", prefix, source.below(100));
+    let length = span + source.below(10_000);
+    let start = source.below(length - span + 1);
+    Locus::new(name, length, start, start + span)
+}
+
+
+fn random_paf<S: Source>(source: &mut S) -> PAF {
+    let cigar = random_cigar(source);
+
+    let (mut nmatch, mut alnlen, mut edits) = (0, 0, 0);
+    for op in cigar.ops() {
+        alnlen += op.len();
+        match op.kind() {
+            CigarKind::Equal => nmatch += op.len(),
+            _ => edits += op.len(),
+        }
+    }
+
+    // At most 8 short blocks, so the spans are small.
+    let query = random_locus(source, 'q', cigar.query_len().unwrap_or(0));
+    let target = random_locus(source, 't', cigar.target_len().unwrap_or(0));
+    let strand = if source.below(2) == 0 { Strand::Plus } else { Strand::Minus };
+    let primary = source.below(4) != 0;
+    let mapq = if primary { source.below(61) as u8 } else { 0 };
+
+    let s1 = nmatch - edits.min(nmatch);
+    let mut fields = Fields::new();
+    fields.push(&format!("NM:i:{}", edits));
+    fields.push(if primary { "tp:A:P" } else { "tp:A:S" });
+    fields.push(&format!("cm:i:{}", nmatch / 20));
+    fields.push(&format!("s1:i:{}", s1));
+    if primary {
+        fields.push(&format!("s2:i:{}", source.below(s1 + 1)));
+    }
+    fields.push(&format!("dv:f:{:.4}", edits as f64 / alnlen as f64));
+    fields.push(&format!("cg:Z:{}", cigar));
+
+    PAF::new(query, strand, target, nmatch, alnlen, mapq, fields)
+}
+
+
+#[cfg(feature = "testing")]
+struct RngSource<'a, R: ?Sized>(&'a mut R);
+
+
+#[cfg(feature = "testing")]
+impl<R: rand::Rng + ?Sized> Source for RngSource<'_, R> {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0.random_range(0..n)
+    }
+}
+
+
+/// Generate a valid random record, including tags and a `cg` CIGAR.
+#[cfg(feature = "testing")]
+pub fn random_record<R: rand::Rng + ?Sized>(rng: &mut R) -> PAF {
+    random_paf(&mut RngSource(rng))
+}
+
+
+#[cfg(feature = "arbitrary")]
+impl Source for arbitrary::Unstructured<'_> {
+    fn below(&mut self, n: u64) -> u64 {
+        // Only fails for an empty range, and yields 0 once data runs out.
+        self.int_in_range(0..=n - 1).unwrap_or(0)
+    }
+}
+
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PAF {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(random_paf(u))
+    }
+}
+
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    fn test_random_record_round_trip() {
+        let mut rng = SmallRng::seed_from_u64(42);
+
+        for _ in 0..200 {
+            let record = random_record(&mut rng);
+            record.query().validate().unwrap();
+            record.target().validate().unwrap();
+
+            let cigar = record.cigar().unwrap();
+            assert_eq!(cigar.query_len().unwrap(), record.query().end() - record.query().start());
+            assert_eq!(cigar.target_len().unwrap(), record.target().end() - record.target().start());
+
+            let parsed: PAF = record.to_string().parse().unwrap();
+            assert_eq!(parsed, record);
+        }
+    }
+}
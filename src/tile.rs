@@ -0,0 +1,124 @@
+//! Choosing the best non-overlapping tiling of alignments along each target.
+
+use std::collections::BTreeMap;
+
+use crate::paf::PAF;
+
+
+/// Select the highest scoring set of records that do not overlap on the
+/// target, independently for each target.
+///
+/// This is weighted interval scheduling with [`PAF::score`] as the weight,
+/// so records with a score of zero or less are never chosen. Records may
+/// overlap by up to `max_overlap` bases, to allow for alignments that
+/// extend slightly past a junction. Unlike [`trim_overlaps`], records are
+/// kept whole or dropped, never trimmed.
+///
+/// The limit holds between every pair of chosen records, not only
+/// neighbours: a record only follows records that end at most
+/// `max_overlap` bases past its start, so it overlaps each of them by no
+/// more than that. This is stricter than needed for a short record nested
+/// inside a longer one, which is only chosen with it if it ends within
+/// `max_overlap` bases of the longer record's start.
+///
+/// The chosen records are returned sorted by target name and start.
+///
+/// [`trim_overlaps`]: crate::trim::trim_overlaps
+pub fn best_tiling(records: &[PAF], max_overlap: u64) -> Vec<PAF> {
+    let mut by_target: BTreeMap<&str, Vec<&PAF>> = BTreeMap::new();
+    for record in records.iter().filter(|r| r.score() > 0) {
+        by_target.entry(record.target().name()).or_default().push(record);
+    }
+
+    let mut output = Vec::new();
+    for (_, mut group) in by_target {
+        group.sort_by_key(|r| (r.target().end(), r.target().start()));
+        let ends: Vec<u64> = group.iter().map(|r| r.target().end()).collect();
+
+        // best[i] is the highest score using only the first i records.
+        let mut best: Vec<i64> = vec![0; group.len() + 1];
+        let mut previous: Vec<usize> = Vec::with_capacity(group.len());
+        for (i, record) in group.iter().enumerate() {
+            let limit = record.target().start().saturating_add(max_overlap);
+            let p = ends[..i].partition_point(|&e| e <= limit);
+            previous.push(p);
+            best[i + 1] = best[i].max(best[p] + record.score());
+        }
+
+        let mut chosen = Vec::new();
+        let mut i = group.len();
+        while i > 0 {
+            if best[i] == best[i - 1] {
+                i -= 1;
+            } else {
+                chosen.push(group[i - 1].clone());
+                i = previous[i - 1];
+            }
+        }
+
+        chosen.sort_by_key(|r| (r.target().start(), r.target().end()));
+        output.extend(chosen);
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_best_tiling() {
+        let records = vec![
+            paf("q1\t1000\t0\t500\t+\tt1\t2000\t0\t500\t450\t500\t60"),
+            paf("q2\t1000\t0\t600\t+\tt1\t2000\t300\t900\t600\t600\t60"),
+            paf("q3\t1000\t0\t520\t+\tt1\t2000\t490\t1010\t400\t520\t60"),
+            paf("q4\t1000\t0\t100\t+\tt2\t2000\t0\t100\t90\t100\t60"),
+            paf("q5\t1000\t0\t100\t+\tt2\t2000\t50\t150\t80\t100\t60\tAS:i:-10"),
+        ];
+
+        let names = |records: &[PAF]| -> Vec<String> {
+            records.iter().map(|r| r.query().name().to_string()).collect()
+        };
+        assert_eq!(names(&best_tiling(&records, 0)), vec!["q2", "q4"]);
+        assert_eq!(names(&best_tiling(&records, 10)), vec!["q1", "q3", "q4"]);
+    }
+
+    #[test]
+    fn test_best_tiling_pairwise_overlap() {
+        // Nested and chained records, checked against every chosen pair.
+        let mut state = 7u64;
+        let mut next = |n: u64| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) % n
+        };
+
+        for _ in 0..200 {
+            let records: Vec<PAF> = (0..12)
+                .map(|i| {
+                    let start = next(200);
+                    let end = start + 1 + next(60);
+                    paf(&format!(
+                        "q{}\t100\t0\t10\t+\tt1\t300\t{}\t{}\t{}\t10\t60",
+                        i, start, end, 1 + next(50)
+                    ))
+                })
+                .collect();
+
+            for max_overlap in &[0, 5, 20] {
+                let chosen = best_tiling(&records, *max_overlap);
+                for (i, a) in chosen.iter().enumerate() {
+                    for b in &chosen[i + 1..] {
+                        let overlap = a.target().end().min(b.target().end())
+                            .saturating_sub(a.target().start().max(b.target().start()));
+                        assert!(overlap <= *max_overlap, "{} and {} overlap by {}", a, b, overlap);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Trimming records that overlap on the target, so no target base is
+//! counted twice.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::paf::PAF;
+
+
+/// Trim records so that no two overlap on the target.
+///
+/// Where records overlap, the one with the higher [`PAF::identity`] keeps
+/// the overlapping region and the other is trimmed with
+/// [`PAF::slice_target`]. Ties go to the longer alignment, then the
+/// earlier record. A record whose middle is covered by a better one is
+/// split into the pieces either side, and a record covered entirely is
+/// removed.
+///
+/// Records without a `cg` tag cannot be trimmed; they are kept whole and
+/// trim the records they overlap as usual. The output is sorted by target
+/// name and start.
+pub fn trim_overlaps(records: &[PAF]) -> Vec<PAF> {
+    let mut order: Vec<usize> = (0..records.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (ra, rb) = (&records[a], &records[b]);
+        rb.identity()
+            .partial_cmp(&ra.identity())
+            .unwrap_or(Ordering::Equal)
+            .then(rb.alnlen().cmp(&ra.alnlen()))
+            .then(a.cmp(&b))
+    });
+
+    let mut claimed: HashMap<&str, Vec<(u64, u64)>> = HashMap::new();
+    let mut output = Vec::with_capacity(records.len());
+
+    for i in order {
+        let record = &records[i];
+        let target = record.target();
+        let spans = claimed.entry(target.name()).or_default();
+        let free = unclaimed(spans, target.start(), target.end());
+
+        if free == [(target.start(), target.end())] || record.cigar().is_none() {
+            output.push(record.clone());
+        } else {
+            output.extend(free.iter().filter_map(|&(s, e)| record.slice_target(s, e)));
+        }
+
+        claim(spans, target.start(), target.end());
+    }
+
+    output.sort_by(|a, b| {
+        a.target().name().cmp(b.target().name())
+            .then(a.target().start().cmp(&b.target().start()))
+            .then(a.target().end().cmp(&b.target().end()))
+    });
+    output
+}
+
+
+/// The parts of `[start, end)` not covered by the sorted, disjoint spans.
+fn unclaimed(spans: &[(u64, u64)], start: u64, end: u64) -> Vec<(u64, u64)> {
+    let mut free = Vec::new();
+    let mut pos = start;
+
+    let first = spans.partition_point(|s| s.1 <= start);
+    for &(s, e) in &spans[first..] {
+        if s >= end {
+            break;
+        }
+        if s > pos {
+            free.push((pos, s));
+        }
+        pos = pos.max(e);
+    }
+
+    if pos < end {
+        free.push((pos, end));
+    }
+    free
+}
+
+
+/// Add `[start, end)` to the sorted, disjoint spans, merging as needed.
+fn claim(spans: &mut Vec<(u64, u64)>, start: u64, end: u64) {
+    if start >= end {
+        return;
+    }
+
+    let first = spans.partition_point(|s| s.1 < start);
+    let last = spans.partition_point(|s| s.0 <= end);
+
+    let (mut new_start, mut new_end) = (start, end);
+    if first < last {
+        new_start = new_start.min(spans[first].0);
+        new_end = new_end.max(spans[last - 1].1);
+    }
+    spans.splice(first..last, std::iter::once((new_start, new_end)));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_trim_overlaps() {
+        let records = vec![
+            paf("q1\t100\t0\t100\t+\tt1\t1000\t0\t100\t90\t100\t60\tcg:Z:100M"),
+            paf("q2\t100\t0\t100\t+\tt1\t1000\t50\t150\t99\t100\t60\tcg:Z:100M"),
+            paf("q3\t200\t0\t200\t+\tt1\t1000\t100\t300\t150\t200\t60\tcg:Z:200M"),
+            paf("q4\t20\t0\t20\t+\tt1\t1000\t200\t220\t20\t20\t60\tcg:Z:20M"),
+            paf("q5\t20\t0\t20\t+\tt1\t1000\t60\t80\t10\t20\t60\tcg:Z:20M"),
+        ];
+
+        let trimmed = trim_overlaps(&records);
+        let spans: Vec<(&str, u64, u64, u64, u64)> = trimmed
+            .iter()
+            .map(|r| (r.query().name(), r.query().start(), r.query().end(), r.target().start(), r.target().end()))
+            .collect();
+
+        assert_eq!(spans, vec![
+            ("q1", 0, 50, 0, 50),
+            ("q2", 0, 100, 50, 150),
+            ("q3", 50, 100, 150, 200),
+            ("q4", 0, 20, 200, 220),
+            ("q3", 120, 200, 220, 300),
+        ]);
+    }
+
+    #[test]
+    fn test_claim() {
+        let mut spans = vec![(0, 10), (20, 30), (40, 50)];
+        assert_eq!(unclaimed(&spans, 5, 45), vec![(10, 20), (30, 40)]);
+
+        claim(&mut spans, 10, 25);
+        assert_eq!(spans, vec![(0, 30), (40, 50)]);
+        claim(&mut spans, 60, 70);
+        assert_eq!(spans, vec![(0, 30), (40, 50), (60, 70)]);
+    }
+}
@@ -0,0 +1,193 @@
+//! Validation of optional fields against SAM and minimap2 tag conventions.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::cigar::Cigar;
+use crate::cs::Cs;
+use crate::paf::PAF;
+use crate::tags::{Tag, TagValue};
+
+
+/// A tag known from the SAM specification or minimap2 output.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct KnownTag {
+    /// The two character tag name.
+    pub name: &'static str,
+    /// The expected SAM type character.
+    pub kind: char,
+    /// A short description of the tag.
+    pub description: &'static str,
+}
+
+
+/// The tags written by minimap2, and the SAM tags it shares.
+pub const MINIMAP2_TAGS: &[KnownTag] = &[
+    KnownTag { name: "tp", kind: 'A', description: "type of alignment" },
+    KnownTag { name: "cm", kind: 'i', description: "number of minimizers on the chain" },
+    KnownTag { name: "s1", kind: 'i', description: "chaining score" },
+    KnownTag { name: "s2", kind: 'i', description: "best secondary chaining score" },
+    KnownTag { name: "NM", kind: 'i', description: "total mismatches and gaps" },
+    KnownTag { name: "MD", kind: 'Z', description: "reference mismatch string" },
+    KnownTag { name: "AS", kind: 'i', description: "DP alignment score" },
+    KnownTag { name: "SA", kind: 'Z', description: "supplementary alignments" },
+    KnownTag { name: "ms", kind: 'i', description: "DP score of the max scoring segment" },
+    KnownTag { name: "nn", kind: 'i', description: "number of ambiguous bases" },
+    KnownTag { name: "ts", kind: 'A', description: "transcript strand" },
+    KnownTag { name: "cg", kind: 'Z', description: "CIGAR string" },
+    KnownTag { name: "cs", kind: 'Z', description: "difference string" },
+    KnownTag { name: "dv", kind: 'f', description: "approximate per-base divergence" },
+    KnownTag { name: "de", kind: 'f', description: "gap-compressed per-base divergence" },
+    KnownTag { name: "rl", kind: 'i', description: "length of repetitive seeds" },
+];
+
+
+/// Look up a tag in [`MINIMAP2_TAGS`].
+pub fn known_tag(name: &str) -> Option<&'static KnownTag> {
+    MINIMAP2_TAGS.iter().find(|t| t.name == name)
+}
+
+
+/// A problem found in the optional fields of a record.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TagWarning {
+    /// The field is not a well formed `NN:T:value` tag, or its value does
+    /// not match its declared type.
+    Malformed { field: String },
+    /// The tag is known but declared with the wrong type.
+    WrongType { name: String, expected: char, got: char },
+    /// The value is well typed but outside the allowed values.
+    InvalidValue { name: String, value: String, reason: &'static str },
+    /// The tag isn't in the registry of known tags.
+    Unknown { name: String },
+    /// The tag appears more than once.
+    Duplicate { name: String },
+}
+
+
+impl fmt::Display for TagWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagWarning::Malformed { field } => write!(f, "Malformed tag '{}'.", field),
+            TagWarning::WrongType { name, expected, got } => write!(
+                f,
+                "Tag '{}' should have type '{}' but has type '{}'.",
+                name, expected, got
+            ),
+            TagWarning::InvalidValue { name, value, reason } => write!(
+                f,
+                "Tag '{}' has invalid value '{}': {}.",
+                name, value, reason
+            ),
+            TagWarning::Unknown { name } => write!(f, "Unknown tag '{}'.", name),
+            TagWarning::Duplicate { name } => write!(f, "Tag '{}' appears more than once.", name),
+        }
+    }
+}
+
+
+/// Check the optional fields of a record against [`MINIMAP2_TAGS`].
+///
+/// Returns every problem found, in field order. An empty result means the
+/// fields are all valid.
+pub fn validate(record: &PAF) -> Vec<TagWarning> {
+    let mut warnings = Vec::new();
+    let mut seen = HashSet::new();
+
+    for field in record.fields() {
+        let tag: Tag = match field.parse() {
+            Ok(t) => t,
+            Err(_) => {
+                warnings.push(TagWarning::Malformed { field: field.to_string() });
+                continue;
+            },
+        };
+
+        if !seen.insert(tag.name().to_string()) {
+            warnings.push(TagWarning::Duplicate { name: tag.name().to_string() });
+        }
+
+        let known = match known_tag(tag.name()) {
+            Some(k) => k,
+            None => {
+                warnings.push(TagWarning::Unknown { name: tag.name().to_string() });
+                continue;
+            },
+        };
+
+        let got = tag.value().type_char();
+        if got != known.kind {
+            warnings.push(TagWarning::WrongType { name: tag.name().to_string(), expected: known.kind, got });
+            continue;
+        }
+
+        if let Some(reason) = check_value(tag.name(), tag.value()) {
+            warnings.push(TagWarning::InvalidValue {
+                name: tag.name().to_string(),
+                value: tag.value().to_string(),
+                reason,
+            });
+        }
+    }
+
+    warnings
+}
+
+
+/// Check the value of a known, correctly typed tag.
+fn check_value(name: &str, value: &TagValue) -> Option<&'static str> {
+    match (name, value) {
+        ("tp", TagValue::Char(c)) if !"PSIi".contains(*c) => Some("expected one of 'P', 'S', 'I' or 'i'"),
+        ("ts", TagValue::Char(c)) if !"+-".contains(*c) => Some("expected '+' or '-'"),
+        ("dv", TagValue::Float(v)) | ("de", TagValue::Float(v)) if !(0.0..=1.0).contains(v) => {
+            Some("expected a value between 0 and 1")
+        },
+        ("cm", TagValue::Int(i)) | ("NM", TagValue::Int(i)) | ("nn", TagValue::Int(i))
+        | ("rl", TagValue::Int(i)) if *i < 0 => Some("expected a non-negative integer"),
+        ("cg", TagValue::String(s)) if s.parse::<Cigar>().is_err() => Some("expected a CIGAR string"),
+        ("cs", TagValue::String(s)) if s.parse::<Cs>().is_err() => Some("expected a cs difference string"),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let record = paf("q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P\tNM:i:0\tcg:Z:10M\tde:f:0.01");
+        assert!(validate(&record).is_empty());
+
+        let record = paf(
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\t\
+             NM:f:1.5\tde:f:1.5\txx:i:1\ttp:A:X\ttp:A:P\tcg:Z:10Q\tNM:i:x"
+        );
+        assert_eq!(validate(&record), vec![
+            TagWarning::WrongType { name: "NM".to_string(), expected: 'i', got: 'f' },
+            TagWarning::InvalidValue {
+                name: "de".to_string(),
+                value: "1.5".to_string(),
+                reason: "expected a value between 0 and 1",
+            },
+            TagWarning::Unknown { name: "xx".to_string() },
+            TagWarning::InvalidValue {
+                name: "tp".to_string(),
+                value: "X".to_string(),
+                reason: "expected one of 'P', 'S', 'I' or 'i'",
+            },
+            TagWarning::Duplicate { name: "tp".to_string() },
+            TagWarning::InvalidValue {
+                name: "cg".to_string(),
+                value: "10Q".to_string(),
+                reason: "expected a CIGAR string",
+            },
+            TagWarning::Malformed { field: "NM:i:x".to_string() },
+        ]);
+    }
+}
@@ -0,0 +1,209 @@
+//! Identity in fixed-size windows along the targets.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use snafu::ResultExt;
+
+use crate::cigar::{Cigar, CigarKind};
+use crate::errors::{Error, Io};
+use crate::export::json_string;
+use crate::paf::{Locus, PAF};
+use crate::stats::TableFormat;
+
+
+/// Base-level alignment counts for one target window.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WindowIdentity {
+    /// The target name.
+    pub target: String,
+    /// The start of the window.
+    pub start: u64,
+    /// The end of the window, clamped to the target length.
+    pub end: u64,
+    /// The number of records aligned within the window.
+    pub records: u64,
+    /// Identical bases.
+    pub matches: u64,
+    /// Substituted bases.
+    pub mismatches: u64,
+    /// Inserted query bases, placed at the target position they follow.
+    pub insertions: u64,
+    /// Deleted target bases.
+    pub deletions: u64,
+}
+
+
+impl WindowIdentity {
+    /// Matches over all aligned columns, including gaps.
+    pub fn identity(&self) -> f64 {
+        let columns = self.matches + self.mismatches + self.insertions + self.deletions;
+        if columns == 0 {
+            0.0
+        } else {
+            self.matches as f64 / columns as f64
+        }
+    }
+
+    /// One minus the identity.
+    pub fn divergence(&self) -> f64 {
+        1.0 - self.identity()
+    }
+}
+
+
+/// The alignment of a record with matches and mismatches distinguished.
+///
+/// Uses the `cs` tag if present, else a `cg` tag without `M` operations.
+fn extended_cigar(record: &PAF) -> Option<Cigar> {
+    if let Some(cs) = record.cs() {
+        return Some(cs.to_cigar(true));
+    }
+
+    record.cigar().filter(|c| c.ops().iter().all(|o| o.kind() != CigarKind::Match))
+}
+
+
+/// The window containing `pos`, created if needed.
+///
+/// Insertions at the very end of the target are placed in the last window.
+fn window_at<'a, 'b>(
+    windows: &'b mut BTreeMap<(&'a str, u64), WindowIdentity>,
+    target: &'a Locus,
+    width: u64,
+    pos: u64,
+) -> &'b mut WindowIdentity {
+    let index = (pos / width).min(target.length().saturating_sub(1) / width);
+    windows.entry((target.name(), index)).or_insert_with(|| WindowIdentity {
+        target: target.name().to_string(),
+        start: index * width,
+        end: ((index + 1) * width).min(target.length()),
+        records: 0,
+        matches: 0,
+        mismatches: 0,
+        insertions: 0,
+        deletions: 0,
+    })
+}
+
+
+/// Compute identity in windows of `width` target bases.
+///
+/// Counts are summed over all records overlapping a window. Records
+/// without a `cs` tag or an extended `cg` tag are skipped. Only windows
+/// with at least one aligned base are returned, sorted by target name then
+/// position.
+///
+/// # Panics
+///
+/// Panics if `width` is 0.
+pub fn windowed_identity(records: &[PAF], width: u64) -> Vec<WindowIdentity> {
+    assert!(width > 0, "window width must be at least 1");
+
+    let mut windows: BTreeMap<(&str, u64), WindowIdentity> = BTreeMap::new();
+
+    for record in records {
+        let cigar = match extended_cigar(record) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let target = record.target();
+        let mut pos = target.start();
+        let mut seen = None;
+        for op in cigar.ops() {
+            if op.kind() == CigarKind::Insertion {
+                window_at(&mut windows, target, width, pos).insertions += op.len();
+                continue;
+            }
+            if !op.kind().consumes_target() {
+                continue;
+            }
+
+            let end = pos + op.len();
+            while pos < end {
+                let index = pos / width;
+                let next = ((index + 1) * width).min(end);
+                let window = window_at(&mut windows, target, width, pos);
+                match op.kind() {
+                    CigarKind::Equal => window.matches += next - pos,
+                    CigarKind::Diff => window.mismatches += next - pos,
+                    CigarKind::Deletion => window.deletions += next - pos,
+                    _ => {},
+                }
+                if seen != Some(index) {
+                    window.records += 1;
+                    seen = Some(index);
+                }
+                pos = next;
+            }
+        }
+    }
+
+    windows.into_values().collect()
+}
+
+
+/// Write windowed identity as a TSV table with a header, or JSON lines.
+pub fn write_windowed_identity<W: Write>(
+    windows: &[WindowIdentity],
+    format: TableFormat,
+    mut writer: W,
+) -> Result<(), Error> {
+    if format == TableFormat::Tsv {
+        writeln!(
+            writer,
+            "target\tstart\tend\trecords\tmatches\tmismatches\tinsertions\tdeletions\tidentity"
+        ).context(Io)?;
+    }
+
+    for w in windows {
+        let line = match format {
+            TableFormat::Tsv => format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                w.target, w.start, w.end, w.records, w.matches,
+                w.mismatches, w.insertions, w.deletions, w.identity()
+            ),
+            TableFormat::Json => format!(
+                "{{\"target\":{},\"start\":{},\"end\":{},\"records\":{},\"matches\":{},\
+                 \"mismatches\":{},\"insertions\":{},\"deletions\":{},\"identity\":{}}}",
+                json_string(&w.target), w.start, w.end, w.records, w.matches,
+                w.mismatches, w.insertions, w.deletions, w.identity()
+            ),
+        };
+        writeln!(writer, "{}", line).context(Io)?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paf(line: &str) -> PAF {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_windowed_identity() {
+        let records = vec![
+            paf("q1\t100\t0\t18\t+\tt1\t25\t0\t19\t16\t20\t60\tcg:Z:8=1X5=1I3=2D"),
+            paf("q2\t100\t0\t10\t+\tt1\t25\t5\t15\t10\t10\t60\tcs:Z::10"),
+            paf("q3\t100\t0\t10\t+\tt1\t25\t0\t10\t10\t10\t60\tcg:Z:10M"),
+        ];
+
+        let windows = windowed_identity(&records, 10);
+        assert_eq!(windows.len(), 2);
+
+        let w = &windows[0];
+        assert_eq!((w.start, w.end, w.records), (0, 10, 2));
+        assert_eq!((w.matches, w.mismatches, w.insertions, w.deletions), (14, 1, 0, 0));
+
+        let w = &windows[1];
+        assert_eq!((w.start, w.end, w.records), (10, 20, 2));
+        assert_eq!((w.matches, w.mismatches, w.insertions, w.deletions), (12, 0, 1, 2));
+        assert!((w.identity() - 12.0 / 15.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,222 @@
+use std::borrow::Cow;
+use std::io::Write;
+
+use snafu::ResultExt;
+
+use crate::errors::{Error, Io};
+use crate::escape::escape_name;
+use crate::record::PafRecord;
+
+
+/// Which optional fields a [`PafWriter`] emits.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub enum TagSelection {
+    /// Every optional field.
+    #[default]
+    All,
+    /// Only tags with these names. Fields that are not tags are dropped.
+    Only(Vec<String>),
+    /// Every field except tags with these names, e.g. `cg` and `cs`.
+    Except(Vec<String>),
+}
+
+
+impl TagSelection {
+    /// Whether a raw optional field is selected.
+    fn selects(&self, field: &str) -> bool {
+        match self {
+            TagSelection::All => true,
+            TagSelection::Only(names) => tag_name(field).is_some_and(|n| names.iter().any(|t| t == n)),
+            TagSelection::Except(names) => tag_name(field).is_none_or(|n| names.iter().all(|t| t != n)),
+        }
+    }
+}
+
+
+/// The name of a raw optional field if it looks like a tag, e.g. `NM:i:1`.
+fn tag_name(field: &str) -> Option<&str> {
+    let bytes = field.as_bytes();
+    if bytes.len() >= 5 && bytes[2] == b':' && bytes[4] == b':' {
+        field.get(..2)
+    } else {
+        None
+    }
+}
+
+
+/// Write PAF records line-by-line to a sink.
+pub struct PafWriter<W: Write> {
+    inner: W,
+    tags: TagSelection,
+    sort_tags: bool,
+    escape_names: bool,
+}
+
+
+impl<W: Write> PafWriter<W> {
+    /// Construct a new writer.
+    pub fn new(inner: W) -> Self {
+        PafWriter { inner, tags: TagSelection::All, sort_tags: false, escape_names: false }
+    }
+
+    /// Choose which optional fields are written. All are written by default.
+    pub fn with_tags(mut self, tags: TagSelection) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Write tags sorted by name, rather than in the order they were read.
+    ///
+    /// The sort is stable, so repeated tags keep their order, and fields
+    /// that are not tags are written after the tags.
+    pub fn with_sorted_tags(mut self, sort: bool) -> Self {
+        self.sort_tags = sort;
+        self
+    }
+
+    /// Percent escape whitespace, control characters and `%` in sequence
+    /// names, see [`crate::escape`].
+    pub fn with_name_escapes(mut self, escape: bool) -> Self {
+        self.escape_names = escape;
+        self
+    }
+
+    /// Write a single record followed by a newline.
+    ///
+    /// Any [`PafRecord`] can be written, e.g. a
+    /// [`MinimalPaf`](crate::minimal::MinimalPaf) without optional fields.
+    pub fn write_record<R: PafRecord + ?Sized>(&mut self, record: &R) -> Result<(), Error> {
+        let fields = if self.tags == TagSelection::All && !self.sort_tags {
+            Cow::Borrowed(record.optional_fields())
+        } else {
+            let mut fields: Vec<&str> = record.optional_fields()
+                .split('\t')
+                .filter(|f| !f.is_empty() && self.tags.selects(f))
+                .collect();
+            if self.sort_tags {
+                fields.sort_by_key(|f| (tag_name(f).is_none(), tag_name(f)));
+            }
+            Cow::Owned(fields.join("\t"))
+        };
+
+        if self.escape_names {
+            let query_name = escape_name(record.query_name());
+            let target_name = escape_name(record.target_name());
+            crate::serialize::write_record_with_names(
+                &mut self.inner,
+                record,
+                &query_name,
+                &target_name,
+                &fields,
+            ).context(Io)?;
+        } else {
+            crate::serialize::write_record_with_fields(&mut self.inner, record, &fields).context(Io)?;
+        }
+        self.inner.write_all(b"\n").context(Io)
+    }
+
+    /// Write a comment, which is read back by `PafReader::comments`.
+    ///
+    /// Each line of `comment` is written on its own line, with a `#`
+    /// prepended unless it already starts with one.
+    pub fn write_comment(&mut self, comment: &str) -> Result<(), Error> {
+        for line in comment.lines() {
+            if line.starts_with('#') {
+                writeln!(self.inner, "{}", line).context(Io)?;
+            } else {
+                writeln!(self.inner, "#{}", line).context(Io)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush the underlying sink.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush().context(Io)
+    }
+
+    /// Get a reference to the underlying sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consume the writer, returning the underlying sink.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::PafReader;
+
+    #[test]
+    fn test_round_trip() {
+        let input = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P\n\
+                     q2\t10\t0\t10\t-\tt1\t20\t5\t15\t9\t10\t0\n";
+
+        let mut writer = PafWriter::new(Vec::new());
+        for record in PafReader::new(input.as_bytes()) {
+            writer.write_record(&record.unwrap()).unwrap();
+        }
+
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), input);
+    }
+
+    #[test]
+    fn test_comment_round_trip() {
+        let input = "#PG minimap2\n\
+                     # second line\n\
+                     q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\n";
+
+        let mut reader = PafReader::new(input.as_bytes());
+        let header = reader.read_header().unwrap().to_vec();
+        assert_eq!(header, vec!["#PG minimap2", "# second line"]);
+
+        let mut writer = PafWriter::new(Vec::new());
+        for comment in &header {
+            writer.write_comment(comment).unwrap();
+        }
+        for record in reader {
+            writer.write_record(&record.unwrap()).unwrap();
+        }
+
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), input);
+
+        let mut writer = PafWriter::new(Vec::new());
+        writer.write_comment("a\nb").unwrap();
+        assert_eq!(writer.into_inner(), b"#a\n#b\n");
+    }
+
+    #[test]
+    fn test_tag_selection() {
+        let record: crate::paf::PAF = "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\tcs:Z::10\ttp:A:P\tcg:Z:10M\tNM:i:0"
+            .parse()
+            .unwrap();
+        let write = |writer: PafWriter<Vec<u8>>| {
+            let mut writer = writer;
+            writer.write_record(&record).unwrap();
+            String::from_utf8(writer.into_inner()).unwrap()
+        };
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+
+        assert_eq!(
+            write(PafWriter::new(Vec::new()).with_tags(TagSelection::Except(names(&["cg", "cs"])))),
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\ttp:A:P\tNM:i:0\n"
+        );
+        assert_eq!(
+            write(PafWriter::new(Vec::new()).with_tags(TagSelection::Only(names(&["NM", "cg"])))),
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\tcg:Z:10M\tNM:i:0\n"
+        );
+        assert_eq!(
+            write(PafWriter::new(Vec::new()).with_sorted_tags(true)),
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\tNM:i:0\tcg:Z:10M\tcs:Z::10\ttp:A:P\n"
+        );
+        assert_eq!(
+            write(PafWriter::new(Vec::new()).with_tags(TagSelection::Only(Vec::new()))),
+            "q1\t10\t0\t10\t+\tt1\t20\t0\t10\t10\t10\t60\n"
+        );
+    }
+}